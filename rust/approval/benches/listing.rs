@@ -0,0 +1,66 @@
+//! compares the bulk listing of tickets with the per-principal one, against an IAM endpoint with a fixed latency.
+//! run with `cargo bench -p approval --features testing`.
+
+use approval::{
+    iam::{ApprovalManager, Listing, RoleApprovalManager},
+    testing::MockIam,
+};
+use futures::TryStreamExt;
+use std::time::{Duration, Instant};
+
+const LATENCY: Duration = Duration::from_millis(5);
+const TICKET_KEY: &str = "tagctl:v1/admin/mpa/ticket";
+
+fn account(roles: usize) -> MockIam {
+    (0..roles).fold(MockIam::new().with_latency(LATENCY), |mock, i| {
+        let name = format!("role-{i:05}");
+        if i % 20 == 0 {
+            mock.with_role(
+                &name,
+                &[(TICKET_KEY, "by/alice/exp=1618033988/for/bob"), ("team", "core")],
+            )
+        } else {
+            mock.with_role(&name, &[("team", "core")])
+        }
+    })
+}
+
+async fn run(mock: &MockIam, listing: Listing) -> (Duration, usize) {
+    let manager = RoleApprovalManager::new(mock.client()).with_listing(listing);
+    let start = Instant::now();
+    let tickets: Vec<_> = manager.list_all_tickets().try_collect().await.expect("listing tickets");
+    (start.elapsed(), tickets.len())
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("tokio runtime");
+    println!(
+        "{:>6}  {:<24} {:>10} {:>8} {:>8}",
+        "roles", "listing", "elapsed", "calls", "tickets"
+    );
+    for roles in [100, 500, 2000] {
+        let scenarios = [
+            ("per principal", account(roles), Listing::PerPrincipal),
+            ("bulk", account(roles), Listing::Bulk),
+            (
+                "bulk, denied",
+                account(roles).deny("GetAccountAuthorizationDetails"),
+                Listing::Bulk,
+            ),
+        ];
+        for (name, mock, listing) in scenarios {
+            let (elapsed, tickets) = runtime.block_on(run(&mock, listing));
+            let calls: usize = ["GetAccountAuthorizationDetails", "ListRoles", "ListRoleTags"]
+                .iter()
+                .map(|action| mock.calls(action))
+                .sum();
+            println!(
+                "{roles:>6}  {name:<24} {:>8}ms {calls:>8} {tickets:>8}",
+                elapsed.as_millis()
+            );
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::OnceLock;
+
+/// the bundled snapshot of IAM actions, one `<service>:<action>` per line.
+/// refresh it from the AWS policy generator with `make iam-catalog`.
+const BUNDLED: &str = include_str!("../assets/iam_actions.txt");
+
+/// the IAM actions of the services it covers.
+/// actions of services missing from the catalog can be neither expanded nor validated.
+#[derive(Debug, Clone, Default)]
+pub struct ActionCatalog {
+    actions: Vec<String>,
+}
+
+impl ActionCatalog {
+    /// parses a catalog of `<service>:<action>` lines, skipping blank lines and `#` comments
+    pub fn parse(s: &str) -> Self {
+        let actions = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        Self { actions }
+    }
+
+    /// the catalog bundled with tagctl
+    pub fn bundled() -> &'static Self {
+        static CATALOG: OnceLock<ActionCatalog> = OnceLock::new();
+        CATALOG.get_or_init(|| Self::parse(BUNDLED))
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.iter().map(String::as_str)
+    }
+
+    /// whether the catalog lists the actions of the service, e.g. `s3`
+    pub fn covers(&self, service: &str) -> bool {
+        self.actions.iter().any(|action| {
+            action
+                .split_once(':')
+                .is_some_and(|(s, _)| s.eq_ignore_ascii_case(service))
+        })
+    }
+
+    /// the catalog's spelling of `action`, if it lists it
+    pub fn find(&self, action: &str) -> Option<&str> {
+        self.actions
+            .iter()
+            .find(|a| a.eq_ignore_ascii_case(action))
+            .map(String::as_str)
+    }
+
+    /// the actions matched by an IAM action pattern, e.g. `s3:Delete*`
+    pub fn expand<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.actions
+            .iter()
+            .map(String::as_str)
+            .filter(move |action| action_matches(pattern, action))
+    }
+}
+
+/// matches an action against an IAM action pattern, ignoring case.
+/// `*` matches any run of characters, `?` any single character.
+pub fn action_matches(pattern: &str, action: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_ascii_lowercase().chars().collect();
+    let action: Vec<char> = action.to_ascii_lowercase().chars().collect();
+
+    // the last `*` seen, and the position in `action` it currently absorbs up to
+    let (mut p, mut a) = (0, 0);
+    let mut backtrack = None;
+    while a < action.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, a));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == action[a] => {
+                p += 1;
+                a += 1;
+            }
+            _ => match backtrack {
+                Some((star, absorbed)) => {
+                    backtrack = Some((star, absorbed + 1));
+                    p = star + 1;
+                    a = absorbed + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{action_matches, ActionCatalog};
+
+    #[test]
+    fn test_action_matches() {
+        assert!(action_matches("s3:DeleteBucket", "s3:deletebucket"));
+        assert!(action_matches("s3:Delete*", "s3:DeleteBucketPolicy"));
+        assert!(action_matches("*", "iam:PassRole"));
+        assert!(action_matches("iam:*Role*", "iam:UpdateRoleDescription"));
+        assert!(action_matches("kms:?ncrypt", "kms:Encrypt"));
+        assert!(!action_matches("s3:Delete*", "s3:PutObject"));
+        assert!(!action_matches("s3:DeleteBucket", "s3:DeleteBucketPolicy"));
+    }
+
+    #[test]
+    fn test_bundled_catalog() {
+        let catalog = ActionCatalog::bundled();
+        assert!(catalog.covers("s3"));
+        assert!(!catalog.covers("s4"));
+        assert_eq!(catalog.find("S3:DELETEBUCKET"), Some("s3:DeleteBucket"));
+        assert_eq!(catalog.find("s3:DeleteBuckets"), None);
+        assert!(catalog.expand("s3:DeleteBucket*").any(|a| a == "s3:DeleteBucketPolicy"));
+        assert!(catalog.expand("sts:*").all(|a| a.starts_with("sts:")));
+    }
+
+    #[test]
+    fn test_bundled_catalog_services() {
+        let catalog = ActionCatalog::bundled();
+        assert!(catalog.expand("ec2:*").any(|a| a == "ec2:TerminateInstances"));
+        assert!(catalog.expand("rds:Delete*").any(|a| a == "rds:DeleteDBInstance"));
+        assert!(catalog.expand("sqs:*").any(|a| a == "sqs:DeleteQueue"));
+        assert_eq!(catalog.find("s3:pausereplication"), Some("s3:PauseReplication"));
+    }
+}
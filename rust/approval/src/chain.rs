@@ -0,0 +1,95 @@
+use crate::ticket::ApprovalTicket;
+use std::collections::{HashMap, HashSet};
+
+/// why the chain of a derived ticket is not intact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainBreak {
+    /// the parent, or an ancestor, is not among the tickets given.
+    /// it is missing, unless it is held by a principal whose tags could not be read.
+    UnknownParent,
+    /// the parent is `evicted`, itself broken, or no longer vouches for the ticket
+    Broken,
+}
+
+/// the ids of derived tickets whose chain is not intact, given all the tickets of an account.
+/// since chains are resolved within a single account, the parents of tickets derived across accounts are unknown.
+pub fn broken_chains<'a>(
+    tickets: impl IntoIterator<Item = &'a ApprovalTicket>,
+    evicted: &HashSet<&str>,
+) -> HashMap<String, ChainBreak> {
+    let tickets: Vec<_> = tickets.into_iter().collect();
+    let by_id: HashMap<&str, &ApprovalTicket> = tickets.iter().filter_map(|t| Some((t.id()?, *t))).collect();
+
+    // parents have shorter lineages than their children, so they are resolved first
+    let mut derived: Vec<_> = tickets.iter().filter(|t| t.parent_id().is_some()).collect();
+    derived.sort_by_key(|t| t.lineage().len());
+
+    let mut broken = HashMap::new();
+    for ticket in derived {
+        let Some(parent_id) = ticket.parent_id() else {
+            continue;
+        };
+        let chain_break = match (broken.get(parent_id), by_id.get(parent_id)) {
+            _ if evicted.contains(parent_id) => Some(ChainBreak::Broken),
+            (Some(parent_break), _) => Some(*parent_break),
+            (None, None) => Some(ChainBreak::UnknownParent),
+            (None, Some(parent)) => ticket.validate_derivation(parent).err().map(|_| ChainBreak::Broken),
+        };
+        if let (Some(chain_break), Some(id)) = (chain_break, ticket.id()) {
+            broken.insert(id.to_string(), chain_break);
+        }
+    }
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{broken_chains, ChainBreak};
+    use crate::ticket::{ApprovalTicket, HumanIdentity};
+    use chrono::{Duration, Utc};
+    use std::collections::{HashMap, HashSet};
+
+    fn chain() -> (ApprovalTicket, ApprovalTicket, ApprovalTicket) {
+        let expiry = Utc::now() + Duration::hours(1);
+        let mut root = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        root.set_expiry(expiry);
+        root.set_chainable(true);
+        let mut child = root.derive(HumanIdentity::new("carol"), expiry).unwrap();
+        child.set_chainable(true);
+        let grandchild = child.derive(HumanIdentity::new("dave"), expiry).unwrap();
+        (root, child, grandchild)
+    }
+
+    #[test]
+    fn test_intact_chain() {
+        let (root, child, grandchild) = chain();
+        assert!(broken_chains([&root, &child, &grandchild], &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_evicted_root_breaks_chain() {
+        let (root, child, grandchild) = chain();
+        let evicted = HashSet::from([root.id().unwrap()]);
+        let broken = broken_chains([&grandchild, &child, &root], &evicted);
+        assert_eq!(
+            broken,
+            HashMap::from([
+                (child.id().unwrap().to_string(), ChainBreak::Broken),
+                (grandchild.id().unwrap().to_string(), ChainBreak::Broken),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_missing_parent_is_unknown() {
+        let (_, child, grandchild) = chain();
+        let broken = broken_chains([&child, &grandchild], &HashSet::new());
+        assert_eq!(
+            broken,
+            HashMap::from([
+                (child.id().unwrap().to_string(), ChainBreak::UnknownParent),
+                (grandchild.id().unwrap().to_string(), ChainBreak::UnknownParent),
+            ])
+        );
+    }
+}
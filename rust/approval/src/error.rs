@@ -0,0 +1,65 @@
+use aws_sdk_iam::error::{ProvideErrorMetadata, SdkError};
+use std::fmt::Debug;
+use thiserror::Error;
+
+/// a coarse classification of a failed AWS API call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiErrorKind {
+    /// the caller is not authorized to perform the call, e.g. due to an SCP deny
+    AccessDenied,
+    /// the principal or resource the call refers to does not exist
+    NotFound,
+    /// the call was throttled by the service
+    Throttling,
+    /// the call failed before or while a response was received
+    Transport,
+    /// any other service error
+    Service,
+}
+
+/// an AWS API error, tagged with its [`ApiErrorKind`].
+/// the `InternalError` variants of the manager errors wrap it, so callers can find it in the error chain.
+#[derive(Error, Debug)]
+#[error("{kind:?}: {source:#}")]
+pub struct ApiError {
+    pub kind: ApiErrorKind,
+    pub code: Option<String>,
+    source: anyhow::Error,
+}
+
+impl ApiError {
+    pub fn from_sdk<E, R>(err: SdkError<E, R>) -> Self
+    where
+        E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+        R: Debug + Send + Sync + 'static,
+    {
+        let code = err.code().map(str::to_string);
+        let kind = match &err {
+            SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+                ApiErrorKind::Transport
+            }
+            _ => code.as_deref().map_or(ApiErrorKind::Service, kind_of_code),
+        };
+        ApiError {
+            kind,
+            code,
+            source: err.into(),
+        }
+    }
+
+    /// finds the first [`ApiError`] in the chain of `err`
+    pub fn find(err: &anyhow::Error) -> Option<&ApiError> {
+        err.chain().find_map(|e| e.downcast_ref::<ApiError>())
+    }
+}
+
+pub(crate) fn kind_of_code(code: &str) -> ApiErrorKind {
+    match code {
+        "AccessDenied" | "AccessDeniedException" | "UnauthorizedOperation" => ApiErrorKind::AccessDenied,
+        "NoSuchEntity" | "NoSuchEntityException" => ApiErrorKind::NotFound,
+        "Throttling" | "ThrottlingException" | "RequestLimitExceeded" | "TooManyRequestsException" => {
+            ApiErrorKind::Throttling
+        }
+        _ => ApiErrorKind::Service,
+    }
+}
@@ -0,0 +1,507 @@
+use crate::{
+    catalog::{action_matches, ActionCatalog},
+    policy::{OneOrMany, PolicyDocument, PolicyStatement, POLICY_VERSION},
+    scp::{HUMAN_IDENTITY_KEY, INVALID_IDENTITY, SEAL_KINDS},
+    tags::Namespace,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// the name prefix of the guarded-action SCPs, followed by their key in `guarded_action_spec`
+pub const GUARDED_ACTIONS_SCP_PREFIX: &str = "guarded_actions_";
+/// the description of the guarded-action SCPs, as emitted by the terraform module
+pub const GUARDED_ACTIONS_SCP_DESCRIPTION: &str = "guard actions from being performed without approval.";
+const GUARD_ACTIONS_SID: &str = "GuardActions";
+/// how many overlapping actions a diagnostic names before summarizing the rest
+const OVERLAP_EXAMPLES: usize = 3;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum GuardError {
+    #[error("{0:?} is not a guarded-action SCP")]
+    NotGuarded(String),
+    #[error("malformed policy document: {0}")]
+    MalformedPolicy(String),
+}
+
+/// the actions a guarded-action SCP denies, unless the principal holds a ticket naming the caller as receiver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuardedActions {
+    /// the key of the entry in `guarded_action_spec`
+    pub key: String,
+    pub actions: Vec<String>,
+    pub not_actions: Vec<String>,
+}
+
+impl GuardedActions {
+    /// reads the deny statements of a guarded-action SCP, given its name and content
+    pub fn from_policy(name: &str, content: &str) -> Result<Self, GuardError> {
+        let key = name
+            .strip_prefix(GUARDED_ACTIONS_SCP_PREFIX)
+            .ok_or_else(|| GuardError::NotGuarded(name.to_string()))?;
+        let document: Value = serde_json::from_str(content).map_err(|e| GuardError::MalformedPolicy(e.to_string()))?;
+        let statements = match &document["Statement"] {
+            Value::Array(statements) => statements.clone(),
+            statement @ Value::Object(_) => vec![statement.clone()],
+            _ => return Err(GuardError::MalformedPolicy("no statements".to_string())),
+        };
+
+        let mut guarded = Self {
+            key: key.to_string(),
+            actions: vec![],
+            not_actions: vec![],
+        };
+        for statement in statements.iter().filter(|s| s["Effect"] == "Deny") {
+            guarded.actions.extend(strings(&statement["Action"]));
+            guarded.not_actions.extend(strings(&statement["NotAction"]));
+        }
+        Ok(guarded)
+    }
+
+    /// whether performing `action` needs a ticket
+    pub fn guards(&self, action: &str) -> bool {
+        self.actions.iter().any(|pattern| action_matches(pattern, action))
+            || (!self.not_actions.is_empty() && !self.not_actions.iter().any(|pattern| action_matches(pattern, action)))
+    }
+
+    /// the actions of the catalog which need a ticket
+    pub fn expand<'a>(&'a self, catalog: &'a ActionCatalog) -> Vec<&'a str> {
+        let mut actions: Vec<&str> = self
+            .actions
+            .iter()
+            .flat_map(|pattern| catalog.expand(pattern))
+            .collect();
+        if !self.not_actions.is_empty() {
+            actions.extend(catalog.expand("*").filter(|action| self.guards(action)));
+        }
+        actions.sort_unstable();
+        actions.dedup();
+        actions
+    }
+}
+
+/// the organizational units and accounts a policy is attached to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DeploymentTargets {
+    #[serde(default)]
+    pub organizational_unit_ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub account_ids: Option<Vec<String>>,
+}
+
+impl DeploymentTargets {
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        let ous = self.organizational_unit_ids.iter().flatten();
+        let accounts = self.account_ids.iter().flatten();
+        ous.chain(accounts).map(String::as_str)
+    }
+}
+
+/// an entry of `guarded_action_spec`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GuardedActionSpec {
+    pub actions: Vec<String>,
+    #[serde(default)]
+    pub deployment_targets: Option<DeploymentTargets>,
+}
+
+/// a guarded-action spec file. it has the shape of the terraform module variables, so it can double as a `.tfvars.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SpecFile {
+    /// the targets of the control-tags SCP, which guarded-action SCPs need to be attached within
+    #[serde(default)]
+    pub deployment_targets: Option<DeploymentTargets>,
+    #[serde(default)]
+    pub guarded_action_spec: BTreeMap<String, GuardedActionSpec>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// a problem found in an entry of the spec
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+/// a guarded-action SCP, as `aws_organizations_policy.guarded_actions` creates it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompiledPolicy {
+    pub key: String,
+    pub name: String,
+    pub description: String,
+    pub document: PolicyDocument,
+}
+
+/// the attachment of a guarded-action SCP to an organizational unit or account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Attachment {
+    pub key: String,
+    pub policy_name: String,
+    pub target_id: String,
+}
+
+/// the SCPs and attachments compiled from a spec. entries with errors compile to no policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Compilation {
+    pub policies: Vec<CompiledPolicy>,
+    pub attachments: Vec<Attachment>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Compilation {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// validates the spec against the catalog, and compiles the guarded-action SCPs reading tickets from `namespace`,
+/// like `scp_guarded_actions.tf` does
+pub fn compile(spec: &SpecFile, catalog: &ActionCatalog, namespace: &Namespace) -> Compilation {
+    let mut compilation = Compilation::default();
+    let control_tags_targets: Option<BTreeSet<&str>> = spec.deployment_targets.as_ref().map(|t| t.ids().collect());
+
+    for (key, entry) in &spec.guarded_action_spec {
+        let mut findings = vec![];
+        if key.is_empty() {
+            findings.push((Severity::Error, "the key cannot be empty".to_string()));
+        }
+        if entry.actions.is_empty() {
+            findings.push((Severity::Error, "the spec must contain at least one action".to_string()));
+        }
+        let targets: Vec<&str> = entry.deployment_targets.iter().flat_map(|t| t.ids()).collect();
+        match &entry.deployment_targets {
+            None => findings.push((Severity::Warning, "the policy is attached nowhere".to_string())),
+            Some(_) if targets.is_empty() => findings.push((
+                Severity::Error,
+                "the spec must contain at least one deployment target".to_string(),
+            )),
+            Some(_) => {}
+        }
+        findings.extend(
+            entry
+                .actions
+                .iter()
+                .filter_map(|pattern| check_action(catalog, pattern)),
+        );
+        findings.extend(seal_overlaps(catalog, &entry.actions));
+        if let Some(control_tags_targets) = &control_tags_targets {
+            findings.extend(
+                targets
+                    .iter()
+                    .filter(|target| !control_tags_targets.contains(*target))
+                    .map(|target| {
+                        (
+                            Severity::Warning,
+                            format!("{} is not a deployment target of the control-tags SCP", target),
+                        )
+                    }),
+            );
+        }
+
+        let valid = findings.iter().all(|(severity, _)| *severity != Severity::Error);
+        compilation
+            .diagnostics
+            .extend(findings.into_iter().map(|(severity, message)| Diagnostic {
+                severity,
+                key: key.clone(),
+                message,
+            }));
+        if !valid {
+            continue;
+        }
+
+        let name = format!("{}{}", GUARDED_ACTIONS_SCP_PREFIX, key);
+        compilation.attachments.extend(targets.iter().map(|target| Attachment {
+            key: key.clone(),
+            policy_name: name.clone(),
+            target_id: target.to_string(),
+        }));
+        compilation.policies.push(CompiledPolicy {
+            key: key.clone(),
+            name,
+            description: GUARDED_ACTIONS_SCP_DESCRIPTION.to_string(),
+            document: guard_document(namespace, &entry.actions),
+        });
+    }
+    compilation
+}
+
+/// the document of `data.aws_iam_policy_document.guarded_actions`, kept in sync by `test_terraform_drift`
+fn guard_document(namespace: &Namespace, actions: &[String]) -> PolicyDocument {
+    let mut seen = BTreeSet::new();
+    let actions: Vec<String> = actions.iter().filter(|a| seen.insert(a.as_str())).cloned().collect();
+    let ticket_condition = BTreeMap::from([(
+        format!("aws:PrincipalTag/{}", namespace.ticket_key()),
+        OneOrMany::One(format!("*/for/${{{}, '{}'}}", HUMAN_IDENTITY_KEY, INVALID_IDENTITY)),
+    )]);
+    PolicyDocument {
+        version: POLICY_VERSION.to_string(),
+        statement: vec![PolicyStatement {
+            sid: GUARD_ACTIONS_SID.to_string(),
+            effect: "Deny".to_string(),
+            principal: None,
+            action: actions.into(),
+            resource: OneOrMany::One("*".to_string()),
+            condition: BTreeMap::from([("StringNotLikeIfExists".to_string(), ticket_condition)]),
+        }],
+    }
+}
+
+/// checks that an action pattern names actions of the catalog
+fn check_action(catalog: &ActionCatalog, pattern: &str) -> Option<(Severity, String)> {
+    if pattern == "*" {
+        return Some((Severity::Warning, "`*` guards every action".to_string()));
+    }
+    let Some((service, action)) = pattern.split_once(':') else {
+        return Some((
+            Severity::Error,
+            format!("{:?} is not of the form <service>:<action>", pattern),
+        ));
+    };
+    let valid = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "-*?".contains(c));
+    if !valid(service) || !valid(action) {
+        return Some((Severity::Error, format!("{:?} is not a valid action", pattern)));
+    }
+
+    let wildcard = |s: &str| s.contains(['*', '?']);
+    if catalog.expand(pattern).next().is_some() {
+        None
+    } else if wildcard(service) {
+        Some((
+            Severity::Warning,
+            format!("{} matches no action of the bundled catalog", pattern),
+        ))
+    } else if !catalog.covers(service) {
+        // the catalog lists every service, an action of another one guards nothing
+        Some((
+            Severity::Error,
+            format!("unknown service {}, {} guards nothing", service, pattern),
+        ))
+    } else if wildcard(action) {
+        Some((Severity::Error, format!("{} matches no action of {}", pattern, service)))
+    } else {
+        let lowercase = pattern.to_ascii_lowercase();
+        let suggestion = catalog
+            .actions()
+            .filter(|candidate| {
+                candidate
+                    .split_once(':')
+                    .is_some_and(|(s, _)| s.eq_ignore_ascii_case(service))
+            })
+            .map(|candidate| (distance(&lowercase, &candidate.to_ascii_lowercase()), candidate))
+            .filter(|(d, _)| *d <= 2)
+            .min_by_key(|(d, _)| *d)
+            .map(|(_, candidate)| format!(", did you mean {}?", candidate))
+            .unwrap_or_default();
+        Some((Severity::Error, format!("unknown action {}{}", pattern, suggestion)))
+    }
+}
+
+/// the actions guarded by `actions` which resource seal kinds deny on sealed resources already.
+/// kinds sealing every action overlap every guarded action by design, and are left out.
+fn seal_overlaps(catalog: &ActionCatalog, actions: &[String]) -> Vec<(Severity, String)> {
+    let guarded: BTreeSet<&str> = actions.iter().flat_map(|pattern| catalog.expand(pattern)).collect();
+    SEAL_KINDS
+        .iter()
+        .filter(|kind| !kind.actions.contains(&"*"))
+        .filter_map(|kind| {
+            let overlap: Vec<&str> = guarded.iter().copied().filter(|action| kind.seals(action)).collect();
+            if overlap.is_empty() {
+                return None;
+            }
+            let mut examples = overlap[..overlap.len().min(OVERLAP_EXAMPLES)].join(", ");
+            if overlap.len() > OVERLAP_EXAMPLES {
+                examples.push_str(&format!(" and {} more", overlap.len() - OVERLAP_EXAMPLES));
+            }
+            Some((
+                Severity::Warning,
+                format!(
+                    "resources sealed with kind {} already need a ticket for {}",
+                    kind.name, examples
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// the Levenshtein distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(values) => values.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compile, GuardError, GuardedActions, Severity, SpecFile, GUARDED_ACTIONS_SCP_DESCRIPTION,
+        GUARDED_ACTIONS_SCP_PREFIX, GUARD_ACTIONS_SID,
+    };
+    use crate::{
+        catalog::ActionCatalog,
+        scp::{HUMAN_IDENTITY_KEY, INVALID_IDENTITY},
+        tags::Namespace,
+    };
+
+    /// `guard compile` renders the policies of the terraform module, so the module must not drift from it
+    #[test]
+    fn test_terraform_drift() {
+        let normalize = |tf: &str| tf.split_whitespace().collect::<Vec<_>>().join(" ");
+        let guarded_actions = normalize(include_str!("../../../terraform/control-tags/scp_guarded_actions.tf"));
+        let control_tags = normalize(include_str!("../../../terraform/control-tags/scp_control_tags.tf"));
+        let locals = normalize(include_str!("../../../terraform/control-tags/locals.tf"));
+
+        let expected = [
+            (&guarded_actions, format!("sid = \"{}\"", GUARD_ACTIONS_SID)),
+            (&guarded_actions, "effect = \"Deny\"".to_string()),
+            (&guarded_actions, "resources = [\"*\"]".to_string()),
+            (&guarded_actions, "test = \"StringNotLikeIfExists\"".to_string()),
+            (
+                &guarded_actions,
+                "variable = \"aws:PrincipalTag/${local.approval_ticket_tag_key}\"".to_string(),
+            ),
+            (
+                &guarded_actions,
+                "\"*/for/$${${tag_key}, '${local.invalid.identity}'}\"".to_string(),
+            ),
+            (
+                &guarded_actions,
+                format!("name = \"{}${{each.key}}\"", GUARDED_ACTIONS_SCP_PREFIX),
+            ),
+            (
+                &guarded_actions,
+                format!("description = \"{}\"", GUARDED_ACTIONS_SCP_DESCRIPTION),
+            ),
+            (&control_tags, format!("identity = \"{}\"", INVALID_IDENTITY)),
+            (
+                &control_tags,
+                format!("human_identity_tag_keys = [ \"{}\", ]", HUMAN_IDENTITY_KEY),
+            ),
+            (
+                &locals,
+                "approval_ticket_tag_key = \"${local.mpa_tag_key}/ticket\"".to_string(),
+            ),
+        ];
+        for (tf, fragment) in expected {
+            assert!(
+                tf.contains(&fragment),
+                "the terraform module no longer contains {}",
+                fragment
+            );
+        }
+    }
+
+    #[test]
+    fn test_guarded_actions_from_policy() {
+        let content = r#"{
+            "Version": "2012-10-17",
+            "Statement": {
+                "Sid": "GuardActions",
+                "Effect": "Deny",
+                "Action": ["s3:DeleteBucket*", "kms:ScheduleKeyDeletion"],
+                "Resource": "*"
+            }
+        }"#;
+        let guarded = GuardedActions::from_policy("guarded_actions_prod", content).unwrap();
+        assert_eq!(guarded.key, "prod");
+        assert!(guarded.guards("s3:DeleteBucketPolicy"));
+        assert!(guarded.guards("KMS:SCHEDULEKEYDELETION"));
+        assert!(!guarded.guards("s3:PutObject"));
+
+        let expanded = guarded.expand(ActionCatalog::bundled());
+        assert!(expanded.contains(&"s3:DeleteBucket"));
+        assert!(expanded.contains(&"kms:ScheduleKeyDeletion"));
+        assert!(!expanded.contains(&"s3:PutObject"));
+
+        assert_eq!(
+            GuardedActions::from_policy("control_tags", content),
+            Err(GuardError::NotGuarded("control_tags".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_compile() {
+        let spec: SpecFile = serde_json::from_str(
+            r#"{
+                "deployment_targets": {"organizational_unit_ids": ["ou-prod"]},
+                "guarded_action_spec": {
+                    "prod": {
+                        "actions": ["s3:DeleteBucket*", "kms:ScheduleKeyDeletion", "kms:ScheduleKeyDeletion"],
+                        "deployment_targets": {"organizational_unit_ids": ["ou-prod"], "account_ids": ["123456789012"]}
+                    },
+                    "typo": {
+                        "actions": ["s3:DeleteBuckett", "s3:Frobnicate*", "example:DoThing", "ec2:TerminateInstancess"],
+                        "deployment_targets": {"account_ids": ["123456789012"]}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let compilation = compile(&spec, ActionCatalog::bundled(), &Namespace::default());
+        assert!(compilation.has_errors());
+
+        assert_eq!(compilation.policies.len(), 1);
+        let document = serde_json::to_value(&compilation.policies[0].document).unwrap();
+        assert_eq!(compilation.policies[0].name, "guarded_actions_prod");
+        assert_eq!(
+            document["Statement"][0]["Action"],
+            serde_json::json!(["s3:DeleteBucket*", "kms:ScheduleKeyDeletion"])
+        );
+        assert_eq!(
+            document["Statement"][0]["Condition"]["StringNotLikeIfExists"]
+                ["aws:PrincipalTag/tagctl:v1/admin/mpa/ticket"],
+            "*/for/${aws:SourceIdentity, 'nil'}"
+        );
+        assert_eq!(compilation.attachments.len(), 2);
+
+        let messages = |key: &str, severity: Severity| -> Vec<&str> {
+            compilation
+                .diagnostics
+                .iter()
+                .filter(|d| d.key == key && d.severity == severity)
+                .map(|d| d.message.as_str())
+                .collect()
+        };
+        assert_eq!(
+            messages("typo", Severity::Error),
+            vec![
+                "unknown action s3:DeleteBuckett, did you mean s3:DeleteBucket?",
+                "s3:Frobnicate* matches no action of s3",
+                "unknown service example, example:DoThing guards nothing",
+                "unknown action ec2:TerminateInstancess, did you mean ec2:TerminateInstances?",
+            ]
+        );
+        assert_eq!(
+            messages("typo", Severity::Warning),
+            vec!["123456789012 is not a deployment target of the control-tags SCP"]
+        );
+        let warnings = messages("prod", Severity::Warning);
+        assert!(warnings[0].starts_with("resources sealed with kind trust_relay already need a ticket for "));
+        assert!(warnings[0].ends_with(" more"));
+    }
+}
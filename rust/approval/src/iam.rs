@@ -1,19 +1,47 @@
 use crate::{
-    tags,
+    error::{ApiError, ApiErrorKind},
+    principal::{IamPrincipal, PrincipalKind},
+    retry::{RetryMetrics, RetryPolicy, RetryingClient},
+    tags::{Namespace, TagSchema, TicketTag},
     ticket::{ApprovalTicket, ParseError},
 };
 use anyhow;
-use aws_sdk_iam::{self, error::BuildError, types::Tag};
+use async_stream::stream;
+use aws_sdk_iam::{
+    self,
+    error::BuildError,
+    operation::get_account_authorization_details::GetAccountAuthorizationDetailsOutput,
+    types::{EntityType, Tag},
+};
 
 use aws_smithy_types_convert::stream::PaginationStreamExt;
-use futures::{Stream, TryStreamExt};
-use std::{future, sync::Arc};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use std::{pin::pin, sync::Arc};
 use thiserror::Error;
 
+/// a principal the manager does not handle
+#[derive(Error, Debug)]
+pub enum PrincipalError {
+    #[error("{principal} is not a {}", .expected.resource_type())]
+    WrongKind {
+        principal: Box<IamPrincipal>,
+        expected: PrincipalKind,
+    },
+    #[error("{principal} is not in account {account}")]
+    OtherAccount {
+        principal: Box<IamPrincipal>,
+        account: String,
+    },
+}
+
 #[derive(Error, Debug)]
 pub enum ListPrincipalsError {
     #[error("cannot list principals: {0:?}")]
     InternalError(#[from] anyhow::Error),
+    #[error("no {} named {name}", .kind.resource_type())]
+    NotFound { kind: PrincipalKind, name: String },
+    #[error(transparent)]
+    Principal(#[from] PrincipalError),
 }
 
 #[derive(Error, Debug)]
@@ -26,6 +54,8 @@ pub enum ListAllTicketsError {
 pub enum ListTicketsError {
     #[error("cannot list tickets: {0:?}")]
     InternalError(#[from] anyhow::Error),
+    #[error(transparent)]
+    Principal(#[from] PrincipalError),
 }
 
 #[derive(Error, Debug)]
@@ -34,211 +64,896 @@ pub enum SetTicketError {
     InternalError(#[from] anyhow::Error),
     #[error("create tag from ticket specification")]
     MalformedTag,
+    #[error("principal already holds a different ticket: {existing}")]
+    Conflict { existing: ApprovalTicket },
+    #[error(transparent)]
+    Principal(#[from] PrincipalError),
 }
 
 #[derive(Error, Debug)]
 pub enum UnsetTicketError {
     #[error("cannot unset ticket: {0:?}")]
     InternalError(#[from] anyhow::Error),
+    #[error(transparent)]
+    Principal(#[from] PrincipalError),
 }
 
-pub type NamedIamPrincipal = String;
+/// the number of principals whose tags are read at once by [`Listing::PerPrincipal`]
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// how [`ApprovalManager::list_all_tickets`] reads the tags of the principals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Listing {
+    /// reads the tags of all principals from a few `GetAccountAuthorizationDetails` pages,
+    /// falling back to [`Listing::PerPrincipal`] if the call is denied
+    #[default]
+    Bulk,
+    /// lists the principals, then calls `ListRoleTags` or `ListUserTags` for each of them
+    PerPrincipal,
+}
 
+/// reads and writes the tickets of the roles or the users of an account.
+/// principals of the other kind, or of another account than the one given to the manager, are refused with a
+/// [`PrincipalError`]. the path of a principal is not checked, as names are unique within an account.
 pub trait ApprovalManager {
-    fn list_all_tickets(&self) -> impl Stream<Item = Result<(NamedIamPrincipal, ApprovalTicket), ListAllTicketsError>>;
+    /// the principal named `name`, with its path, account and partition.
+    /// fails with [`ListPrincipalsError::NotFound`] if the account has no such principal.
+    fn principal(&self, name: &str) -> impl std::future::Future<Output = Result<IamPrincipal, ListPrincipalsError>>;
+    /// the principals holding a ticket tag, including tags which do not parse as a ticket.
+    /// a principal is listed once for its ticket, and once for each value under a ticket key which does not parse.
+    fn list_all_tickets(&self) -> impl Stream<Item = Result<(IamPrincipal, TicketTag), ListAllTicketsError>>;
+    /// the ticket of the principal, a ticket tag which does not parse counts as no ticket
     fn get_ticket(
         &self,
-        principal: &NamedIamPrincipal,
+        principal: &IamPrincipal,
     ) -> impl std::future::Future<Output = Result<Option<ApprovalTicket>, ListTicketsError>>;
+    /// the ticket tag of the principal, reporting values which do not parse as [`TicketTag::Malformed`]
+    fn get_ticket_tag(
+        &self,
+        principal: &IamPrincipal,
+    ) -> impl std::future::Future<Output = Result<Option<TicketTag>, ListTicketsError>>;
+    /// sets the ticket on the principal.
+    /// unless `force` is set, fails with [`SetTicketError::Conflict`] if the principal already holds an unexpired ticket
+    /// with a different id, or without one.
+    /// note that the check and the write are not atomic, as IAM tagging offers no conditional writes.
     fn set_ticket(
         &self,
-        principal: &NamedIamPrincipal,
+        principal: &IamPrincipal,
         ticket: ApprovalTicket,
+        force: bool,
     ) -> impl std::future::Future<Output = Result<(), SetTicketError>>;
-    fn unset_ticket(
+    fn unset_ticket(&self, principal: &IamPrincipal)
+        -> impl std::future::Future<Output = Result<(), UnsetTicketError>>;
+    /// untags the ticket key `key` if it still holds the malformed `value`, leaving the other ticket keys in place.
+    /// whether the value was untagged.
+    fn unset_malformed_ticket(
         &self,
-        principal: &NamedIamPrincipal,
-    ) -> impl std::future::Future<Output = Result<(), UnsetTicketError>>;
+        principal: &IamPrincipal,
+        key: &str,
+        value: &str,
+    ) -> impl std::future::Future<Output = Result<bool, UnsetTicketError>>;
 }
 
 pub struct RoleApprovalManager {
-    iam: std::sync::Arc<aws_sdk_iam::Client>,
+    iam: RetryingClient,
+    schema: TagSchema,
+    listing: Listing,
+    concurrency: usize,
+    account_id: Option<String>,
 }
 
 impl RoleApprovalManager {
     pub fn new(iam: Arc<aws_sdk_iam::Client>) -> Self {
-        Self { iam }
+        Self {
+            iam: RetryingClient::new(iam),
+            schema: TagSchema::default(),
+            listing: Listing::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            account_id: None,
+        }
+    }
+
+    /// reads and writes tickets with `schema` instead of `tagctl:v1`
+    pub fn with_schema(self, schema: TagSchema) -> Self {
+        Self { schema, ..self }
+    }
+
+    pub fn with_listing(self, listing: Listing) -> Self {
+        Self { listing, ..self }
+    }
+
+    /// refuses the principals of other accounts than `account_id`, the account of the client
+    pub fn with_account(self, account_id: impl Into<String>) -> Self {
+        Self {
+            account_id: Some(account_id.into()),
+            ..self
+        }
+    }
+
+    /// reads the tags of up to `concurrency` principals at once when listing them one by one
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    /// retries throttled or failed calls under `policy` instead of [`RetryPolicy::default`]
+    pub fn with_retry_policy(self, policy: RetryPolicy) -> Self {
+        Self {
+            iam: self.iam.with_policy(policy),
+            ..self
+        }
+    }
+
+    /// counts calls and retries into `metrics`, e.g. to share them between managers
+    pub fn with_retry_metrics(self, metrics: Arc<RetryMetrics>) -> Self {
+        Self {
+            iam: self.iam.with_metrics(metrics),
+            ..self
+        }
+    }
+
+    pub fn retry_metrics(&self) -> &Arc<RetryMetrics> {
+        &self.iam.metrics
     }
 }
 
 pub struct UserApprovalManager {
-    iam: std::sync::Arc<aws_sdk_iam::Client>,
+    iam: RetryingClient,
+    schema: TagSchema,
+    listing: Listing,
+    concurrency: usize,
+    account_id: Option<String>,
 }
 
 impl UserApprovalManager {
     pub fn new(iam: Arc<aws_sdk_iam::Client>) -> Self {
-        Self { iam }
+        Self {
+            iam: RetryingClient::new(iam),
+            schema: TagSchema::default(),
+            listing: Listing::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            account_id: None,
+        }
+    }
+
+    /// reads and writes tickets with `schema` instead of `tagctl:v1`
+    pub fn with_schema(self, schema: TagSchema) -> Self {
+        Self { schema, ..self }
+    }
+
+    pub fn with_listing(self, listing: Listing) -> Self {
+        Self { listing, ..self }
+    }
+
+    /// refuses the principals of other accounts than `account_id`, the account of the client
+    pub fn with_account(self, account_id: impl Into<String>) -> Self {
+        Self {
+            account_id: Some(account_id.into()),
+            ..self
+        }
+    }
+
+    /// reads the tags of up to `concurrency` principals at once when listing them one by one
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    /// retries throttled or failed calls under `policy` instead of [`RetryPolicy::default`]
+    pub fn with_retry_policy(self, policy: RetryPolicy) -> Self {
+        Self {
+            iam: self.iam.with_policy(policy),
+            ..self
+        }
+    }
+
+    /// counts calls and retries into `metrics`, e.g. to share them between managers
+    pub fn with_retry_metrics(self, metrics: Arc<RetryMetrics>) -> Self {
+        Self {
+            iam: self.iam.with_metrics(metrics),
+            ..self
+        }
+    }
+
+    pub fn retry_metrics(&self) -> &Arc<RetryMetrics> {
+        &self.iam.metrics
     }
 }
 
-impl ApprovalManager for RoleApprovalManager {
-    fn list_all_tickets(&self) -> impl Stream<Item = Result<(NamedIamPrincipal, ApprovalTicket), ListAllTicketsError>> {
+impl RoleApprovalManager {
+    fn list_tickets_per_principal(
+        &self,
+    ) -> impl Stream<Item = Result<(IamPrincipal, TicketTag), ListAllTicketsError>> + '_ {
         self.iam
             .list_roles()
             .into_paginator()
             .items()
             .send()
             .into_stream_03x()
-            .map_err(|e| ListAllTicketsError::InternalError(e.into()))
-            .map_ok(|role| async {
-                let ticket = self
-                    .get_ticket(&role.role_name)
+            .map_err(|e| ListAllTicketsError::InternalError(ApiError::from_sdk(e).into()))
+            .map_ok(move |role| async move {
+                let principal = role
+                    .arn
+                    .parse::<IamPrincipal>()
+                    .map_err(|e| ListAllTicketsError::InternalError(e.into()))?;
+                let tags = self
+                    .ticket_tags(&principal)
                     .await
                     .map_err(|e| ListAllTicketsError::InternalError(e.into()))?;
-                Ok((role.role_name, ticket))
-            })
-            .try_buffer_unordered(4)
-            .try_filter_map(|(principal, ticket)| {
-                future::ready(match ticket {
-                    Some(ticket) => Ok(Some((principal, ticket))),
-                    None => Ok(None),
-                })
+                Ok((principal, tags))
             })
+            .try_buffer_unordered(self.concurrency)
+            .map_ok(|(principal, tags)| stream::iter(tags.into_iter().map(move |tag| Ok((principal.clone(), tag)))))
+            .try_flatten()
     }
 
-    async fn get_ticket(&self, principal: &NamedIamPrincipal) -> Result<Option<ApprovalTicket>, ListTicketsError> {
+    /// the ticket tags of the principal, see [`TagSchema::find_ticket_tags`]
+    async fn ticket_tags(&self, principal: &IamPrincipal) -> Result<Vec<TicketTag>, ListTicketsError> {
+        let name = principal_name(principal, PrincipalKind::Role, self.account_id.as_deref())?;
         let tags = self
             .iam
             .list_role_tags()
-            .role_name(principal)
+            .role_name(name)
             .send()
             .await
+            .map_err(ApiError::from_sdk)
             .map_err(|e| ListTicketsError::InternalError(e.into()))?
             .tags;
 
-        let ticket = tags.iter().find_map(|t| ApprovalTicket::try_from(t).ok());
+        Ok(self.schema.find_ticket_tags(tags.iter().map(|t| (t.key(), t.value()))))
+    }
+}
+
+impl ApprovalManager for RoleApprovalManager {
+    async fn principal(&self, name: &str) -> Result<IamPrincipal, ListPrincipalsError> {
+        let arn = self
+            .iam
+            .get_role()
+            .role_name(name)
+            .send()
+            .await
+            .map_err(ApiError::from_sdk)
+            .map_err(|e| match e.kind {
+                ApiErrorKind::NotFound => ListPrincipalsError::NotFound {
+                    kind: PrincipalKind::Role,
+                    name: name.to_string(),
+                },
+                _ => ListPrincipalsError::InternalError(e.into()),
+            })?
+            .role
+            .map(|role| role.arn)
+            .ok_or_else(|| ListPrincipalsError::InternalError(anyhow::anyhow!("no role found for {name}")))?;
+        let principal = arn.parse().map_err(anyhow::Error::new)?;
+        principal_name(&principal, PrincipalKind::Role, self.account_id.as_deref())?;
+        Ok(principal)
+    }
+
+    fn list_all_tickets(&self) -> impl Stream<Item = Result<(IamPrincipal, TicketTag), ListAllTicketsError>> {
+        match self.listing {
+            Listing::Bulk => {
+                let pages = authorization_details(&self.iam, EntityType::Role, |page| {
+                    page.role_detail_list
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|detail| Some((detail.arn?, detail.tags.unwrap_or_default())))
+                        .collect::<Vec<_>>()
+                });
+                list_tickets_in_bulk(&self.schema, pages, || self.list_tickets_per_principal()).left_stream()
+            }
+            Listing::PerPrincipal => self.list_tickets_per_principal().right_stream(),
+        }
+    }
+
+    async fn get_ticket(&self, principal: &IamPrincipal) -> Result<Option<ApprovalTicket>, ListTicketsError> {
+        Ok(self.get_ticket_tag(principal).await?.and_then(TicketTag::into_ticket))
+    }
 
-        Ok(ticket)
+    async fn get_ticket_tag(&self, principal: &IamPrincipal) -> Result<Option<TicketTag>, ListTicketsError> {
+        Ok(self.ticket_tags(principal).await?.into_iter().next())
     }
 
-    async fn set_ticket(&self, principal: &NamedIamPrincipal, ticket: ApprovalTicket) -> Result<(), SetTicketError> {
-        let tag = ticket.try_into().map_err(|_| SetTicketError::MalformedTag)?;
+    async fn set_ticket(
+        &self,
+        principal: &IamPrincipal,
+        ticket: ApprovalTicket,
+        force: bool,
+    ) -> Result<(), SetTicketError> {
+        let name = principal_name(principal, PrincipalKind::Role, self.account_id.as_deref())?;
+        if !force {
+            let existing = self
+                .get_ticket(principal)
+                .await
+                .map_err(|e| SetTicketError::InternalError(e.into()))?;
+            check_conflict(existing, &ticket)?;
+        }
+
+        let tag = ticket_tag(self.schema.current(), &ticket)?;
 
         self.iam
             .tag_role()
-            .role_name(principal)
+            .role_name(name)
             .tags(tag)
             .send()
             .await
+            .map_err(ApiError::from_sdk)
             .map_err(|e| SetTicketError::InternalError(e.into()))?;
         Ok(())
     }
 
-    async fn unset_ticket(&self, principal: &NamedIamPrincipal) -> Result<(), UnsetTicketError> {
+    async fn unset_ticket(&self, principal: &IamPrincipal) -> Result<(), UnsetTicketError> {
+        let name = principal_name(principal, PrincipalKind::Role, self.account_id.as_deref())?;
         self.iam
             .untag_role()
-            .tag_keys(tags::KEY_ADMIN_TICKET)
-            .role_name(principal)
+            .set_tag_keys(Some(self.schema.ticket_keys()))
+            .role_name(name)
             .send()
             .await
+            .map_err(ApiError::from_sdk)
             .map_err(|e| UnsetTicketError::InternalError(e.into()))?;
         Ok(())
     }
+
+    async fn unset_malformed_ticket(
+        &self,
+        principal: &IamPrincipal,
+        key: &str,
+        value: &str,
+    ) -> Result<bool, UnsetTicketError> {
+        let name = principal_name(principal, PrincipalKind::Role, self.account_id.as_deref())?;
+        let held = self
+            .ticket_tags(principal)
+            .await
+            .map_err(|e| UnsetTicketError::InternalError(e.into()))?
+            .iter()
+            .any(|tag| matches!(tag, TicketTag::Malformed(tag) if tag.key == key && tag.value == value));
+        if !held {
+            return Ok(false);
+        }
+        self.iam
+            .untag_role()
+            .tag_keys(key)
+            .role_name(name)
+            .send()
+            .await
+            .map_err(ApiError::from_sdk)
+            .map_err(|e| UnsetTicketError::InternalError(e.into()))?;
+        Ok(true)
+    }
 }
 
-impl ApprovalManager for UserApprovalManager {
-    fn list_all_tickets(&self) -> impl Stream<Item = Result<(NamedIamPrincipal, ApprovalTicket), ListAllTicketsError>> {
+impl UserApprovalManager {
+    fn list_tickets_per_principal(
+        &self,
+    ) -> impl Stream<Item = Result<(IamPrincipal, TicketTag), ListAllTicketsError>> + '_ {
         self.iam
             .list_users()
             .into_paginator()
             .items()
             .send()
             .into_stream_03x()
-            .map_err(|e| ListAllTicketsError::InternalError(e.into()))
-            .map_ok(|user| async {
-                let ticket = self
-                    .get_ticket(&user.user_name)
+            .map_err(|e| ListAllTicketsError::InternalError(ApiError::from_sdk(e).into()))
+            .map_ok(move |user| async move {
+                let principal = user
+                    .arn
+                    .parse::<IamPrincipal>()
+                    .map_err(|e| ListAllTicketsError::InternalError(e.into()))?;
+                let tags = self
+                    .ticket_tags(&principal)
                     .await
                     .map_err(|e| ListAllTicketsError::InternalError(e.into()))?;
-                Ok((user.user_name, ticket))
-            })
-            .try_buffer_unordered(4)
-            .try_filter_map(|(principal, ticket)| {
-                future::ready(match ticket {
-                    Some(ticket) => Ok(Some((principal, ticket))),
-                    None => Ok(None),
-                })
+                Ok((principal, tags))
             })
+            .try_buffer_unordered(self.concurrency)
+            .map_ok(|(principal, tags)| stream::iter(tags.into_iter().map(move |tag| Ok((principal.clone(), tag)))))
+            .try_flatten()
     }
 
-    async fn get_ticket(&self, principal: &NamedIamPrincipal) -> Result<Option<ApprovalTicket>, ListTicketsError> {
+    /// the ticket tags of the principal, see [`TagSchema::find_ticket_tags`]
+    async fn ticket_tags(&self, principal: &IamPrincipal) -> Result<Vec<TicketTag>, ListTicketsError> {
+        let name = principal_name(principal, PrincipalKind::User, self.account_id.as_deref())?;
         let tags = self
             .iam
             .list_user_tags()
-            .user_name(principal)
+            .user_name(name)
             .send()
             .await
+            .map_err(ApiError::from_sdk)
             .map_err(|e| ListTicketsError::InternalError(e.into()))?
             .tags;
 
-        let ticket = tags.iter().find_map(|t| ApprovalTicket::try_from(t).ok());
+        Ok(self.schema.find_ticket_tags(tags.iter().map(|t| (t.key(), t.value()))))
+    }
+}
+
+impl ApprovalManager for UserApprovalManager {
+    async fn principal(&self, name: &str) -> Result<IamPrincipal, ListPrincipalsError> {
+        let arn = self
+            .iam
+            .get_user()
+            .user_name(name)
+            .send()
+            .await
+            .map_err(ApiError::from_sdk)
+            .map_err(|e| match e.kind {
+                ApiErrorKind::NotFound => ListPrincipalsError::NotFound {
+                    kind: PrincipalKind::User,
+                    name: name.to_string(),
+                },
+                _ => ListPrincipalsError::InternalError(e.into()),
+            })?
+            .user
+            .map(|user| user.arn)
+            .ok_or_else(|| ListPrincipalsError::InternalError(anyhow::anyhow!("no user found for {name}")))?;
+        let principal = arn.parse().map_err(anyhow::Error::new)?;
+        principal_name(&principal, PrincipalKind::User, self.account_id.as_deref())?;
+        Ok(principal)
+    }
+
+    fn list_all_tickets(&self) -> impl Stream<Item = Result<(IamPrincipal, TicketTag), ListAllTicketsError>> {
+        match self.listing {
+            Listing::Bulk => {
+                let pages = authorization_details(&self.iam, EntityType::User, |page| {
+                    page.user_detail_list
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|detail| Some((detail.arn?, detail.tags.unwrap_or_default())))
+                        .collect::<Vec<_>>()
+                });
+                list_tickets_in_bulk(&self.schema, pages, || self.list_tickets_per_principal()).left_stream()
+            }
+            Listing::PerPrincipal => self.list_tickets_per_principal().right_stream(),
+        }
+    }
+
+    async fn get_ticket(&self, principal: &IamPrincipal) -> Result<Option<ApprovalTicket>, ListTicketsError> {
+        Ok(self.get_ticket_tag(principal).await?.and_then(TicketTag::into_ticket))
+    }
 
-        Ok(ticket)
+    async fn get_ticket_tag(&self, principal: &IamPrincipal) -> Result<Option<TicketTag>, ListTicketsError> {
+        Ok(self.ticket_tags(principal).await?.into_iter().next())
     }
 
-    async fn set_ticket(&self, principal: &NamedIamPrincipal, ticket: ApprovalTicket) -> Result<(), SetTicketError> {
-        let tag = ticket.try_into().map_err(|_| SetTicketError::MalformedTag)?;
+    async fn set_ticket(
+        &self,
+        principal: &IamPrincipal,
+        ticket: ApprovalTicket,
+        force: bool,
+    ) -> Result<(), SetTicketError> {
+        let name = principal_name(principal, PrincipalKind::User, self.account_id.as_deref())?;
+        if !force {
+            let existing = self
+                .get_ticket(principal)
+                .await
+                .map_err(|e| SetTicketError::InternalError(e.into()))?;
+            check_conflict(existing, &ticket)?;
+        }
+
+        let tag = ticket_tag(self.schema.current(), &ticket)?;
 
         self.iam
             .tag_user()
-            .user_name(principal)
+            .user_name(name)
             .tags(tag)
             .send()
             .await
+            .map_err(ApiError::from_sdk)
             .map_err(|e| SetTicketError::InternalError(e.into()))?;
         Ok(())
     }
 
-    async fn unset_ticket(&self, principal: &NamedIamPrincipal) -> Result<(), UnsetTicketError> {
+    async fn unset_ticket(&self, principal: &IamPrincipal) -> Result<(), UnsetTicketError> {
+        let name = principal_name(principal, PrincipalKind::User, self.account_id.as_deref())?;
         self.iam
             .untag_user()
-            .tag_keys(tags::KEY_ADMIN_TICKET)
-            .user_name(principal)
+            .set_tag_keys(Some(self.schema.ticket_keys()))
+            .user_name(name)
             .send()
             .await
+            .map_err(ApiError::from_sdk)
             .map_err(|e| UnsetTicketError::InternalError(e.into()))?;
         Ok(())
     }
+
+    async fn unset_malformed_ticket(
+        &self,
+        principal: &IamPrincipal,
+        key: &str,
+        value: &str,
+    ) -> Result<bool, UnsetTicketError> {
+        let name = principal_name(principal, PrincipalKind::User, self.account_id.as_deref())?;
+        let held = self
+            .ticket_tags(principal)
+            .await
+            .map_err(|e| UnsetTicketError::InternalError(e.into()))?
+            .iter()
+            .any(|tag| matches!(tag, TicketTag::Malformed(tag) if tag.key == key && tag.value == value));
+        if !held {
+            return Ok(false);
+        }
+        self.iam
+            .untag_user()
+            .tag_keys(key)
+            .user_name(name)
+            .send()
+            .await
+            .map_err(ApiError::from_sdk)
+            .map_err(|e| UnsetTicketError::InternalError(e.into()))?;
+        Ok(true)
+    }
+}
+
+/// the name of the principal, which must be of the kind and, if known, in the account the manager handles
+fn principal_name<'a>(
+    principal: &'a IamPrincipal,
+    kind: PrincipalKind,
+    account_id: Option<&str>,
+) -> Result<&'a str, PrincipalError> {
+    if principal.kind != kind {
+        return Err(PrincipalError::WrongKind {
+            principal: Box::new(principal.clone()),
+            expected: kind,
+        });
+    }
+    if let Some(account_id) = account_id.filter(|account_id| principal.account_id != *account_id) {
+        return Err(PrincipalError::OtherAccount {
+            principal: Box::new(principal.clone()),
+            account: account_id.to_string(),
+        });
+    }
+    Ok(&principal.name)
+}
+
+/// the pages of `GetAccountAuthorizationDetails` for `entity`
+fn authorization_details<'a, T: 'a>(
+    iam: &'a aws_sdk_iam::Client,
+    entity: EntityType,
+    details: impl Fn(GetAccountAuthorizationDetailsOutput) -> T + 'a,
+) -> impl Stream<Item = Result<T, ApiError>> + 'a {
+    stream! {
+        let mut marker = None;
+        loop {
+            let page = iam
+                .get_account_authorization_details()
+                .filter(entity.clone())
+                .set_marker(marker.clone())
+                .send()
+                .await
+                .map_err(ApiError::from_sdk);
+            match page {
+                Ok(page) => {
+                    marker = page.marker.clone().filter(|_| page.is_truncated);
+                    yield Ok(details(page));
+                    if marker.is_none() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// the ticket tags found in pages of principal ARNs and their tags.
+/// if the first page is denied, e.g. as the caller lacks `iam:GetAccountAuthorizationDetails`, falls back to `fallback`.
+fn list_tickets_in_bulk<'a, S>(
+    schema: &'a TagSchema,
+    pages: impl Stream<Item = Result<Vec<(String, Vec<Tag>)>, ApiError>> + 'a,
+    fallback: impl FnOnce() -> S + 'a,
+) -> impl Stream<Item = Result<(IamPrincipal, TicketTag), ListAllTicketsError>> + 'a
+where
+    S: Stream<Item = Result<(IamPrincipal, TicketTag), ListAllTicketsError>> + 'a,
+{
+    stream! {
+        let mut pages = pin!(pages);
+        let mut first = true;
+        while let Some(page) = pages.next().await {
+            match page {
+                Ok(principals) => {
+                    for (arn, tags) in principals {
+                        let found = schema.find_ticket_tags(tags.iter().map(|t| (t.key(), t.value())));
+                        if found.is_empty() {
+                            continue;
+                        }
+                        match arn.parse::<IamPrincipal>() {
+                            Ok(principal) => {
+                                for tag in found {
+                                    yield Ok((principal.clone(), tag));
+                                }
+                            }
+                            Err(e) => yield Err(ListAllTicketsError::InternalError(e.into())),
+                        }
+                    }
+                }
+                Err(e) if first && e.kind == ApiErrorKind::AccessDenied => {
+                    for await item in fallback() {
+                        yield item;
+                    }
+                    return;
+                }
+                Err(e) => {
+                    yield Err(ListAllTicketsError::InternalError(e.into()));
+                    return;
+                }
+            }
+            first = false;
+        }
+    }
+}
+
+/// a ticket may replace an expired one, or an update of the same ticket, i.e. one carrying the same id.
+/// tickets written before ids were introduced cannot be told apart, so they are only replaced once expired.
+fn check_conflict(existing: Option<ApprovalTicket>, ticket: &ApprovalTicket) -> Result<(), SetTicketError> {
+    let Some(existing) = existing else {
+        return Ok(());
+    };
+    if existing.expires_at().is_some_and(|expiry| expiry <= chrono::Utc::now()) {
+        return Ok(());
+    }
+    match (existing.id(), ticket.id()) {
+        (Some(existing_id), Some(id)) if existing_id == id => Ok(()),
+        _ => Err(SetTicketError::Conflict { existing }),
+    }
+}
+
+fn ticket_tag(namespace: &Namespace, ticket: &ApprovalTicket) -> Result<Tag, SetTicketError> {
+    Tag::builder()
+        .key(namespace.ticket_key())
+        .value(ticket.to_string())
+        .build()
+        .map_err(|_| SetTicketError::MalformedTag)
 }
 
 #[derive(Error, Debug)]
 pub enum TicketBuildError {
-    #[error("Invalid tag key: {0}. Tag key must start with {}", tags::KEY_ADMIN_TICKET)]
+    #[error("Invalid tag key: {0}. Tag key must start with {key}", key = Namespace::default().ticket_key())]
     TagKeyInvalid(String),
     #[error("Failed to parse tag value: {0}")]
     TagValueParseError(#[from] ParseError),
 }
 
+/// reads a ticket written with the default `tagctl:v1` schema, see [`TagSchema::find_ticket`] for other schemas
 impl TryFrom<&Tag> for crate::ticket::ApprovalTicket {
     type Error = TicketBuildError;
 
     fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
-        if !tag.key().starts_with(tags::KEY_ADMIN_TICKET) {
+        if !tag.key().starts_with(&Namespace::default().ticket_key()) {
             return Err(TicketBuildError::TagKeyInvalid(tag.key().to_string()));
         }
 
         tag.value()
             .parse::<ApprovalTicket>()
-            .map_err(|e| TicketBuildError::TagValueParseError(e))
+            .map_err(TicketBuildError::TagValueParseError)
     }
 }
 
+/// writes a ticket with the default `tagctl:v1` schema
 impl TryFrom<crate::ticket::ApprovalTicket> for Tag {
     type Error = BuildError;
     fn try_from(ticket: crate::ticket::ApprovalTicket) -> Result<Self, Self::Error> {
         Tag::builder()
-            .key(tags::KEY_ADMIN_TICKET)
+            .key(Namespace::default().ticket_key())
             .value(ticket.to_string())
             .build()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_conflict, ApprovalManager, ListPrincipalsError, ListTicketsError, Listing, PrincipalError,
+        RoleApprovalManager, SetTicketError, UnsetTicketError, UserApprovalManager,
+    };
+    use crate::{
+        error::{ApiError, ApiErrorKind},
+        principal::{IamPrincipal, PrincipalKind},
+        retry::{RetryMetrics, RetryPolicy},
+        tags::{TagSchema, TicketTag},
+        testing::{MockIam, ACCOUNT_ID},
+        ticket::{ApprovalTicket, HumanIdentity},
+    };
+    use futures::TryStreamExt;
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn test_check_conflict() {
+        let ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        let other = ApprovalTicket::new(HumanIdentity::new("carol"), HumanIdentity::new("bob"));
+        let legacy = "by/carol/exp=4102444800/for/bob".parse::<ApprovalTicket>().unwrap();
+        let expired_legacy = "by/carol/exp=1618033988/for/bob".parse::<ApprovalTicket>().unwrap();
+        let mut expired = other.clone();
+        expired.set_expiry(chrono::Utc::now() - chrono::Duration::minutes(1));
+
+        assert!(check_conflict(None, &ticket).is_ok());
+        assert!(check_conflict(Some(ticket.clone()), &ticket).is_ok());
+        assert!(matches!(
+            check_conflict(Some(other.clone()), &ticket),
+            Err(SetTicketError::Conflict { existing }) if existing == other
+        ));
+        assert!(matches!(
+            check_conflict(Some(legacy), &ticket),
+            Err(SetTicketError::Conflict { .. })
+        ));
+        assert!(check_conflict(Some(expired_legacy), &ticket).is_ok());
+        assert!(check_conflict(Some(expired), &ticket).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_set_ticket_conflict() {
+        let key = "tagctl:v1/admin/mpa/ticket";
+        let mock = MockIam::new()
+            .with_role("expired", &[(key, "by/carol/exp=1618033988/for/bob")])
+            .with_role("held", &[(key, "by/carol/exp=4102444800/for/bob")]);
+        let manager = RoleApprovalManager::new(mock.client());
+        let ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+
+        let expired = IamPrincipal::role(ACCOUNT_ID, "expired");
+        manager.set_ticket(&expired, ticket.clone(), false).await.unwrap();
+        assert_eq!(manager.get_ticket(&expired).await.unwrap(), Some(ticket.clone()));
+
+        let held = IamPrincipal::role(ACCOUNT_ID, "held");
+        let err = manager.set_ticket(&held, ticket.clone(), false).await.unwrap_err();
+        assert!(matches!(err, SetTicketError::Conflict { existing } if existing.giver.to_string() == "carol"));
+        assert_eq!(mock.calls("TagRole"), 1);
+
+        manager.set_ticket(&held, ticket.clone(), true).await.unwrap();
+        assert_eq!(manager.get_ticket(&held).await.unwrap(), Some(ticket));
+    }
+
+    #[tokio::test]
+    async fn test_list_all_tickets() {
+        let key = "tagctl:v1/admin/mpa/ticket";
+        let account = || {
+            MockIam::new()
+                .with_page_size(2)
+                .with_role("admin", &[(key, "by/alice/exp=1618033988/for/bob")])
+                .with_role("broken", &[(key, "garbage")])
+                .with_role("plain", &[("team", "core")])
+        };
+        let scenarios = [
+            (account(), Listing::Bulk, 2, 0),
+            (account(), Listing::PerPrincipal, 0, 3),
+            (account().deny("GetAccountAuthorizationDetails"), Listing::Bulk, 1, 3),
+        ];
+        for (mock, listing, bulk_calls, tag_calls) in scenarios {
+            let manager = RoleApprovalManager::new(mock.client()).with_listing(listing);
+            let mut tickets: Vec<_> = manager.list_all_tickets().try_collect().await.unwrap();
+            tickets.sort_by(|a, b| a.0.cmp(&b.0));
+
+            assert_eq!(tickets.len(), 2, "{listing:?}");
+            assert_eq!(tickets[0].0, IamPrincipal::role(ACCOUNT_ID, "admin"));
+            assert!(matches!(&tickets[0].1, TicketTag::Valid(ticket) if ticket.giver.to_string() == "alice"));
+            assert_eq!(tickets[1].0.name, "broken");
+            assert!(matches!(&tickets[1].1, TicketTag::Malformed(tag) if tag.value == "garbage"));
+            assert_eq!(mock.calls("GetAccountAuthorizationDetails"), bulk_calls, "{listing:?}");
+            assert_eq!(mock.calls("ListRoleTags"), tag_calls, "{listing:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_next_to_ticket() {
+        let (v1, v2) = ("tagctl:v1/admin/mpa/ticket", "tagctl:v2/admin/mpa/ticket");
+        let schema = TagSchema::new("tagctl:v2".parse().unwrap())
+            .with_readable_versions([1])
+            .unwrap();
+        let mock = MockIam::new().with_role("admin", &[(v2, "by/alice/exp=1618033988/for/bob"), (v1, "garbage")]);
+        let admin = IamPrincipal::role(ACCOUNT_ID, "admin");
+        for listing in [Listing::Bulk, Listing::PerPrincipal] {
+            let manager = RoleApprovalManager::new(mock.client())
+                .with_schema(schema.clone())
+                .with_listing(listing);
+            let tags: Vec<_> = manager.list_all_tickets().try_collect().await.unwrap();
+            assert_eq!(tags.len(), 2, "{listing:?}");
+            assert!(matches!(&tags[0].1, TicketTag::Valid(ticket) if ticket.giver.to_string() == "alice"));
+            assert!(matches!(&tags[1].1, TicketTag::Malformed(tag) if tag.key == v1));
+        }
+
+        let manager = RoleApprovalManager::new(mock.client()).with_schema(schema);
+        assert!(!manager.unset_malformed_ticket(&admin, v1, "other").await.unwrap());
+        assert!(manager.unset_malformed_ticket(&admin, v1, "garbage").await.unwrap());
+        assert_eq!(
+            mock.role_tags("admin").unwrap(),
+            vec![(v2.to_string(), "by/alice/exp=1618033988/for/bob".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_throttled_calls() {
+        let key = "tagctl:v1/admin/mpa/ticket";
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        };
+        let mock = MockIam::new()
+            .with_role("admin", &[(key, "by/alice/exp=1618033988/for/bob")])
+            .throttle("ListRoleTags", 2)
+            .throttle("GetAccountAuthorizationDetails", 1);
+        let admin = IamPrincipal::role(ACCOUNT_ID, "admin");
+        let metrics = Arc::new(RetryMetrics::default());
+        let manager = RoleApprovalManager::new(mock.client())
+            .with_retry_metrics(metrics.clone())
+            .with_retry_policy(policy);
+
+        assert!(manager.get_ticket(&admin).await.unwrap().is_some());
+        let tickets: Vec<_> = manager.list_all_tickets().try_collect().await.unwrap();
+        assert_eq!(tickets.len(), 1);
+        let stats = metrics.stats();
+        assert_eq!(
+            (stats.calls, stats.retries, stats.throttled, stats.exhausted),
+            (5, 3, 3, 0)
+        );
+
+        let mock = mock.throttle("ListRoleTags", 3);
+        let err = manager.get_ticket(&admin).await.unwrap_err();
+        let err = anyhow::Error::from(err);
+        assert!(matches!(ApiError::find(&err), Some(e) if e.kind == ApiErrorKind::Throttling));
+        assert_eq!(mock.calls("ListRoleTags"), 6);
+        assert_eq!(manager.retry_metrics().stats().exhausted, 1);
+
+        // the principals listed one by one are paginated by the same retrying client
+        let mock = mock.throttle("ListRoles", 1);
+        let manager = RoleApprovalManager::new(mock.client())
+            .with_listing(Listing::PerPrincipal)
+            .with_retry_policy(policy);
+        let tickets: Vec<_> = manager.list_all_tickets().try_collect().await.unwrap();
+        assert_eq!(tickets.len(), 1);
+        assert_eq!(manager.retry_metrics().stats().throttled, 1);
+    }
+
+    #[tokio::test]
+    async fn test_principals() {
+        let mock = MockIam::new().with_role("admin", &[]).with_user("alice", &[]);
+        let roles = RoleApprovalManager::new(mock.client());
+
+        let admin = roles.principal("admin").await.unwrap();
+        assert_eq!(admin.to_string(), format!("arn:aws:iam::{ACCOUNT_ID}:role/admin"));
+        assert!(matches!(
+            roles.principal("missing").await,
+            Err(ListPrincipalsError::NotFound { kind: PrincipalKind::Role, name }) if name == "missing"
+        ));
+
+        let alice = UserApprovalManager::new(mock.client())
+            .principal("alice")
+            .await
+            .unwrap();
+        assert!(matches!(
+            roles.get_ticket(&alice).await,
+            Err(ListTicketsError::Principal(PrincipalError::WrongKind { .. }))
+        ));
+        assert_eq!(mock.calls("ListRoleTags"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_principal_of_other_account() {
+        let mock = MockIam::new().with_role("admin", &[]);
+        let roles = RoleApprovalManager::new(mock.client()).with_account(ACCOUNT_ID);
+        let admin = roles.principal("admin").await.unwrap();
+        assert!(roles.get_ticket(&admin).await.unwrap().is_none());
+
+        // a role of the same name in another account is refused rather than read or untagged in this one
+        let other = IamPrincipal::role("210987654321", "admin");
+        assert!(matches!(
+            roles.get_ticket(&other).await,
+            Err(ListTicketsError::Principal(PrincipalError::OtherAccount { account, .. })) if account == ACCOUNT_ID
+        ));
+        assert!(matches!(
+            roles.unset_ticket(&other).await,
+            Err(UnsetTicketError::Principal(PrincipalError::OtherAccount { .. }))
+        ));
+        assert_eq!(mock.calls("ListRoleTags"), 1);
+        assert_eq!(mock.calls("UntagRole"), 0);
+
+        let roles = RoleApprovalManager::new(mock.client()).with_account("210987654321");
+        assert!(matches!(
+            roles.principal("admin").await,
+            Err(ListPrincipalsError::Principal(PrincipalError::OtherAccount { .. }))
+        ));
+    }
+}
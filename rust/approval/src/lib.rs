@@ -1,4 +1,19 @@
+pub mod catalog;
+#[cfg(feature = "chainable")]
+pub mod chain;
+pub mod error;
+pub mod guard;
 pub mod iam;
+pub mod org;
+pub mod policy;
+pub mod principal;
+pub mod rcp;
+pub mod retry;
+pub mod scp;
+#[cfg(feature = "signed")]
+pub mod signature;
+pub mod tag_policy;
+pub mod tags;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod ticket;
-
-mod tags;
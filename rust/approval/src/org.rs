@@ -0,0 +1,169 @@
+use crate::error::ApiError;
+use async_stream::try_stream;
+use aws_sdk_organizations::types::{ChildType, Parent, ParentType, PolicyType, TargetType};
+use aws_smithy_types_convert::stream::PaginationStreamExt;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+
+/// the ids of the accounts a policy applies to, attached directly or through the organizational units and roots
+/// above them. this is how the retention lambda and `tagctl migrate` find the accounts to visit.
+pub fn traverse_accounts_affected_by_policy<'a>(
+    client: &'a aws_sdk_organizations::Client,
+    policy_id: impl Into<String>,
+) -> impl Stream<Item = anyhow::Result<String>> + 'a {
+    let targets = client
+        .list_targets_for_policy()
+        .policy_id(policy_id)
+        .into_paginator()
+        .send()
+        .into_stream_03x()
+        .flat_map(|output| match output {
+            Ok(output) => stream::iter(
+                output
+                    .targets
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Ok)
+                    .collect::<Vec<_>>(),
+            ),
+            Err(err) => stream::iter(vec![Err(err)]),
+        });
+
+    targets
+        .map_ok(|target| {
+            let Some(target_id) = target.target_id else {
+                return stream::empty().boxed();
+            };
+            match target.r#type {
+                Some(TargetType::Account) => stream::iter(vec![Ok(target_id)]).boxed(),
+                Some(TargetType::Root | TargetType::OrganizationalUnit) => {
+                    traverse_account_tree(client, target_id).boxed()
+                }
+                _ => {
+                    tracing::warn!(msg = "Unknown target type", target_id =% target_id);
+                    stream::empty().boxed()
+                }
+            }
+        })
+        .try_flatten()
+}
+
+fn traverse_account_tree<'a>(
+    client: &'a aws_sdk_organizations::Client,
+    target_id: String,
+) -> impl Stream<Item = Result<String, anyhow::Error>> + Send + 'a {
+    try_stream! {
+        let accounts = list_accounts_for_target(client, &target_id);
+        for await account in accounts {
+            yield account?;
+        }
+
+        let org_units = list_org_units_for_target(client, &target_id);
+        for await ou in org_units {
+            let nested = traverse_account_tree(client, ou?).boxed();
+            for await account in nested {
+                yield account?;
+            }
+        }
+    }
+}
+
+fn list_accounts_for_target(
+    client: &aws_sdk_organizations::Client,
+    target_id: &str,
+) -> impl Stream<Item = Result<String, anyhow::Error>> {
+    client
+        .list_children()
+        .parent_id(target_id)
+        .child_type(ChildType::Account)
+        .into_paginator()
+        .send()
+        .into_stream_03x()
+        .map_ok(|output| {
+            let account_ids = output
+                .children
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|child| child.id)
+                .map(Ok);
+            stream::iter(account_ids)
+        })
+        .try_flatten()
+}
+
+fn list_org_units_for_target(
+    client: &aws_sdk_organizations::Client,
+    target_id: &str,
+) -> impl Stream<Item = Result<String, anyhow::Error>> {
+    client
+        .list_children()
+        .parent_id(target_id)
+        .child_type(ChildType::OrganizationalUnit)
+        .into_paginator()
+        .send()
+        .into_stream_03x()
+        .map_ok(|output| {
+            let ou_ids = output
+                .children
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|child| child.id)
+                .map(Ok);
+            stream::iter(ou_ids)
+        })
+        .try_flatten()
+}
+
+/// an SCP applying to an account, and the account, organizational unit or root it is attached to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedPolicy {
+    pub id: String,
+    pub name: String,
+    pub target_id: String,
+}
+
+/// the SCPs applying to an account, attached to the account itself or inherited from the organizational units
+/// and root above it. listing them is only permitted from the management account or a delegated administrator.
+pub async fn list_policies_applying_to_account(
+    client: &aws_sdk_organizations::Client,
+    account_id: &str,
+) -> Result<Vec<AppliedPolicy>, ApiError> {
+    let mut policies = vec![];
+    let mut target_id = account_id.to_string();
+    loop {
+        let pages: Vec<_> = client
+            .list_policies_for_target()
+            .target_id(&target_id)
+            .filter(PolicyType::ServiceControlPolicy)
+            .into_paginator()
+            .send()
+            .into_stream_03x()
+            .try_collect()
+            .await
+            .map_err(ApiError::from_sdk)?;
+        let summaries = pages.into_iter().flat_map(|page| page.policies.unwrap_or_default());
+        policies.extend(summaries.map(|policy| AppliedPolicy {
+            id: policy.id.unwrap_or_default(),
+            name: policy.name.unwrap_or_default(),
+            target_id: target_id.clone(),
+        }));
+
+        let parent = client
+            .list_parents()
+            .child_id(&target_id)
+            .send()
+            .await
+            .map_err(ApiError::from_sdk)?
+            .parents
+            .unwrap_or_default()
+            .into_iter()
+            .next();
+        match parent {
+            Some(Parent {
+                id: Some(id),
+                r#type: Some(ParentType::OrganizationalUnit | ParentType::Root),
+                ..
+            }) => target_id = id,
+            _ => return Ok(policies),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// the version of the IAM policy language
+pub const POLICY_VERSION: &str = "2012-10-17";
+
+/// a policy element holding one string, or many
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<Vec<String>> for OneOrMany {
+    fn from(mut values: Vec<String>) -> Self {
+        match values.len() {
+            1 => OneOrMany::One(values.remove(0)),
+            _ => OneOrMany::Many(values),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyStatement {
+    pub sid: String,
+    pub effect: String,
+    /// only set in resource-based policies, e.g. resource control policies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub principal: Option<OneOrMany>,
+    pub action: OneOrMany,
+    pub resource: OneOrMany,
+    pub condition: BTreeMap<String, BTreeMap<String, OneOrMany>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyDocument {
+    pub version: String,
+    pub statement: Vec<PolicyStatement>,
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt::Display, str::FromStr};
+use thiserror::Error;
+
+/// the kinds of IAM principals which hold tickets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrincipalKind {
+    Role,
+    User,
+}
+
+impl PrincipalKind {
+    /// the resource type of the kind in an ARN
+    pub fn resource_type(&self) -> &'static str {
+        match self {
+            PrincipalKind::Role => "role",
+            PrincipalKind::User => "user",
+        }
+    }
+}
+
+/// an IAM role or user, e.g. `arn:aws:iam::123456789012:role/team/deployer`.
+/// (de)serializes as its ARN.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IamPrincipal {
+    /// `aws`, `aws-cn` or `aws-us-gov`
+    pub partition: String,
+    pub account_id: String,
+    pub kind: PrincipalKind,
+    /// starts and ends with a slash, `/` by default
+    pub path: String,
+    pub name: String,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PrincipalParseError {
+    #[error("not an IAM role or user ARN: {0}")]
+    NotAPrincipal(String),
+    #[error("invalid account id in {0}")]
+    InvalidAccount(String),
+}
+
+impl IamPrincipal {
+    /// the principal `name` of `kind` in `account_id`, at path `/` of the `aws` partition
+    pub fn new(kind: PrincipalKind, account_id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            partition: "aws".to_string(),
+            account_id: account_id.into(),
+            kind,
+            path: "/".to_string(),
+            name: name.into(),
+        }
+    }
+
+    pub fn role(account_id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self::new(PrincipalKind::Role, account_id, name)
+    }
+
+    pub fn user(account_id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self::new(PrincipalKind::User, account_id, name)
+    }
+
+    pub fn with_path(self, path: impl Into<String>) -> Self {
+        Self {
+            path: normalize_path(&path.into()),
+            ..self
+        }
+    }
+}
+
+/// wraps the path in slashes, as IAM returns it
+fn normalize_path(path: &str) -> String {
+    let inner = path.trim_matches('/');
+    if inner.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{inner}/")
+    }
+}
+
+impl Display for IamPrincipal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "arn:{}:iam::{}:{}{}{}",
+            self.partition,
+            self.account_id,
+            self.kind.resource_type(),
+            self.path,
+            self.name
+        )
+    }
+}
+
+impl FromStr for IamPrincipal {
+    type Err = PrincipalParseError;
+
+    fn from_str(arn: &str) -> Result<Self, Self::Err> {
+        let not_a_principal = || PrincipalParseError::NotAPrincipal(arn.to_string());
+        let ["arn", partition, "iam", "", account_id, resource] = arn.splitn(6, ':').collect::<Vec<_>>()[..] else {
+            return Err(not_a_principal());
+        };
+        if account_id.len() != 12 || !account_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(PrincipalParseError::InvalidAccount(arn.to_string()));
+        }
+        let (kind, path) = match resource.split_once('/') {
+            Some(("role", path)) => (PrincipalKind::Role, path),
+            Some(("user", path)) => (PrincipalKind::User, path),
+            _ => return Err(not_a_principal()),
+        };
+        let (path, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if partition.is_empty() || name.is_empty() {
+            return Err(not_a_principal());
+        }
+        Ok(Self {
+            partition: partition.to_string(),
+            account_id: account_id.to_string(),
+            kind,
+            path: normalize_path(path),
+            name: name.to_string(),
+        })
+    }
+}
+
+impl Serialize for IamPrincipal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for IamPrincipal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IamPrincipal, PrincipalKind, PrincipalParseError};
+
+    #[test]
+    fn test_parse_principal() {
+        let role: IamPrincipal = "arn:aws:iam::123456789012:role/team/ci/deployer".parse().unwrap();
+        assert_eq!(role.kind, PrincipalKind::Role);
+        assert_eq!(role.account_id, "123456789012");
+        assert_eq!(role.path, "/team/ci/");
+        assert_eq!(role.name, "deployer");
+        assert_eq!(role.to_string(), "arn:aws:iam::123456789012:role/team/ci/deployer");
+
+        let user: IamPrincipal = "arn:aws-cn:iam::123456789012:user/alice".parse().unwrap();
+        assert_eq!(
+            user,
+            IamPrincipal {
+                partition: "aws-cn".to_string(),
+                ..IamPrincipal::user("123456789012", "alice")
+            }
+        );
+        assert_eq!(user.path, "/");
+        assert_eq!(
+            IamPrincipal::role("123456789012", "deployer")
+                .with_path("team")
+                .to_string(),
+            "arn:aws:iam::123456789012:role/team/deployer"
+        );
+
+        let json = serde_json::to_string(&role).unwrap();
+        assert_eq!(json, "\"arn:aws:iam::123456789012:role/team/ci/deployer\"");
+        assert_eq!(serde_json::from_str::<IamPrincipal>(&json).unwrap(), role);
+
+        for arn in [
+            "arn:aws:sts::123456789012:assumed-role/deployer/alice",
+            "arn:aws:iam::123456789012:policy/deny",
+            "arn:aws:iam::123456789012:role/",
+            "deployer",
+        ] {
+            assert!(
+                matches!(arn.parse::<IamPrincipal>(), Err(PrincipalParseError::NotAPrincipal(_))),
+                "{arn}"
+            );
+        }
+        assert_eq!(
+            "arn:aws:iam::1234:role/deployer".parse::<IamPrincipal>(),
+            Err(PrincipalParseError::InvalidAccount(
+                "arn:aws:iam::1234:role/deployer".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    catalog::ActionCatalog,
+    policy::{OneOrMany, PolicyDocument, PolicyStatement, POLICY_VERSION},
+    scp::{SealKind, SEAL_KINDS},
+    tags::Namespace,
+};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// the services resource control policies apply to. RCPs deny nothing on the resources of other services.
+pub const RCP_SERVICES: &[&str] = &["s3", "sts", "kms", "sqs", "secretsmanager"];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RcpError {
+    #[error("expected an organization id like o-a1b2c3d4e5, got {0:?}")]
+    InvalidOrganizationId(String),
+}
+
+/// whether resource control policies apply to the resources of `action`
+pub fn covers(action: &str) -> bool {
+    action
+        .split_once(':')
+        .is_some_and(|(service, _)| RCP_SERVICES.iter().any(|s| s.eq_ignore_ascii_case(service)))
+}
+
+/// the actions of the RCP services a seal kind denies. RCPs support neither `*` nor `NotAction`,
+/// so seals are spelled out per service, or per action of the catalog when they seal part of a service.
+/// services missing from the catalog are sealed whole, unless the kind spares `<service>:*`.
+pub fn sealed_actions(kind: &SealKind, catalog: &ActionCatalog) -> Vec<String> {
+    let mut sealed = vec![];
+    for service in RCP_SERVICES {
+        let whole = format!("{}:*", service);
+        let actions: Vec<&str> = catalog.expand(&whole).collect();
+        if actions.is_empty() {
+            if kind.seals(&whole) {
+                sealed.push(whole);
+            }
+            continue;
+        }
+        let sealed_in_service: Vec<&str> = actions.iter().copied().filter(|action| kind.seals(action)).collect();
+        if sealed_in_service.len() == actions.len() {
+            sealed.push(whole);
+        } else {
+            sealed.extend(sealed_in_service.into_iter().map(str::to_string));
+        }
+    }
+    sealed
+}
+
+/// the resource control policy mirroring the seal kinds of the control-tags SCP.
+/// it denies the sealed actions to principals outside `organization_id`, whose tickets cannot be trusted,
+/// while the SCP keeps governing the principals of the organization. AWS service principals are exempt.
+pub fn render(
+    namespace: &Namespace,
+    organization_id: &str,
+    catalog: &ActionCatalog,
+) -> Result<PolicyDocument, RcpError> {
+    let valid = organization_id.strip_prefix("o-").is_some_and(|id| {
+        (10..=32).contains(&id.len()) && id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    });
+    if !valid {
+        return Err(RcpError::InvalidOrganizationId(organization_id.to_string()));
+    }
+
+    let one = |value: &str| OneOrMany::One(value.to_string());
+    let statement = SEAL_KINDS
+        .iter()
+        .map(|kind| PolicyStatement {
+            sid: kind.rcp_sid.to_string(),
+            effect: "Deny".to_string(),
+            principal: Some(one("*")),
+            action: sealed_actions(kind, catalog).into(),
+            resource: one("*"),
+            condition: BTreeMap::from([
+                (
+                    "StringEquals".to_string(),
+                    BTreeMap::from([(format!("aws:ResourceTag/{}", namespace.seal_kind_key()), one(kind.name))]),
+                ),
+                (
+                    "StringNotEqualsIfExists".to_string(),
+                    BTreeMap::from([("aws:PrincipalOrgID".to_string(), one(organization_id))]),
+                ),
+                (
+                    "BoolIfExists".to_string(),
+                    BTreeMap::from([("aws:PrincipalIsAWSService".to_string(), one("false"))]),
+                ),
+            ]),
+        })
+        .collect();
+    Ok(PolicyDocument {
+        version: POLICY_VERSION.to_string(),
+        statement,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{covers, render, RcpError};
+    use crate::{catalog::ActionCatalog, tags::Namespace};
+    use serde_json::json;
+
+    #[test]
+    fn test_render_rcp() {
+        let document = render(&Namespace::default(), "o-a1b2c3d4e5", ActionCatalog::bundled()).unwrap();
+        let document = serde_json::to_value(document).unwrap();
+        let statements = document["Statement"].as_array().unwrap();
+        assert_eq!(statements.len(), 2);
+
+        let total = &statements[0];
+        assert_eq!(total["Sid"], "CTRCPKB0");
+        assert_eq!(total["Principal"], "*");
+        assert_eq!(
+            total["Action"],
+            json!(["s3:*", "sts:*", "kms:*", "sqs:*", "secretsmanager:*"])
+        );
+        assert_eq!(
+            total["Condition"]["StringEquals"]["aws:ResourceTag/tagctl:v1/admin/mpa/seal/kind"],
+            "total"
+        );
+        assert_eq!(
+            total["Condition"]["StringNotEqualsIfExists"]["aws:PrincipalOrgID"],
+            "o-a1b2c3d4e5"
+        );
+        assert_eq!(
+            statements[1]["Action"],
+            json!(["s3:*", "kms:*", "sqs:*", "secretsmanager:*"])
+        );
+
+        assert_eq!(
+            render(&Namespace::default(), "a1b2c3d4e5", ActionCatalog::bundled()),
+            Err(RcpError::InvalidOrganizationId("a1b2c3d4e5".to_string()))
+        );
+        assert!(covers("S3:GetObject"));
+        assert!(!covers("iam:PassRole"));
+    }
+}
@@ -0,0 +1,229 @@
+//! retries of throttled or failed IAM calls by the SDK, which also slows down all calls of a client while IAM throttles
+
+use crate::error::{kind_of_code, ApiErrorKind};
+use aws_sdk_iam::config::{
+    interceptors::FinalizerInterceptorContextRef,
+    retry::{ClientRateLimiter, RetryConfig, RetryPartition},
+    ConfigBag, Intercept, RuntimeComponents,
+};
+use aws_smithy_runtime_api::{box_error::BoxError, client::retries::RequestAttempts};
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// how often and how patiently a failed call is retried, by the adaptive retry mode of the SDK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// the number of attempts of a call, including the first one
+    pub max_attempts: u32,
+    /// the backoff after the first failed attempt, doubling with each further attempt
+    pub base_delay: Duration,
+    /// the upper bound of a backoff
+    pub max_delay: Duration,
+}
+
+/// the backoffs of a call add up to less than a second, well within the timeout of the retention lambda
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(400),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// calls are attempted once
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    fn retry_config(&self) -> RetryConfig {
+        RetryConfig::adaptive()
+            .with_max_attempts(self.max_attempts)
+            .with_initial_backoff(self.base_delay)
+            .with_max_backoff(self.max_delay)
+    }
+}
+
+/// counts of the calls made by the managers sharing it
+#[derive(Debug, Default)]
+pub struct RetryMetrics {
+    calls: AtomicU64,
+    retries: AtomicU64,
+    throttled: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+/// a snapshot of [`RetryMetrics`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryStats {
+    /// attempts, including retries
+    pub calls: u64,
+    pub retries: u64,
+    /// attempts failing with [`ApiErrorKind::Throttling`]
+    pub throttled: u64,
+    /// calls which still failed with a retryable error after the last attempt
+    pub exhausted: u64,
+}
+
+impl RetryMetrics {
+    pub fn stats(&self) -> RetryStats {
+        RetryStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// an IAM client whose calls are retried under `policy` and counted into `metrics`.
+/// it replaces the retry configuration of the client it is built from, so that calls are never retried twice over,
+/// and rate limits its calls on its own while IAM throttles, instead of sharing the limiter of the process.
+#[derive(Debug, Clone)]
+pub(crate) struct RetryingClient {
+    base: Arc<aws_sdk_iam::Client>,
+    client: Arc<aws_sdk_iam::Client>,
+    partition: RetryPartition,
+    pub policy: RetryPolicy,
+    pub metrics: Arc<RetryMetrics>,
+}
+
+impl RetryingClient {
+    pub fn new(base: Arc<aws_sdk_iam::Client>) -> Self {
+        let partition = RetryPartition::custom("tagctl-iam")
+            .client_rate_limiter(ClientRateLimiter::default())
+            .build();
+        Self::build(base, partition, RetryPolicy::default(), Arc::default())
+    }
+
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self::build(self.base, self.partition, policy, self.metrics)
+    }
+
+    pub fn with_metrics(self, metrics: Arc<RetryMetrics>) -> Self {
+        Self::build(self.base, self.partition, self.policy, metrics)
+    }
+
+    fn build(
+        base: Arc<aws_sdk_iam::Client>,
+        partition: RetryPartition,
+        policy: RetryPolicy,
+        metrics: Arc<RetryMetrics>,
+    ) -> Self {
+        let config = base
+            .config()
+            .to_builder()
+            .retry_config(policy.retry_config())
+            .retry_partition(partition.clone())
+            .interceptor(RetryCounter {
+                metrics: metrics.clone(),
+                max_attempts: policy.max_attempts,
+            })
+            .build();
+        Self {
+            client: Arc::new(aws_sdk_iam::Client::from_conf(config)),
+            base,
+            partition,
+            policy,
+            metrics,
+        }
+    }
+}
+
+impl Deref for RetryingClient {
+    type Target = aws_sdk_iam::Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+/// counts the attempts of the calls of a client, and those failing with a retryable error
+#[derive(Debug)]
+struct RetryCounter {
+    metrics: Arc<RetryMetrics>,
+    max_attempts: u32,
+}
+
+impl Intercept for RetryCounter {
+    fn name(&self) -> &'static str {
+        "RetryCounter"
+    }
+
+    fn read_after_attempt(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        RetryMetrics::count(&self.metrics.calls);
+        if attempts(cfg) > 1 {
+            RetryMetrics::count(&self.metrics.retries);
+        }
+        if failure(context).is_some_and(|(kind, _)| kind == ApiErrorKind::Throttling) {
+            RetryMetrics::count(&self.metrics.throttled);
+        }
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if attempts(cfg) >= self.max_attempts && failure(context).is_some_and(|(kind, code)| is_retryable(kind, code)) {
+            RetryMetrics::count(&self.metrics.exhausted);
+        }
+        Ok(())
+    }
+}
+
+/// the number of attempts of the call so far
+fn attempts(cfg: &ConfigBag) -> u32 {
+    cfg.load::<RequestAttempts>().map_or(1, |attempts| attempts.attempts())
+}
+
+/// the kind and the code of the error the last attempt failed with, if it failed.
+/// IAM reports the code in the xml body of the response.
+fn failure<'a>(context: &'a FinalizerInterceptorContextRef<'_>) -> Option<(ApiErrorKind, Option<&'a str>)> {
+    let error = context.output_or_error()?.err()?;
+    if error.is_timeout_error() || error.is_connector_error() || error.is_response_error() {
+        return Some((ApiErrorKind::Transport, None));
+    }
+    let code = context
+        .response()
+        .and_then(|response| std::str::from_utf8(response.body().bytes()?).ok())
+        .and_then(|body| body.split_once("<Code>"))
+        .and_then(|(_, code)| code.split_once("</Code>"))
+        .map(|(code, _)| code);
+    Some((code.map_or(ApiErrorKind::Service, kind_of_code), code))
+}
+
+/// throttling, transport failures and internal errors of the service are worth retrying
+fn is_retryable(kind: ApiErrorKind, code: Option<&str>) -> bool {
+    match kind {
+        ApiErrorKind::Throttling | ApiErrorKind::Transport => true,
+        ApiErrorKind::Service => matches!(
+            code,
+            Some("ServiceFailure" | "ServiceFailureException" | "InternalFailure" | "ServiceUnavailable")
+        ),
+        ApiErrorKind::AccessDenied | ApiErrorKind::NotFound => false,
+    }
+}
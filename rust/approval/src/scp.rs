@@ -0,0 +1,526 @@
+use crate::{
+    catalog::action_matches,
+    rcp,
+    tags::{self, Namespace},
+    ticket::ApprovalTicket,
+};
+use serde::Serialize;
+
+/// a deny statement of the control tags SCPs, as emitted by the terraform module
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Statement {
+    /// the statement key in the terraform module
+    pub key: &'static str,
+    /// the short SID, emitted by default
+    pub sid: &'static str,
+    /// whether the SID follows `emit_scp_sids`. fixed SIDs are always emitted as is.
+    pub selectable: bool,
+    /// what the statement denies, in plain language
+    pub summary: &'static str,
+    /// how to get the denied request through
+    pub remedy: &'static str,
+}
+
+impl Statement {
+    /// the SID emitted with `emit_scp_sids = "long"`, e.g. `CtlNoGrant` for `ctl_no_grant`
+    pub fn long_sid(&self) -> Option<String> {
+        if !self.selectable {
+            return None;
+        }
+        let sid = self
+            .key
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                    .unwrap_or_default()
+            })
+            .collect();
+        Some(sid)
+    }
+
+    /// finds a statement by its short or long SID
+    pub fn find(sid: &str) -> Option<&'static Statement> {
+        STATEMENTS
+            .iter()
+            .find(|s| s.sid == sid || s.long_sid().as_deref() == Some(sid))
+    }
+}
+
+pub const STATEMENTS: &[Statement] = &[
+    Statement {
+        key: "ctl_no_grant",
+        sid: "CT00",
+        selectable: true,
+        summary: "the request sets or removes a control tag, but the calling principal has no grant area tag",
+        remedy: "use a principal tagged with a grant area, e.g. assume your mirror role with `tagctl mirror assume`",
+    },
+    Statement {
+        key: "ctl_outside_grant",
+        sid: "CT01",
+        selectable: true,
+        summary: "the request sets or removes a control tag outside the grant area of the calling principal",
+        remedy: "use a principal whose grant area covers the tag key, or only send control tags within your grant area",
+    },
+    Statement {
+        key: "ctl_lookalike",
+        sid: "CT02",
+        selectable: true,
+        summary: "the request uses a tag key that imitates the control tag prefix, e.g. `tagctl-` or `tagctl/`",
+        remedy: "rename the tag key so it does not start with `tagctl` followed by a separator",
+    },
+    Statement {
+        key: "anti_invalid_identity",
+        sid: "CT03",
+        selectable: true,
+        summary: "the request sets the reserved source identity `nil`",
+        remedy: "set a real human identity as source identity",
+    },
+    Statement {
+        key: "anti_impersonate_non_sso",
+        sid: "CT04",
+        selectable: true,
+        summary: "a principal which is not an SSO role nor an identity broker attempts to set a source identity",
+        remedy: "assume roles from an SSO role or from a principal tagged as identity broker",
+    },
+    Statement {
+        key: "anti_impersonate_sso",
+        sid: "CT05",
+        selectable: true,
+        summary: "an SSO role attempts to set a source identity other than its own session name",
+        remedy: "set your SSO session name as source identity, as `tagctl mirror assume` does",
+    },
+    Statement {
+        key: "anti_non_human",
+        sid: "CT06",
+        selectable: true,
+        summary: "a principal without a source identity attempts to set a multi-party approval tag",
+        remedy: "set the ticket from a session with a source identity, e.g. one created by `tagctl mirror assume`",
+    },
+    Statement {
+        key: "anti_reflexive",
+        sid: "CT07",
+        selectable: true,
+        summary: "the ticket names the caller's own identity as receiver, i.e. the caller approves themselves",
+        remedy: "ask another human to give you the ticket",
+    },
+    Statement {
+        key: "anti_forge",
+        sid: "CT08",
+        selectable: true,
+        summary: "the ticket names someone other than the caller's identity as giver",
+        remedy: "give tickets only in your own name, `by/<your source identity>/...`",
+    },
+    Statement {
+        key: "seal_op_no_approval",
+        sid: "CTRS0",
+        selectable: true,
+        summary: "the request sets or removes a resource seal, but the caller holds no ticket naming them as receiver",
+        remedy: "ask another human for a ticket with `tagctl ticket set <you>`, then retry",
+    },
+    Statement {
+        key: "seal_op_outside_grant",
+        sid: "CTRS1",
+        selectable: true,
+        summary: "the request seals a resource with a grant outside the grant area of the calling principal",
+        remedy: "seal with a grant within your grant area",
+    },
+    Statement {
+        key: "seal_kind_total",
+        sid: "CTRSKB0",
+        selectable: false,
+        summary: "the resource is sealed with kind `total`, which denies every action without approval",
+        remedy: "ask another human for a ticket with `tagctl ticket set <you>`, then retry",
+    },
+    Statement {
+        key: "seal_kind_trust_relay",
+        sid: "CTRSKB1",
+        selectable: false,
+        summary: "the resource is sealed with kind `trust_relay`, which denies all but reads and sts actions without approval",
+        remedy: "ask another human for a ticket with `tagctl ticket set <you>`, then retry",
+    },
+    Statement {
+        key: "rcp_seal_kind_total",
+        sid: "CTRCPKB0",
+        selectable: false,
+        summary: "the resource is sealed with kind `total`, which its RCP denies to principals outside the organization",
+        remedy: "no action is open to outside principals, act from the organization with a ticket naming you as receiver",
+    },
+    Statement {
+        key: "rcp_seal_kind_trust_relay",
+        sid: "CTRCPKB1",
+        selectable: false,
+        summary: "the resource is sealed with kind `trust_relay`, which its RCP denies to principals outside the organization",
+        remedy: "only sts actions are open to outside principals, assume a role of the organization to relay the request",
+    },
+    Statement {
+        key: "trusted_stacksets_exec",
+        sid: "CFTSSE",
+        selectable: false,
+        summary: "the request modifies the stacksets execution role, which only the stacksets service may change",
+        remedy: "change the role through its stack set instead",
+    },
+    Statement {
+        key: "guard_actions",
+        sid: "GuardActions",
+        selectable: false,
+        summary: "the action is guarded, and the caller holds no ticket naming them as receiver",
+        remedy: "ask another human for a ticket with `tagctl ticket set <you>`, then retry",
+    },
+];
+
+/// the condition key holding the human identity of the caller, compared with the receiver of tickets
+pub const HUMAN_IDENTITY_KEY: &str = "aws:SourceIdentity";
+/// the value substituted for a missing human identity, which no ticket names as receiver
+pub const INVALID_IDENTITY: &str = "nil";
+
+/// a builtin kind of resource seal, as emitted by the terraform module.
+/// actions on a resource sealed with it are denied, unless the caller holds a ticket naming them as receiver.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SealKind {
+    pub name: &'static str,
+    pub sid: &'static str,
+    /// the SID of the statement mirroring the seal in the resource control policy
+    pub rcp_sid: &'static str,
+    pub actions: &'static [&'static str],
+    pub not_actions: &'static [&'static str],
+}
+
+impl SealKind {
+    pub fn find(name: &str) -> Option<&'static SealKind> {
+        SEAL_KINDS.iter().find(|kind| kind.name == name)
+    }
+
+    /// whether the seal denies `action` without a ticket
+    pub fn seals(&self, action: &str) -> bool {
+        self.actions.iter().any(|pattern| action_matches(pattern, action))
+            || (!self.not_actions.is_empty() && !self.not_actions.iter().any(|pattern| action_matches(pattern, action)))
+    }
+}
+
+pub const SEAL_KINDS: &[SealKind] = &[
+    SealKind {
+        name: "total",
+        sid: "CTRSKB0",
+        rcp_sid: "CTRCPKB0",
+        actions: &["*"],
+        not_actions: &[],
+    },
+    SealKind {
+        name: "trust_relay",
+        sid: "CTRSKB1",
+        rcp_sid: "CTRCPKB1",
+        actions: &[],
+        not_actions: &["iam:Get*", "iam:List*", "sts:*"],
+    },
+];
+
+/// what is known about a tagging request that was denied. unknown facts are `None`.
+#[derive(Debug, Default, Clone)]
+pub struct TaggingAttempt {
+    /// the control tag namespace the SCPs were rendered for
+    pub namespace: Namespace,
+    /// the keys of the tags set or removed by the request
+    pub tag_keys: Vec<String>,
+    /// the ticket set by the request, if any
+    pub ticket: Option<ApprovalTicket>,
+    /// the source identity of the caller. `Some(None)` if the caller is known to have none.
+    pub source_identity: Option<Option<String>>,
+    /// the grant area of the caller. `Some(None)` if the caller is known to have none.
+    pub grant_area: Option<Option<String>>,
+}
+
+impl TaggingAttempt {
+    /// an attempt to set `ticket` on a principal
+    pub fn set_ticket(namespace: &Namespace, ticket: ApprovalTicket) -> Self {
+        Self {
+            namespace: namespace.clone(),
+            tag_keys: vec![namespace.ticket_key()],
+            ticket: Some(ticket),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Certainty {
+    /// the statement was named by AWS
+    Matched,
+    /// the statement denies the request given the known facts
+    Likely,
+    /// the statement may deny the request, but the facts needed to tell are unknown
+    Possible,
+}
+
+/// the statements which may have denied the attempt, by replaying the SCP conditions locally
+pub fn evaluate(attempt: &TaggingAttempt) -> Vec<(&'static Statement, Certainty)> {
+    let find = |sid| Statement::find(sid).expect("statement exists");
+    let mut findings = vec![];
+    let namespace = &attempt.namespace;
+
+    let control_prefix = namespace.control_prefix();
+    let control_keys: Vec<_> = attempt
+        .tag_keys
+        .iter()
+        .filter(|k| k.starts_with(&control_prefix))
+        .collect();
+    if !control_keys.is_empty() {
+        match &attempt.grant_area {
+            Some(None) => findings.push((find("CT00"), Certainty::Likely)),
+            Some(Some(area)) => {
+                let outside = control_keys
+                    .iter()
+                    .any(|k| k.as_str() != area && !k.starts_with(&format!("{}/", area)));
+                if outside {
+                    findings.push((find("CT01"), Certainty::Likely));
+                }
+            }
+            None => {
+                findings.push((find("CT00"), Certainty::Possible));
+                findings.push((find("CT01"), Certainty::Possible));
+            }
+        }
+    }
+
+    let lookalike = attempt.tag_keys.iter().any(|k| {
+        k.strip_prefix(namespace.root())
+            .is_some_and(|rest| rest.starts_with(tags::LOOKALIKE_SEPARATORS))
+    });
+    if lookalike {
+        findings.push((find("CT02"), Certainty::Likely));
+    }
+
+    let mpa_prefix = format!("{}/", namespace.mpa_key());
+    if attempt.tag_keys.iter().any(|k| k.starts_with(&mpa_prefix)) {
+        match &attempt.source_identity {
+            Some(None) => findings.push((find("CT06"), Certainty::Likely)),
+            None => findings.push((find("CT06"), Certainty::Possible)),
+            Some(Some(_)) => {}
+        }
+    }
+
+    if let Some(ticket) = &attempt.ticket {
+        match &attempt.source_identity {
+            Some(Some(identity)) => {
+                if ticket.receiver.to_string() == *identity {
+                    findings.push((find("CT07"), Certainty::Likely));
+                }
+                if ticket.giver.to_string() != *identity {
+                    findings.push((find("CT08"), Certainty::Likely));
+                }
+            }
+            _ => {
+                findings.push((find("CT07"), Certainty::Possible));
+                findings.push((find("CT08"), Certainty::Possible));
+            }
+        }
+    }
+
+    let seal_prefix = format!("{}/", namespace.seal_key());
+    if attempt.tag_keys.iter().any(|k| k.starts_with(&seal_prefix)) {
+        findings.push((find("CTRS0"), Certainty::Possible));
+    }
+
+    findings
+}
+
+/// the policies replayed by [`evaluate_access`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Enforcement {
+    /// the control-tags SCP only, which governs the principals of the organization
+    Scp,
+    /// the control-tags SCP, and the resource control policy rendered by `tagctl policy render --rcp`
+    Rcp,
+}
+
+/// what is known about a request to a resource which may be sealed. unknown facts are `None`.
+#[derive(Debug, Default, Clone)]
+pub struct ResourceAccess {
+    /// the action performed on the resource, e.g. `s3:PutObject`
+    pub action: String,
+    /// the seal kind of the resource. `Some(None)` if the resource is known to be unsealed.
+    pub seal_kind: Option<Option<String>>,
+    /// whether the principal belongs to the organization. AWS service principals are exempt from seals.
+    pub principal_in_org: Option<bool>,
+    /// the source identity of the caller. `Some(None)` if the caller is known to have none.
+    pub source_identity: Option<Option<String>>,
+    /// the ticket on the calling principal. `Some(None)` if it is known to hold none.
+    pub ticket: Option<Option<ApprovalTicket>>,
+}
+
+/// the seal statements which deny the access, by replaying the seal kinds of the SCP and, with
+/// [`Enforcement::Rcp`], of the resource control policy locally
+pub fn evaluate_access(access: &ResourceAccess, enforcement: Enforcement) -> Vec<(&'static Statement, Certainty)> {
+    let find = |sid| Statement::find(sid).expect("statement exists");
+    let kinds: Vec<(&SealKind, Certainty)> = match &access.seal_kind {
+        Some(None) => vec![],
+        Some(Some(name)) => SealKind::find(name)
+            .map(|kind| (kind, Certainty::Likely))
+            .into_iter()
+            .collect(),
+        None => SEAL_KINDS.iter().map(|kind| (kind, Certainty::Possible)).collect(),
+    };
+
+    // the ticket is compared with the source identity of the caller, `nil` when missing
+    let ticket = match (&access.ticket, &access.source_identity) {
+        (Some(None), _) | (Some(Some(_)), Some(None)) => Some(false),
+        (Some(Some(ticket)), Some(Some(identity))) => Some(ticket.receiver.to_string() == *identity),
+        _ => None,
+    };
+    let ticket_certainty = match ticket {
+        Some(true) => None,
+        Some(false) => Some(Certainty::Likely),
+        None => Some(Certainty::Possible),
+    };
+    let rcp_applies = enforcement == Enforcement::Rcp && rcp::covers(&access.action);
+
+    let mut findings = vec![];
+    for (kind, certainty) in kinds.into_iter().filter(|(kind, _)| kind.seals(&access.action)) {
+        if access.principal_in_org != Some(false) {
+            if let Some(ticket_certainty) = ticket_certainty {
+                let certainty = weakest([certainty, ticket_certainty, org_certainty(access, true)]);
+                findings.push((find(kind.sid), certainty));
+            }
+        }
+        if rcp_applies && access.principal_in_org != Some(true) {
+            findings.push((find(kind.rcp_sid), weakest([certainty, org_certainty(access, false)])));
+        }
+    }
+    findings
+}
+
+/// how certain it is that the principal is, or is not, within the organization
+fn org_certainty(access: &ResourceAccess, in_org: bool) -> Certainty {
+    match access.principal_in_org {
+        Some(known) if known == in_org => Certainty::Likely,
+        _ => Certainty::Possible,
+    }
+}
+
+fn weakest(certainties: impl IntoIterator<Item = Certainty>) -> Certainty {
+    let rank = |certainty: &Certainty| match certainty {
+        Certainty::Matched => 2,
+        Certainty::Likely => 1,
+        Certainty::Possible => 0,
+    };
+    certainties.into_iter().min_by_key(rank).unwrap_or(Certainty::Possible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, evaluate_access, Certainty, Enforcement, ResourceAccess, Statement, TaggingAttempt};
+    use crate::{
+        tags::Namespace,
+        ticket::{ApprovalTicket, HumanIdentity},
+    };
+
+    #[test]
+    fn test_find_statement() {
+        assert_eq!(Statement::find("CT07").map(|s| s.key), Some("anti_reflexive"));
+        assert_eq!(Statement::find("AntiReflexive").map(|s| s.sid), Some("CT07"));
+        assert_eq!(Statement::find("CtlNoGrant").map(|s| s.sid), Some("CT00"));
+        assert!(Statement::find("CT99").is_none());
+
+        let remedy = |sid| Statement::find(sid).map(|s| s.remedy);
+        assert_ne!(remedy("CTRCPKB0"), remedy("CTRCPKB1"));
+    }
+
+    #[test]
+    fn test_evaluate_reflexive_ticket() {
+        let ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("alice"));
+        let attempt = TaggingAttempt {
+            source_identity: Some(Some("alice".to_string())),
+            grant_area: Some(Some("tagctl:v1/admin".to_string())),
+            ..TaggingAttempt::set_ticket(&Namespace::default(), ticket)
+        };
+        let sids: Vec<_> = evaluate(&attempt).into_iter().map(|(s, c)| (s.sid, c)).collect();
+        assert_eq!(sids, vec![("CT07", Certainty::Likely)]);
+    }
+
+    #[test]
+    fn test_evaluate_no_grant_area() {
+        let ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        let attempt = TaggingAttempt {
+            source_identity: Some(Some("alice".to_string())),
+            grant_area: Some(None),
+            ..TaggingAttempt::set_ticket(&Namespace::default(), ticket)
+        };
+        let sids: Vec<_> = evaluate(&attempt).into_iter().map(|(s, c)| (s.sid, c)).collect();
+        assert_eq!(sids, vec![("CT00", Certainty::Likely)]);
+    }
+
+    #[test]
+    fn test_evaluate_lookalike() {
+        let attempt = TaggingAttempt {
+            tag_keys: vec!["tagctl-owner".to_string()],
+            ..Default::default()
+        };
+        let sids: Vec<_> = evaluate(&attempt).into_iter().map(|(s, _)| s.sid).collect();
+        assert_eq!(sids, vec!["CT02"]);
+    }
+
+    #[test]
+    fn test_evaluate_custom_namespace() {
+        let namespace: Namespace = "acme:v2".parse().unwrap();
+        let attempt = TaggingAttempt {
+            tag_keys: vec!["acme-owner".to_string(), "tagctl:v1/admin".to_string()],
+            grant_area: Some(None),
+            namespace,
+            ..Default::default()
+        };
+        let sids: Vec<_> = evaluate(&attempt).into_iter().map(|(s, _)| s.sid).collect();
+        assert_eq!(sids, vec!["CT02"]);
+    }
+
+    #[test]
+    fn test_evaluate_access_outside_org() {
+        let access = ResourceAccess {
+            action: "s3:PutObject".to_string(),
+            seal_kind: Some(Some("trust_relay".to_string())),
+            principal_in_org: Some(false),
+            ..Default::default()
+        };
+        assert!(evaluate_access(&access, Enforcement::Scp).is_empty());
+        let sids: Vec<_> = evaluate_access(&access, Enforcement::Rcp)
+            .into_iter()
+            .map(|(s, c)| (s.sid, c))
+            .collect();
+        assert_eq!(sids, vec![("CTRCPKB1", Certainty::Likely)]);
+
+        // trust_relay does not seal sts, and RCPs do not cover iam
+        for action in ["sts:AssumeRole", "iam:UpdateAssumeRolePolicy"] {
+            let access = ResourceAccess {
+                action: action.to_string(),
+                ..access.clone()
+            };
+            assert!(evaluate_access(&access, Enforcement::Rcp).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_evaluate_access_within_org() {
+        let ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        let mut access = ResourceAccess {
+            action: "kms:Decrypt".to_string(),
+            seal_kind: Some(Some("total".to_string())),
+            principal_in_org: Some(true),
+            source_identity: Some(Some("bob".to_string())),
+            ticket: Some(Some(ticket)),
+        };
+        assert!(evaluate_access(&access, Enforcement::Rcp).is_empty());
+
+        access.source_identity = Some(Some("carol".to_string()));
+        let sids: Vec<_> = evaluate_access(&access, Enforcement::Rcp)
+            .into_iter()
+            .map(|(s, c)| (s.sid, c))
+            .collect();
+        assert_eq!(sids, vec![("CTRSKB0", Certainty::Likely)]);
+
+        access.seal_kind = Some(None);
+        assert!(evaluate_access(&access, Enforcement::Rcp).is_empty());
+    }
+}
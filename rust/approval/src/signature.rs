@@ -0,0 +1,134 @@
+use crate::ticket::{ApprovalTicket, SignatureError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::Rng;
+use std::{collections::HashMap, str::FromStr};
+use thiserror::Error;
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// separates the `<giver>=<public key>` entries of a keyring
+const KEYRING_SEPARATOR: char = ',';
+
+#[derive(Error, Debug, PartialEq)]
+pub enum KeyError {
+    #[error("key is not valid unpadded base64url")]
+    Encoding,
+    #[error("key must be 32 bytes long")]
+    Length,
+    #[error("key is not a valid ed25519 public key")]
+    Invalid,
+    #[error("expected <giver>=<public key>, got {0:?}")]
+    MalformedEntry(String),
+}
+
+/// generates a fresh key for a giver to sign tickets with
+pub fn generate_signing_key() -> SigningKey {
+    SigningKey::from_bytes(&rand::thread_rng().gen())
+}
+
+/// encodes a signing or public key the way tagctl stores and exchanges them
+pub fn encode_key(bytes: &[u8; 32]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn decode_key(s: &str) -> Result<[u8; 32], KeyError> {
+    URL_SAFE_NO_PAD
+        .decode(s.trim())
+        .map_err(|_| KeyError::Encoding)?
+        .try_into()
+        .map_err(|_| KeyError::Length)
+}
+
+pub fn parse_signing_key(s: &str) -> Result<SigningKey, KeyError> {
+    decode_key(s).map(|bytes| SigningKey::from_bytes(&bytes))
+}
+
+pub fn parse_verifying_key(s: &str) -> Result<VerifyingKey, KeyError> {
+    VerifyingKey::from_bytes(&decode_key(s)?).map_err(|_| KeyError::Invalid)
+}
+
+/// the public keys trusted to sign tickets, by giver.
+/// parses from comma separated `<giver>=<public key>` entries.
+#[derive(Debug, Default, Clone)]
+pub struct Keyring {
+    keys: HashMap<String, VerifyingKey>,
+}
+
+impl Keyring {
+    pub fn insert(&mut self, giver: impl Into<String>, key: VerifyingKey) -> &Self {
+        self.keys.insert(giver.into(), key);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// checks that the ticket is signed by the trusted key of its giver
+    pub fn verify(&self, ticket: &ApprovalTicket) -> Result<(), SignatureError> {
+        let key = self
+            .keys
+            .get(&ticket.giver.to_string())
+            .ok_or_else(|| SignatureError::UnknownGiver(ticket.giver.clone()))?;
+        ticket.verify(key)
+    }
+}
+
+impl FromStr for Keyring {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keyring = Keyring::default();
+        for entry in s.split(KEYRING_SEPARATOR).map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((giver, key)) = entry.split_once('=') else {
+                return Err(KeyError::MalformedEntry(entry.to_string()));
+            };
+            keyring.insert(giver.trim(), parse_verifying_key(key)?);
+        }
+        Ok(keyring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_key, generate_signing_key, parse_signing_key, KeyError, Keyring};
+    use crate::ticket::{ApprovalTicket, HumanIdentity, SignatureError};
+
+    #[test]
+    fn test_keyring_verify() {
+        let alice = generate_signing_key();
+        let bob = generate_signing_key();
+        let keyring: Keyring = format!(
+            "alice={}, bob={}",
+            encode_key(alice.verifying_key().as_bytes()),
+            encode_key(bob.verifying_key().as_bytes())
+        )
+        .parse()
+        .unwrap();
+
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        ticket.sign(&alice).unwrap();
+        assert_eq!(keyring.verify(&ticket), Ok(()));
+
+        ticket.sign(&bob).unwrap();
+        assert_eq!(keyring.verify(&ticket), Err(SignatureError::Invalid));
+
+        let carol = ApprovalTicket::new(HumanIdentity::new("carol"), HumanIdentity::new("bob"));
+        assert_eq!(
+            keyring.verify(&carol),
+            Err(SignatureError::UnknownGiver(HumanIdentity::new("carol")))
+        );
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let key = generate_signing_key();
+        let parsed = parse_signing_key(&encode_key(key.as_bytes())).unwrap();
+        assert_eq!(parsed.as_bytes(), key.as_bytes());
+
+        assert_eq!(parse_signing_key("not base64!").unwrap_err(), KeyError::Encoding);
+        assert_eq!(parse_signing_key("AAAA").unwrap_err(), KeyError::Length);
+        assert!(matches!("alice".parse::<Keyring>(), Err(KeyError::MalformedEntry(_))));
+        assert!("".parse::<Keyring>().unwrap().is_empty());
+    }
+}
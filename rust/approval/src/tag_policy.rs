@@ -0,0 +1,97 @@
+use crate::{scp::SEAL_KINDS, tags::Namespace};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// an operator of the Organizations policy syntax, only `@@assign` is emitted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Assign<T> {
+    #[serde(rename = "@@assign")]
+    pub assign: T,
+}
+
+impl<T> From<T> for Assign<T> {
+    fn from(assign: T) -> Self {
+        Self { assign }
+    }
+}
+
+/// the rules of one tag key. Organizations keys them by the lowercased tag key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagRule {
+    /// the tag key with the casing compliant tags must use
+    pub tag_key: Assign<String>,
+    /// the values compliant tags may hold. `*` is only allowed at the end of a value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_value: Option<Assign<Vec<String>>>,
+    /// the resource types on which noncompliant tagging requests are denied, e.g. `iam:role`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enforced_for: Option<Assign<Vec<String>>>,
+}
+
+/// an AWS Organizations tag policy
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TagPolicy {
+    pub tags: BTreeMap<String, TagRule>,
+}
+
+impl TagPolicy {
+    /// the tag policy of the control tags of `namespace`: the casing of their keys, the seal kinds,
+    /// and the value patterns of grant areas and tickets.
+    /// noncompliant tags are only reported, unless resource types are listed in `enforced_for`.
+    pub fn for_control_tags(namespace: &Namespace, enforced_for: &[String]) -> Self {
+        let grant_areas = vec![format!("{}/*", namespace)];
+        let rules = [
+            (namespace.grant_area_key(), grant_areas.clone()),
+            (namespace.identity_broker_key(), vec!["true".to_string()]),
+            // tickets are rendered as `by/<giver>/<spec>/for/<receiver>`
+            (namespace.ticket_key(), vec!["by/*".to_string()]),
+            (
+                namespace.seal_kind_key(),
+                SEAL_KINDS.iter().map(|kind| kind.name.to_string()).collect(),
+            ),
+            (namespace.seal_grant_key(), grant_areas),
+        ];
+
+        let enforced_for = (!enforced_for.is_empty()).then(|| enforced_for.to_vec().into());
+        let tags = rules
+            .into_iter()
+            .map(|(key, values)| {
+                let rule = TagRule {
+                    tag_key: key.clone().into(),
+                    tag_value: Some(values.into()),
+                    enforced_for: enforced_for.clone(),
+                };
+                (key.to_lowercase(), rule)
+            })
+            .collect();
+        Self { tags }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TagPolicy;
+    use crate::tags::Namespace;
+    use serde_json::json;
+
+    #[test]
+    fn test_control_tags_tag_policy() {
+        let namespace: Namespace = "Acme:v2".parse().unwrap();
+        let policy = serde_json::to_value(TagPolicy::for_control_tags(&namespace, &[])).unwrap();
+        assert_eq!(
+            policy["tags"]["acme:v2/admin/mpa/seal/kind"],
+            json!({
+                "tag_key": {"@@assign": "Acme:v2/admin/mpa/seal/kind"},
+                "tag_value": {"@@assign": ["total", "trust_relay"]}
+            })
+        );
+        assert_eq!(
+            policy["tags"]["acme:v2/meta/grant_area"]["tag_value"]["@@assign"],
+            json!(["Acme:v2/*"])
+        );
+        assert_eq!(policy["tags"].as_object().unwrap().len(), 5);
+
+        let policy = TagPolicy::for_control_tags(&namespace, &["iam:role".to_string()]);
+        assert!(policy.tags.values().all(|rule| rule.enforced_for.is_some()));
+    }
+}
@@ -1,8 +1,352 @@
-use const_format::concatcp;
+use crate::ticket::ApprovalTicket;
+use serde::{Deserialize, Serialize};
+use std::{env::var, fmt::Display, str::FromStr};
+use thiserror::Error;
 
-mod prefix {
-    pub(super) const CONTROL: &str = "tagctl:v1";
+/// the namespace root of the terraform module
+pub const DEFAULT_ROOT: &str = "tagctl";
+/// the schema version of the terraform module
+pub const DEFAULT_VERSION: u32 = 1;
+
+/// selects the namespace control tags are written to, e.g. `tagctl:v2`
+pub const NAMESPACE_ENV: &str = "TAGCTL_NAMESPACE";
+/// the comma separated schema versions tickets are also read from, e.g. `1` while migrating to `tagctl:v2`
+pub const READABLE_VERSIONS_ENV: &str = "TAGCTL_READABLE_SCHEMA_VERSIONS";
+
+/// characters which, following the root prefix, make a tag key look like a control tag without being one
+pub(crate) const LOOKALIKE_SEPARATORS: &[char] = &['.', '+', '=', '@', '_', '/', '-'];
+
+/// the maximum length of an IAM tag value
+pub(crate) const MAX_TAG_VALUE_LEN: usize = 256;
+
+/// the ticket tag of a principal, which may not parse as a ticket
+#[derive(Debug, Clone, PartialEq)]
+pub enum TicketTag {
+    Valid(ApprovalTicket),
+    Malformed(MalformedTicket),
+}
+
+/// a value under a ticket key which does not parse as a ticket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MalformedTicket {
+    pub key: String,
+    pub value: String,
+    /// why the value does not parse
+    pub error: String,
+}
+
+impl TicketTag {
+    pub fn ticket(&self) -> Option<&ApprovalTicket> {
+        match self {
+            TicketTag::Valid(ticket) => Some(ticket),
+            TicketTag::Malformed(_) => None,
+        }
+    }
+
+    pub fn into_ticket(self) -> Option<ApprovalTicket> {
+        match self {
+            TicketTag::Valid(ticket) => Some(ticket),
+            TicketTag::Malformed(_) => None,
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum NamespaceError {
+    #[error("namespace root must be non-empty and alphanumeric, got {0:?}")]
+    InvalidRoot(String),
+    #[error("schema version must be a positive integer, got {0:?}")]
+    InvalidVersion(String),
+    #[error("expected <root>:v<version>, got {0:?}")]
+    Malformed(String),
+}
+
+/// the namespace and schema version of the control tags, e.g. `tagctl:v1`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespace {
+    root: String,
+    version: u32,
+}
+
+impl Namespace {
+    pub fn new(root: impl Into<String>, version: u32) -> Result<Self, NamespaceError> {
+        let root = root.into();
+        if root.is_empty() || !root.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(NamespaceError::InvalidRoot(root));
+        }
+        if version == 0 {
+            return Err(NamespaceError::InvalidVersion(version.to_string()));
+        }
+        Ok(Self { root, version })
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// the same namespace root at another schema version
+    pub fn with_version(&self, version: u32) -> Result<Self, NamespaceError> {
+        Self::new(self.root.clone(), version)
+    }
+
+    /// the prefix shared by the control tags of every schema version, e.g. `tagctl:`
+    pub fn control_prefix(&self) -> String {
+        format!("{}:", self.root)
+    }
+
+    pub fn grant_area_key(&self) -> String {
+        format!("{}/meta/grant_area", self)
+    }
+
+    pub fn identity_broker_key(&self) -> String {
+        format!("{}/meta/id_broker", self)
+    }
+
+    pub fn admin_key(&self) -> String {
+        format!("{}/admin", self)
+    }
+
+    pub fn mpa_key(&self) -> String {
+        format!("{}/mpa", self.admin_key())
+    }
+
+    pub fn ticket_key(&self) -> String {
+        format!("{}/ticket", self.mpa_key())
+    }
+
+    pub fn seal_key(&self) -> String {
+        format!("{}/seal", self.mpa_key())
+    }
+
+    pub fn seal_kind_key(&self) -> String {
+        format!("{}/kind", self.seal_key())
+    }
+
+    pub fn seal_grant_key(&self) -> String {
+        format!("{}/grant", self.seal_key())
+    }
+}
+
+impl Default for Namespace {
+    fn default() -> Self {
+        Self {
+            root: DEFAULT_ROOT.to_string(),
+            version: DEFAULT_VERSION,
+        }
+    }
+}
+
+impl Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:v{}", self.root, self.version)
+    }
+}
+
+impl FromStr for Namespace {
+    type Err = NamespaceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root, version) = s
+            .split_once(":v")
+            .ok_or_else(|| NamespaceError::Malformed(s.to_string()))?;
+        let version = version
+            .parse()
+            .map_err(|_| NamespaceError::InvalidVersion(version.to_string()))?;
+        Self::new(root, version)
+    }
+}
+
+/// the namespace control tags are written to, and the older schema versions tickets are still read from
+/// while accounts are migrated.
+/// tickets under older keys are only read to be migrated and evicted, they authorize nothing:
+/// the SCPs only check the ticket key of the current namespace, so authorization checks use [`Self::current_only`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagSchema {
+    current: Namespace,
+    readable_versions: Vec<u32>,
+}
+
+impl TagSchema {
+    pub fn new(current: Namespace) -> Self {
+        Self {
+            current,
+            readable_versions: vec![],
+        }
+    }
+
+    /// also reads tickets written with the given schema versions of the current namespace root
+    pub fn with_readable_versions(mut self, versions: impl IntoIterator<Item = u32>) -> Result<Self, NamespaceError> {
+        for version in versions {
+            self.current.with_version(version)?;
+            if version != self.current.version && !self.readable_versions.contains(&version) {
+                self.readable_versions.push(version);
+            }
+        }
+        Ok(self)
+    }
+
+    /// reads the schema from [`NAMESPACE_ENV`] and [`READABLE_VERSIONS_ENV`], defaulting to `tagctl:v1`
+    pub fn from_env() -> Result<Self, NamespaceError> {
+        let current = match var(NAMESPACE_ENV) {
+            Ok(namespace) if !namespace.is_empty() => namespace.parse()?,
+            _ => Namespace::default(),
+        };
+        let versions = var(READABLE_VERSIONS_ENV).unwrap_or_default();
+        let versions = versions
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse().map_err(|_| NamespaceError::InvalidVersion(v.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(current).with_readable_versions(versions)
+    }
+
+    /// the namespace control tags are written to
+    pub fn current(&self) -> &Namespace {
+        &self.current
+    }
+
+    /// the schema reading tickets from the current namespace only, as the SCPs do
+    pub fn current_only(&self) -> Self {
+        Self::new(self.current.clone())
+    }
+
+    /// the namespaces tickets are read from, the current one first
+    pub fn readable(&self) -> impl Iterator<Item = Namespace> + '_ {
+        std::iter::once(self.current.clone()).chain(
+            self.readable_versions
+                .iter()
+                .filter_map(|&version| self.current.with_version(version).ok()),
+        )
+    }
+
+    /// the ticket tag keys of all readable namespaces, the current one first
+    pub fn ticket_keys(&self) -> Vec<String> {
+        self.readable().map(|ns| ns.ticket_key()).collect()
+    }
+
+    /// finds the ticket among the tags of a principal.
+    /// a ticket written with the current schema version wins over tickets written with older ones.
+    pub fn find_ticket<'a>(&self, tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<ApprovalTicket> {
+        let keys = self.ticket_keys();
+        tags.into_iter()
+            .filter_map(|(key, value)| {
+                let rank = keys.iter().position(|k| k == key)?;
+                Some((rank, value.parse::<ApprovalTicket>().ok()?))
+            })
+            .min_by_key(|(rank, _)| *rank)
+            .map(|(_, ticket)| ticket)
+    }
+
+    /// like [`Self::find_ticket`], but reports the value under the newest ticket key when no ticket parses
+    pub fn find_ticket_tag<'a>(&self, tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<TicketTag> {
+        self.find_ticket_tags(tags).into_iter().next()
+    }
+
+    /// the ticket found by [`Self::find_ticket`] if any, followed by every value under a ticket key which does not
+    /// parse, the newest key first. a malformed value is reported even next to a valid ticket under another key.
+    pub fn find_ticket_tags<'a>(&self, tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<TicketTag> {
+        let keys = self.ticket_keys();
+        let mut found = tags
+            .into_iter()
+            .filter_map(|(key, value)| Some((keys.iter().position(|k| k == key)?, key, value)))
+            .map(|(rank, key, value)| match value.parse::<ApprovalTicket>() {
+                Ok(ticket) => (false, rank, TicketTag::Valid(ticket)),
+                Err(e) => (
+                    true,
+                    rank,
+                    TicketTag::Malformed(MalformedTicket {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                        error: e.to_string(),
+                    }),
+                ),
+            })
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(malformed, rank, _)| (*malformed, *rank));
+        let mut valid = false;
+        found
+            .into_iter()
+            .filter(|(malformed, _, _)| *malformed || !std::mem::replace(&mut valid, true))
+            .map(|(_, _, tag)| tag)
+            .collect()
+    }
 }
 
-pub(crate) const KEY_ADMIN: &str = concatcp!(prefix::CONTROL, "/", "admin");
-pub(crate) const KEY_ADMIN_TICKET: &str = concatcp!(KEY_ADMIN, "/", "mpa", "/", "ticket");
+#[cfg(test)]
+mod tests {
+    use super::{Namespace, NamespaceError, TagSchema, TicketTag};
+
+    #[test]
+    fn test_namespace_keys() {
+        let ns = Namespace::default();
+        assert_eq!(ns.to_string(), "tagctl:v1");
+        assert_eq!(ns.control_prefix(), "tagctl:");
+        assert_eq!(ns.grant_area_key(), "tagctl:v1/meta/grant_area");
+        assert_eq!(ns.ticket_key(), "tagctl:v1/admin/mpa/ticket");
+        assert_eq!(ns.seal_key(), "tagctl:v1/admin/mpa/seal");
+        assert_eq!(ns.seal_kind_key(), "tagctl:v1/admin/mpa/seal/kind");
+
+        let ns: Namespace = "acme:v2".parse().unwrap();
+        assert_eq!(ns.ticket_key(), "acme:v2/admin/mpa/ticket");
+    }
+
+    #[test]
+    fn test_parse_namespace() {
+        assert_eq!(
+            "tagctl".parse::<Namespace>(),
+            Err(NamespaceError::Malformed("tagctl".to_string()))
+        );
+        assert_eq!(
+            "tagctl:v0".parse::<Namespace>(),
+            Err(NamespaceError::InvalidVersion("0".to_string()))
+        );
+        assert_eq!(
+            "tag-ctl:v1".parse::<Namespace>(),
+            Err(NamespaceError::InvalidRoot("tag-ctl".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_ticket_across_versions() {
+        let schema = TagSchema::new("tagctl:v2".parse().unwrap())
+            .with_readable_versions([1, 2])
+            .unwrap();
+        assert_eq!(
+            schema.ticket_keys(),
+            vec!["tagctl:v2/admin/mpa/ticket", "tagctl:v1/admin/mpa/ticket"]
+        );
+
+        let v1 = ("tagctl:v1/admin/mpa/ticket", "by/alice/exp=1618033988/for/bob");
+        let v2 = ("tagctl:v2/admin/mpa/ticket", "by/carol/exp=1618033988/for/bob");
+        let v3 = ("tagctl:v3/admin/mpa/ticket", "by/dave/exp=1618033988/for/bob");
+
+        let ticket = schema.find_ticket([v1]).unwrap();
+        assert_eq!(ticket.giver.to_string(), "alice");
+        let ticket = schema.find_ticket([v1, v2]).unwrap();
+        assert_eq!(ticket.giver.to_string(), "carol");
+        assert!(schema.find_ticket([v3]).is_none());
+        assert!(TagSchema::default().find_ticket([v2]).is_none());
+        assert!(schema.current_only().find_ticket([v1]).is_none());
+
+        let garbage = ("tagctl:v2/admin/mpa/ticket", "garbage");
+        assert!(schema.find_ticket([garbage]).is_none());
+        let Some(TicketTag::Malformed(malformed)) = schema.find_ticket_tag([garbage, v3]) else {
+            panic!("expected a malformed ticket");
+        };
+        assert_eq!(malformed.key, "tagctl:v2/admin/mpa/ticket");
+        assert_eq!(malformed.value, "garbage");
+        let tag = schema.find_ticket_tag([garbage, v1]).unwrap();
+        assert_eq!(tag.ticket().unwrap().giver.to_string(), "alice");
+        assert!(schema.find_ticket_tag([v3]).is_none());
+
+        let tags = schema.find_ticket_tags([v2, ("tagctl:v1/admin/mpa/ticket", "garbage")]);
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].ticket().unwrap().giver.to_string(), "carol");
+        assert!(matches!(&tags[1], TicketTag::Malformed(tag) if tag.key == "tagctl:v1/admin/mpa/ticket"));
+    }
+}
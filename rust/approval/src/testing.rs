@@ -0,0 +1,377 @@
+//! an in-memory IAM endpoint, answering the calls the approval managers make, for tests and benchmarks
+
+use aws_sdk_iam::config::{retry::RetryConfig, BehaviorVersion, Credentials, Region};
+use aws_smithy_runtime_api::client::{
+    http::{HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector},
+    orchestrator::{HttpRequest, HttpResponse},
+    runtime_components::RuntimeComponents,
+};
+use aws_smithy_runtime_api::http::StatusCode;
+use aws_smithy_types::body::SdkBody;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+type Tags = Vec<(String, String)>;
+
+/// the account of the principals of the mock
+pub const ACCOUNT_ID: &str = "123456789012";
+
+/// the state of the fake account, and the calls it received
+#[derive(Debug, Default)]
+struct Account {
+    roles: BTreeMap<String, Tags>,
+    users: BTreeMap<String, Tags>,
+    denied: HashSet<String>,
+    denied_principals: HashSet<String>,
+    throttled: HashMap<String, usize>,
+    calls: HashMap<String, usize>,
+}
+
+/// an IAM endpoint holding roles and users with tags.
+/// each call waits for `latency`, and pages hold `page_size` items unless the call sets `MaxItems`.
+#[derive(Debug, Clone)]
+pub struct MockIam {
+    account: Arc<Mutex<Account>>,
+    latency: Duration,
+    page_size: usize,
+}
+
+impl Default for MockIam {
+    fn default() -> Self {
+        Self {
+            account: Default::default(),
+            latency: Duration::ZERO,
+            page_size: 100,
+        }
+    }
+}
+
+impl MockIam {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_role(self, name: &str, tags: &[(&str, &str)]) -> Self {
+        self.account
+            .lock()
+            .unwrap()
+            .roles
+            .insert(name.to_string(), to_tags(tags));
+        self
+    }
+
+    pub fn with_user(self, name: &str, tags: &[(&str, &str)]) -> Self {
+        self.account
+            .lock()
+            .unwrap()
+            .users
+            .insert(name.to_string(), to_tags(tags));
+        self
+    }
+
+    pub fn with_latency(self, latency: Duration) -> Self {
+        Self { latency, ..self }
+    }
+
+    pub fn with_page_size(self, page_size: usize) -> Self {
+        Self { page_size, ..self }
+    }
+
+    /// answers calls of `action`, e.g. `GetAccountAuthorizationDetails`, with `AccessDenied`
+    pub fn deny(self, action: &str) -> Self {
+        self.account.lock().unwrap().denied.insert(action.to_string());
+        self
+    }
+
+    /// answers calls naming the role or user `name` with `AccessDenied`, e.g. to fail the listing of its tags
+    pub fn deny_principal(self, name: &str) -> Self {
+        self.account.lock().unwrap().denied_principals.insert(name.to_string());
+        self
+    }
+
+    /// answers the next `times` calls of `action` with `Throttling`
+    pub fn throttle(self, action: &str, times: usize) -> Self {
+        self.account.lock().unwrap().throttled.insert(action.to_string(), times);
+        self
+    }
+
+    /// a client calling this endpoint
+    pub fn client(&self) -> Arc<aws_sdk_iam::Client> {
+        let config = aws_sdk_iam::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("AKIDMOCK", "secret", None, None, "mock"))
+            .retry_config(RetryConfig::disabled())
+            .http_client(self.clone())
+            .build();
+        Arc::new(aws_sdk_iam::Client::from_conf(config))
+    }
+
+    /// the number of calls of `action` received so far
+    pub fn calls(&self, action: &str) -> usize {
+        self.account
+            .lock()
+            .unwrap()
+            .calls
+            .get(action)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// the tags of the role, as they are now
+    pub fn role_tags(&self, name: &str) -> Option<Tags> {
+        self.account.lock().unwrap().roles.get(name).cloned()
+    }
+
+    fn respond(&self, params: &HashMap<String, String>) -> (u16, String) {
+        let mut account = self.account.lock().unwrap();
+        let action = params.get("Action").cloned().unwrap_or_default();
+        *account.calls.entry(action.clone()).or_default() += 1;
+        let principal_denied = [params.get("RoleName"), params.get("UserName")]
+            .into_iter()
+            .flatten()
+            .any(|name| account.denied_principals.contains(name));
+        if account.denied.contains(&action) || principal_denied {
+            return access_denied();
+        }
+        if let Some(times) = account.throttled.get_mut(&action).filter(|times| **times > 0) {
+            *times -= 1;
+            return (
+                400,
+                "<ErrorResponse><Error><Type>Sender</Type><Code>Throttling</Code>\
+                 <Message>Rate exceeded</Message></Error><RequestId>mock</RequestId></ErrorResponse>"
+                    .to_string(),
+            );
+        }
+        let start = params.get("Marker").and_then(|m| m.parse().ok()).unwrap_or(0);
+        let size = params
+            .get("MaxItems")
+            .and_then(|m| m.parse().ok())
+            .unwrap_or(self.page_size);
+        let name = |key: &str| params.get(key).cloned().unwrap_or_default();
+        let result = match action.as_str() {
+            "ListRoles" => page(&account.roles, start, size, "Roles", |name, _| principal("Role", name)),
+            "ListUsers" => page(&account.users, start, size, "Users", |name, _| principal("User", name)),
+            "ListRoleTags" => match account.roles.get(&name("RoleName")) {
+                Some(tags) => format!("<Tags>{}</Tags><IsTruncated>false</IsTruncated>", members(tags)),
+                None => return not_found(),
+            },
+            "ListUserTags" => match account.users.get(&name("UserName")) {
+                Some(tags) => format!("<Tags>{}</Tags><IsTruncated>false</IsTruncated>", members(tags)),
+                None => return not_found(),
+            },
+            "GetRole" => match account.roles.get(&name("RoleName")) {
+                Some(_) => format!("<Role>{}</Role>", principal("Role", &name("RoleName"))),
+                None => return not_found(),
+            },
+            "GetUser" => match account.users.get(&name("UserName")) {
+                Some(_) => format!("<User>{}</User>", principal("User", &name("UserName"))),
+                None => return not_found(),
+            },
+            "GetAccountAuthorizationDetails" => {
+                let filters: Vec<_> = params
+                    .iter()
+                    .filter(|(key, _)| key.starts_with("Filter.member."))
+                    .map(|(_, value)| value.as_str())
+                    .collect();
+                let (list, details) = match filters.as_slice() {
+                    ["Role"] => ("RoleDetailList", &account.roles),
+                    ["User"] => ("UserDetailList", &account.users),
+                    _ => return (400, "the mock filters by either Role or User".to_string()),
+                };
+                let kind = &list[..4];
+                page(details, start, size, list, |name, tags| {
+                    format!(
+                        "<Path>/</Path><{kind}Name>{}</{kind}Name><Arn>{}</Arn><Tags>{}</Tags>",
+                        escape(name),
+                        escape(&arn(kind, name)),
+                        members(tags)
+                    )
+                })
+            }
+            "TagRole" | "TagUser" => {
+                let (principals, name) = if action == "TagRole" {
+                    (&mut account.roles, name("RoleName"))
+                } else {
+                    (&mut account.users, name("UserName"))
+                };
+                let mut added: Tags = Vec::new();
+                for n in 1.. {
+                    let (Some(key), Some(value)) = (
+                        params.get(&format!("Tags.member.{n}.Key")),
+                        params.get(&format!("Tags.member.{n}.Value")),
+                    ) else {
+                        break;
+                    };
+                    added.push((key.clone(), value.clone()));
+                }
+                match principals.get_mut(&name) {
+                    Some(tags) => {
+                        tags.retain(|(key, _)| !added.iter().any(|(k, _)| k == key));
+                        tags.extend(added);
+                    }
+                    None => return not_found(),
+                }
+                String::new()
+            }
+            "UntagRole" | "UntagUser" => {
+                let (principals, name) = if action == "UntagRole" {
+                    (&mut account.roles, name("RoleName"))
+                } else {
+                    (&mut account.users, name("UserName"))
+                };
+                let keys: HashSet<_> = params
+                    .iter()
+                    .filter(|(key, _)| key.starts_with("TagKeys.member."))
+                    .map(|(_, value)| value.clone())
+                    .collect();
+                match principals.get_mut(&name) {
+                    Some(tags) => tags.retain(|(key, _)| !keys.contains(key)),
+                    None => return not_found(),
+                }
+                String::new()
+            }
+            _ => return (400, format!("the mock does not implement {action}")),
+        };
+        (
+            200,
+            format!("<{action}Response><{action}Result>{result}</{action}Result></{action}Response>"),
+        )
+    }
+}
+
+impl HttpConnector for MockIam {
+    fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+        let body = request.body().bytes().unwrap_or_default();
+        let params = parse_form(&String::from_utf8_lossy(body));
+        let mock = self.clone();
+        HttpConnectorFuture::new(async move {
+            if !mock.latency.is_zero() {
+                tokio::time::sleep(mock.latency).await;
+            }
+            let (status, body) = mock.respond(&params);
+            Ok(HttpResponse::new(
+                StatusCode::try_from(status).expect("valid status"),
+                SdkBody::from(body),
+            ))
+        })
+    }
+}
+
+impl HttpClient for MockIam {
+    fn http_connector(&self, _: &HttpConnectorSettings, _: &RuntimeComponents) -> SharedHttpConnector {
+        SharedHttpConnector::new(self.clone())
+    }
+}
+
+fn to_tags(tags: &[(&str, &str)]) -> Tags {
+    tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+fn access_denied() -> (u16, String) {
+    (
+        403,
+        "<ErrorResponse><Error><Type>Sender</Type><Code>AccessDenied</Code>\
+         <Message>denied by the mock</Message></Error><RequestId>mock</RequestId></ErrorResponse>"
+            .to_string(),
+    )
+}
+
+fn not_found() -> (u16, String) {
+    (
+        404,
+        "<ErrorResponse><Error><Type>Sender</Type><Code>NoSuchEntity</Code>\
+         <Message>not found by the mock</Message></Error><RequestId>mock</RequestId></ErrorResponse>"
+            .to_string(),
+    )
+}
+
+/// a page of `items` starting at `start`, whose marker is the index of the next item
+fn page(
+    items: &BTreeMap<String, Tags>,
+    start: usize,
+    size: usize,
+    list: &str,
+    member: impl Fn(&str, &Tags) -> String,
+) -> String {
+    let mut out = format!("<{list}>");
+    for (name, tags) in items.iter().skip(start).take(size) {
+        write!(out, "<member>{}</member>", member(name, tags)).unwrap();
+    }
+    write!(out, "</{list}>").unwrap();
+    let next = start + size;
+    if next < items.len() {
+        write!(out, "<IsTruncated>true</IsTruncated><Marker>{next}</Marker>").unwrap();
+    } else {
+        out.push_str("<IsTruncated>false</IsTruncated>");
+    }
+    out
+}
+
+fn arn(kind: &str, name: &str) -> String {
+    format!("arn:aws:iam::{ACCOUNT_ID}:{}/{name}", kind.to_lowercase())
+}
+
+fn principal(kind: &str, name: &str) -> String {
+    format!(
+        "<Path>/</Path><{kind}Name>{}</{kind}Name><{kind}Id>AIDAMOCK{id:012}</{kind}Id>\
+         <Arn>{}</Arn><CreateDate>2024-01-01T00:00:00Z</CreateDate>",
+        escape(name),
+        escape(&arn(kind, name)),
+        id = name.len(),
+    )
+}
+
+fn members(tags: &Tags) -> String {
+    tags.iter()
+        .map(|(key, value)| {
+            format!(
+                "<member><Key>{}</Key><Value>{}</Value></member>",
+                escape(key),
+                escape(value)
+            )
+        })
+        .collect()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// the parameters of a query protocol request body
+fn parse_form(body: &str) -> HashMap<String, String> {
+    body.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (decode(key), decode(value)))
+        .collect()
+}
+
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => out.push(byte),
+                    Err(_) => out.extend_from_slice(&bytes[i..i + 3]),
+                }
+                i += 2;
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
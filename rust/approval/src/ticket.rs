@@ -1,4 +1,5 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
@@ -15,6 +16,81 @@ pub enum ParseError {
     MissingReceiver,
 }
 
+#[derive(Error, Debug, PartialEq)]
+pub enum ExtendError {
+    #[error("ticket has no expiry")]
+    MissingExpiry,
+    #[error("ticket has already expired at {0}")]
+    Expired(DateTime<Utc>),
+    #[error("extension must be positive")]
+    NonPositiveExtension,
+    #[error("extended expiry {requested} is past the maximum ticket ttl, latest allowed expiry is {limit}")]
+    ExceedsMaxTtl {
+        requested: DateTime<Utc>,
+        limit: DateTime<Utc>,
+    },
+    #[error("extended ticket is {0} characters long, tag values are limited to {limit}", limit = crate::tags::MAX_TAG_VALUE_LEN)]
+    TooLong(usize),
+}
+
+mod spec_key {
+    pub(super) const ID: &str = "id";
+    pub(super) const EXPIRY: &str = "exp";
+    pub(super) const EXTENSIONS: &str = "ext";
+    pub(super) const SCOPE: &str = "scope";
+    pub(super) const REASON: &str = "reason";
+    #[cfg(feature = "chainable")]
+    pub(super) const CHAIN: &str = "chain";
+    #[cfg(feature = "chainable")]
+    pub(super) const LINEAGE: &str = "lineage";
+    #[cfg(feature = "signed")]
+    pub(super) const SIGNATURE: &str = "sig";
+}
+
+#[cfg(feature = "signed")]
+#[derive(Error, Debug, PartialEq)]
+pub enum SignatureError {
+    #[error("ticket is not signed")]
+    Missing,
+    #[error("ticket signature is malformed")]
+    Malformed,
+    #[error("ticket signature does not match the ticket")]
+    Invalid,
+    #[error("no public key is trusted for giver {0}")]
+    UnknownGiver(HumanIdentity),
+    #[error("signed ticket is {0} characters long, tag values are limited to {limit}", limit = crate::tags::MAX_TAG_VALUE_LEN)]
+    TooLong(usize),
+}
+
+#[cfg(feature = "chainable")]
+#[derive(Error, Debug, PartialEq)]
+pub enum DeriveError {
+    #[error("parent ticket is not chainable")]
+    NotChainable,
+    #[error("parent ticket has no id")]
+    MissingParentId,
+    #[error("parent ticket has no expiry")]
+    MissingParentExpiry,
+    #[error("parent ticket has expired at {0}")]
+    ParentExpired(DateTime<Utc>),
+    #[error("derived ticket expiry {requested} exceeds the parent ticket expiry {limit}")]
+    ExceedsParentExpiry {
+        requested: DateTime<Utc>,
+        limit: DateTime<Utc>,
+    },
+    #[error("derived ticket is not given by the receiver of the parent ticket")]
+    GiverNotParentReceiver,
+    #[error("derived ticket does not descend from the parent ticket")]
+    LineageMismatch,
+}
+
+/// separates the ids in the lineage of a derived ticket
+#[cfg(feature = "chainable")]
+const LINEAGE_SEPARATOR: &str = ".";
+
+/// separates the previous expiries recorded in the extension history of a ticket
+const EXTENSIONS_SEPARATOR: &str = ".";
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct HumanIdentity(String);
 
@@ -90,14 +166,26 @@ impl Display for ApprovalTicket {
 }
 
 impl ApprovalTicket {
+    /// creates a new ticket with a freshly generated id
     pub fn new(giver: HumanIdentity, receiver: HumanIdentity) -> Self {
+        let id = format!("{:016x}", rand::thread_rng().gen::<u64>());
         ApprovalTicket {
             giver,
             receiver,
-            spec: HashMap::new(),
+            spec: HashMap::from([(spec_key::ID.to_string(), id)]),
         }
     }
 
+    /// the id of the ticket. tickets issued before ids were introduced have none.
+    pub fn id(&self) -> Option<&str> {
+        self.spec.get(spec_key::ID).map(String::as_str)
+    }
+
+    /// the decoded key-value pairs between the giver and receiver sections
+    pub fn spec(&self) -> &HashMap<String, String> {
+        &self.spec
+    }
+
     pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) -> &Self {
         self.spec.insert(key.into(), value.into());
         self
@@ -105,33 +193,225 @@ impl ApprovalTicket {
 
     #[cfg(feature = "chainable")]
     pub fn set_chainable(&mut self, chainable: bool) -> &Self {
-        self.spec.insert("chain".to_string(), chainable.to_string());
+        self.spec.insert(spec_key::CHAIN.to_string(), chainable.to_string());
         self
     }
     #[cfg(feature = "chainable")]
     pub fn is_chainable(&self) -> bool {
         self.spec
-            .get_key_value("chain")
-            .map_or(false, |(_, v)| v.parse::<bool>().unwrap_or(false))
+            .get_key_value(spec_key::CHAIN)
+            .is_some_and(|(_, v)| v.parse::<bool>().unwrap_or(false))
+    }
+
+    /// the ids of the tickets this ticket was derived from, root first. empty for tickets given directly.
+    #[cfg(feature = "chainable")]
+    pub fn lineage(&self) -> Vec<&str> {
+        self.spec
+            .get(spec_key::LINEAGE)
+            .map(|lineage| lineage.split(LINEAGE_SEPARATOR).collect())
+            .unwrap_or_default()
+    }
+
+    /// the id of the ticket this ticket was directly derived from
+    #[cfg(feature = "chainable")]
+    pub fn parent_id(&self) -> Option<&str> {
+        self.lineage().last().copied()
+    }
+
+    /// derives a ticket from this chainable ticket, given by its receiver to `receiver`.
+    /// the derived ticket must not outlive this ticket, and records this ticket in its lineage.
+    #[cfg(feature = "chainable")]
+    pub fn derive(&self, receiver: HumanIdentity, expiry: DateTime<Utc>) -> Result<ApprovalTicket, DeriveError> {
+        let mut derived = ApprovalTicket::new(self.receiver.clone(), receiver);
+        derived.set_expiry(expiry);
+
+        let id = self.id().ok_or(DeriveError::MissingParentId)?;
+        let mut lineage = self.lineage();
+        lineage.push(id);
+        derived
+            .spec
+            .insert(spec_key::LINEAGE.to_string(), lineage.join(LINEAGE_SEPARATOR));
+
+        derived.validate_derivation(self)?;
+        Ok(derived)
+    }
+
+    /// checks that this ticket is a valid derivation of `parent`, which must still be in force
+    #[cfg(feature = "chainable")]
+    pub fn validate_derivation(&self, parent: &ApprovalTicket) -> Result<(), DeriveError> {
+        if !parent.is_chainable() {
+            return Err(DeriveError::NotChainable);
+        }
+        let parent_id = parent.id().ok_or(DeriveError::MissingParentId)?;
+        let mut expected_lineage = parent.lineage();
+        expected_lineage.push(parent_id);
+        if self.lineage() != expected_lineage {
+            return Err(DeriveError::LineageMismatch);
+        }
+        if self.giver != parent.receiver {
+            return Err(DeriveError::GiverNotParentReceiver);
+        }
+
+        let limit = parent.expires_at().ok_or(DeriveError::MissingParentExpiry)?;
+        if limit <= Utc::now() {
+            return Err(DeriveError::ParentExpired(limit));
+        }
+        match self.expires_at() {
+            Some(requested) if requested <= limit => Ok(()),
+            Some(requested) => Err(DeriveError::ExceedsParentExpiry { requested, limit }),
+            None => Err(DeriveError::ExceedsParentExpiry {
+                requested: DateTime::<Utc>::MAX_UTC,
+                limit,
+            }),
+        }
+    }
+
+    /// what the ticket is meant to be used for, as stated by the giver
+    pub fn set_scope(&mut self, scope: impl Into<String>) -> &Self {
+        self.spec.insert(spec_key::SCOPE.to_string(), scope.into());
+        self
+    }
+
+    pub fn scope(&self) -> Option<&str> {
+        self.spec.get(spec_key::SCOPE).map(String::as_str)
+    }
+
+    /// why the ticket was given, as stated by the giver
+    pub fn set_reason(&mut self, reason: impl Into<String>) -> &Self {
+        self.spec.insert(spec_key::REASON.to_string(), reason.into());
+        self
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.spec.get(spec_key::REASON).map(String::as_str)
     }
 
     pub fn set_expiry(&mut self, expiry: DateTime<Utc>) -> &Self {
-        self.spec.insert("exp".to_string(), expiry.timestamp().to_string());
+        self.spec
+            .insert(spec_key::EXPIRY.to_string(), expiry.timestamp().to_string());
         self
     }
 
     pub fn expires_at(&self) -> Option<DateTime<Utc>> {
         self.spec
-            .get_key_value("exp")
+            .get_key_value(spec_key::EXPIRY)
             .and_then(|(_, v)| v.parse::<i64>().ok())
             .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
     }
+
+    /// pushes the expiry of the ticket forward by `by`, recording the previous expiry in the extension history.
+    /// the extended expiry must not be further than `max_ttl` from now, and the extended ticket must still fit in a tag value.
+    pub fn extend(&mut self, by: Duration, max_ttl: Duration) -> Result<DateTime<Utc>, ExtendError> {
+        if by <= Duration::zero() {
+            return Err(ExtendError::NonPositiveExtension);
+        }
+
+        let now = Utc::now();
+        let expiry = self.expires_at().ok_or(ExtendError::MissingExpiry)?;
+        if expiry <= now {
+            return Err(ExtendError::Expired(expiry));
+        }
+
+        let requested = expiry + by;
+        let limit = now + max_ttl;
+        if requested >= limit {
+            return Err(ExtendError::ExceedsMaxTtl { requested, limit });
+        }
+
+        let mut extended = self.clone();
+        let mut history = extended.spec.get(spec_key::EXTENSIONS).cloned().unwrap_or_default();
+        if !history.is_empty() {
+            history.push_str(EXTENSIONS_SEPARATOR);
+        }
+        history.push_str(&expiry.timestamp().to_string());
+        extended.spec.insert(spec_key::EXTENSIONS.to_string(), history);
+        extended.set_expiry(requested);
+
+        let len = extended.to_string().len();
+        if len > crate::tags::MAX_TAG_VALUE_LEN {
+            return Err(ExtendError::TooLong(len));
+        }
+
+        *self = extended;
+        Ok(requested)
+    }
+
+    /// the text covered by the signature: the ticket without its signature, with its spec sorted by key
+    #[cfg(feature = "signed")]
+    fn signed_payload(&self) -> String {
+        let mut spec: Vec<_> = self
+            .spec
+            .iter()
+            .filter(|(k, _)| k.as_str() != spec_key::SIGNATURE)
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        spec.sort();
+        format!("by/{}/{}/for/{}", self.giver, spec.join("/"), self.receiver)
+    }
+
+    /// signs the ticket with the giver's key, replacing any previous signature.
+    /// the signature must be renewed whenever the ticket changes, e.g. after an extension.
+    #[cfg(feature = "signed")]
+    pub fn sign(&mut self, key: &ed25519_dalek::SigningKey) -> Result<(), SignatureError> {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+        use ed25519_dalek::Signer;
+
+        let signature = key.sign(self.signed_payload().as_bytes());
+        self.spec.insert(
+            spec_key::SIGNATURE.to_string(),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+        );
+
+        let len = self.to_string().len();
+        if len > crate::tags::MAX_TAG_VALUE_LEN {
+            self.spec.remove(spec_key::SIGNATURE);
+            return Err(SignatureError::TooLong(len));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "signed")]
+    pub fn is_signed(&self) -> bool {
+        self.spec.contains_key(spec_key::SIGNATURE)
+    }
+
+    /// checks the signature of the ticket against the giver's public key
+    #[cfg(feature = "signed")]
+    pub fn verify(&self, key: &ed25519_dalek::VerifyingKey) -> Result<(), SignatureError> {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+        let encoded = self.spec.get(spec_key::SIGNATURE).ok_or(SignatureError::Missing)?;
+        let bytes: [u8; 64] = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(SignatureError::Malformed)?;
+        key.verify_strict(
+            self.signed_payload().as_bytes(),
+            &ed25519_dalek::Signature::from_bytes(&bytes),
+        )
+        .map_err(|_| SignatureError::Invalid)
+    }
+
+    /// the expiries this ticket had before each of its extensions, oldest first.
+    pub fn extensions(&self) -> Vec<DateTime<Utc>> {
+        self.spec
+            .get(spec_key::EXTENSIONS)
+            .map(|history| {
+                history
+                    .split(EXTENSIONS_SEPARATOR)
+                    .filter_map(|v| v.parse::<i64>().ok())
+                    .filter_map(|seconds| DateTime::from_timestamp(seconds, 0))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ApprovalTicket, HumanIdentity};
-    use chrono::DateTime;
+    use super::{ApprovalTicket, ExtendError, HumanIdentity};
+    use chrono::{DateTime, Duration, Utc};
 
     #[test]
     fn test_parse_ticket() {
@@ -145,7 +425,7 @@ mod tests {
         assert_eq!(parsed.receiver.0, "bob");
 
         #[cfg(feature = "chainable")]
-        assert_eq!(parsed.is_chainable(), true);
+        assert!(parsed.is_chainable());
 
         assert_eq!(parsed.spec.get("exp"), Some(&"1618033988".to_string()));
     }
@@ -184,7 +464,42 @@ mod tests {
             .collect(),
         };
 
-        assert_eq!(ticket.is_chainable(), true);
+        assert!(ticket.is_chainable());
+    }
+
+    #[test]
+    #[cfg(feature = "chainable")]
+    fn test_derive_ticket() {
+        use super::DeriveError;
+
+        let mut parent = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        let parent_expiry = DateTime::from_timestamp((Utc::now() + Duration::hours(1)).timestamp(), 0).unwrap();
+        parent.set_expiry(parent_expiry);
+
+        let err = parent.derive(HumanIdentity::new("carol"), parent_expiry).unwrap_err();
+        assert_eq!(err, DeriveError::NotChainable);
+
+        parent.set_chainable(true);
+        let child = parent.derive(HumanIdentity::new("carol"), parent_expiry).unwrap();
+        assert_eq!(child.giver, HumanIdentity::new("bob"));
+        assert_eq!(child.parent_id(), parent.id());
+        assert!(!child.is_chainable());
+
+        let err = parent
+            .derive(HumanIdentity::new("carol"), parent_expiry + Duration::minutes(1))
+            .unwrap_err();
+        assert!(matches!(err, DeriveError::ExceedsParentExpiry { .. }));
+
+        let mut grandparent_bound = child.clone();
+        grandparent_bound.set_chainable(true);
+        let grandchild = grandparent_bound
+            .derive(HumanIdentity::new("dave"), parent_expiry)
+            .unwrap();
+        assert_eq!(grandchild.lineage(), vec![parent.id().unwrap(), child.id().unwrap()]);
+        assert_eq!(
+            grandchild.validate_derivation(&parent),
+            Err(DeriveError::LineageMismatch)
+        );
     }
 
     #[test]
@@ -201,4 +516,105 @@ mod tests {
             Some(DateTime::from_timestamp(1618033988, 0).unwrap())
         );
     }
+
+    #[test]
+    fn test_new_ticket_has_id() {
+        let first = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        let second = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        assert!(first.id().is_some());
+        assert_ne!(first.id(), second.id());
+    }
+
+    #[test]
+    fn test_extend_ticket() {
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        let id = ticket.id().map(str::to_string);
+        let expiry = DateTime::from_timestamp((Utc::now() + Duration::hours(1)).timestamp(), 0).unwrap();
+        ticket.set_expiry(expiry);
+
+        let extended = ticket.extend(Duration::minutes(30), Duration::hours(4)).unwrap();
+        assert_eq!(extended, expiry + Duration::minutes(30));
+        assert_eq!(ticket.expires_at(), Some(extended));
+        assert_eq!(ticket.id().map(str::to_string), id);
+
+        ticket.extend(Duration::minutes(30), Duration::hours(4)).unwrap();
+        assert_eq!(ticket.extensions(), vec![expiry, extended]);
+
+        let roundtrip = ticket.to_string().parse::<ApprovalTicket>().unwrap();
+        assert_eq!(roundtrip, ticket);
+    }
+
+    #[test]
+    fn test_extend_ticket_past_max_ttl() {
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        ticket.set_expiry(Utc::now() + Duration::hours(1));
+
+        let err = ticket.extend(Duration::hours(4), Duration::hours(4)).unwrap_err();
+        assert!(matches!(err, ExtendError::ExceedsMaxTtl { .. }));
+        assert!(ticket.extensions().is_empty());
+    }
+
+    #[test]
+    fn test_extend_ticket_past_tag_value_limit() {
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        ticket.set_expiry(Utc::now() + Duration::minutes(5));
+
+        let err = loop {
+            match ticket.extend(Duration::seconds(1), Duration::hours(4)) {
+                Ok(_) => assert!(ticket.to_string().len() <= 256),
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(err, ExtendError::TooLong(len) if len > 256));
+        assert!(ticket.to_string().len() <= 256);
+    }
+
+    #[test]
+    fn test_extend_expired_ticket() {
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        ticket.set_expiry(Utc::now() - Duration::minutes(1));
+
+        let err = ticket.extend(Duration::minutes(30), Duration::hours(4)).unwrap_err();
+        assert!(matches!(err, ExtendError::Expired(_)));
+    }
+
+    #[cfg(feature = "signed")]
+    #[test]
+    fn test_sign_ticket() {
+        use super::SignatureError;
+        use ed25519_dalek::SigningKey;
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        ticket.set_expiry(Utc::now() + Duration::hours(1));
+        assert_eq!(ticket.verify(&key.verifying_key()), Err(SignatureError::Missing));
+
+        ticket.sign(&key).unwrap();
+        let restored = ticket.to_string().parse::<ApprovalTicket>().unwrap();
+        assert!(restored.to_string().len() <= 256);
+        assert_eq!(restored.verify(&key.verifying_key()), Ok(()));
+
+        let other = SigningKey::from_bytes(&[8; 32]);
+        assert_eq!(restored.verify(&other.verifying_key()), Err(SignatureError::Invalid));
+
+        let mut tampered = restored.clone();
+        tampered.receiver = HumanIdentity::new("mallory");
+        assert_eq!(tampered.verify(&key.verifying_key()), Err(SignatureError::Invalid));
+
+        let mut extended = restored;
+        extended.extend(Duration::minutes(30), Duration::hours(4)).unwrap();
+        assert_eq!(extended.verify(&key.verifying_key()), Err(SignatureError::Invalid));
+    }
+
+    #[cfg(feature = "signed")]
+    #[test]
+    fn test_sign_ticket_too_long() {
+        use super::SignatureError;
+        use ed25519_dalek::SigningKey;
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("a".repeat(150)), HumanIdentity::new("bob"));
+        assert!(matches!(ticket.sign(&key), Err(SignatureError::TooLong(_))));
+        assert!(!ticket.is_signed());
+    }
 }
@@ -0,0 +1,410 @@
+use crate::{
+    error::{CliError, ErrorKind},
+    output::OutputFormat,
+};
+use anyhow::Context as _;
+use approval::tags::{Namespace, TagSchema, NAMESPACE_ENV, READABLE_VERSIONS_ENV};
+use clap::{Args, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// overrides the location of the configuration file
+const CONFIG_PATH_ENV: &str = "TAGCTL_CONFIG";
+
+#[derive(Args)]
+#[command(about)]
+pub(crate) struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// prints the settings of a context, or a single one of them
+    Get {
+        key: Option<ContextKey>,
+        /// the context to read. Default to the current context.
+        #[arg(long)]
+        context: Option<String>,
+    },
+    /// changes a setting of a context, creating the context if needed
+    Set {
+        key: ContextKey,
+        value: String,
+        /// the context to change. Default to the current context.
+        #[arg(long)]
+        context: Option<String>,
+    },
+    /// removes a setting from a context
+    Unset {
+        key: ContextKey,
+        /// the context to change. Default to the current context.
+        #[arg(long)]
+        context: Option<String>,
+    },
+    /// selects the context used when none is passed with `--context`
+    UseContext { name: String },
+}
+
+/// selects the configuration context of a command, and overrides its AWS profile
+#[derive(Args)]
+pub(crate) struct ContextArgs {
+    /// the configuration context to use. Default to the current context of the configuration file.
+    #[arg(long = "context", global = true, env = "TAGCTL_CONTEXT")]
+    name: Option<String>,
+
+    /// the AWS profile to use for the operation
+    #[arg(long, global = true, env = "TAGCTL_PROFILE")]
+    profile: Option<String>,
+
+    /// the namespace and schema version of the control tags, e.g. `tagctl:v1`
+    #[arg(long, global = true, env = NAMESPACE_ENV)]
+    namespace: Option<Namespace>,
+
+    /// older schema versions to also read tickets from, e.g. while migrating from `tagctl:v1` to `tagctl:v2`
+    #[arg(long, global = true, env = READABLE_VERSIONS_ENV, value_delimiter = ',')]
+    readable_schema_versions: Vec<u32>,
+}
+
+impl ContextArgs {
+    /// loads the selected context, with the settings passed on the command line or environment applied over it
+    pub(crate) fn load(&self) -> anyhow::Result<Context> {
+        let mut context = Config::load()?.select(self.name.as_deref())?;
+        if self.profile.is_some() {
+            context.profile.clone_from(&self.profile);
+        }
+        if let Some(namespace) = &self.namespace {
+            context.namespace = Some(namespace.to_string());
+        }
+        if !self.readable_schema_versions.is_empty() {
+            context.readable_schema_versions = Some(self.readable_schema_versions.clone());
+        }
+        Ok(context)
+    }
+}
+
+/// whether tickets must state why they are given
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ReasonPolicy {
+    #[default]
+    Optional,
+    Required,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ContextKey {
+    Profile,
+    RoleName,
+    Output,
+    TicketTtl,
+    MaxTicketTtl,
+    Scope,
+    Reason,
+    Identity,
+    Namespace,
+    ReadableSchemaVersions,
+    #[cfg(feature = "signed")]
+    SigningKey,
+}
+
+impl std::fmt::Display for ContextKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no skipped keys");
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/// the defaults applied to commands run within a context. command line flags and environment variables take precedence.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub(crate) struct Context {
+    /// the AWS profile to use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) profile: Option<String>,
+    /// the role whose ticket is managed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) role_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<OutputFormat>,
+    /// how long tickets are valid for, e.g. "1h"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_ttl: Option<String>,
+    /// the maximum ticket ttl of the deployment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_ticket_ttl: Option<String>,
+    /// the scope recorded in the tickets given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reason: Option<ReasonPolicy>,
+    /// the human identity of the caller, when it differs from the session name of the caller
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) identity: Option<String>,
+    /// the namespace and schema version of the control tags, e.g. `tagctl:v1`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    /// older schema versions to also read tickets from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readable_schema_versions: Option<Vec<u32>>,
+    /// the file holding the key tickets are signed with
+    #[cfg(feature = "signed")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) signing_key: Option<PathBuf>,
+}
+
+impl Context {
+    pub(crate) fn ticket_ttl(&self) -> anyhow::Result<Option<humantime::Duration>> {
+        parse_duration(ContextKey::TicketTtl, self.ticket_ttl.as_deref())
+    }
+
+    pub(crate) fn max_ticket_ttl(&self) -> anyhow::Result<Option<humantime::Duration>> {
+        parse_duration(ContextKey::MaxTicketTtl, self.max_ticket_ttl.as_deref())
+    }
+
+    /// the tag schema of the deployment, `tagctl:v1` unless configured otherwise
+    pub(crate) fn schema(&self) -> anyhow::Result<TagSchema> {
+        let namespace = match &self.namespace {
+            Some(namespace) => parse_namespace(namespace)?,
+            None => Namespace::default(),
+        };
+        TagSchema::new(namespace)
+            .with_readable_versions(self.readable_schema_versions.clone().unwrap_or_default())
+            .map_err(|e| CliError::new(ErrorKind::InvalidInput, e.to_string()).into())
+    }
+
+    fn get(&self, key: ContextKey) -> Option<String> {
+        match key {
+            ContextKey::Profile => self.profile.clone(),
+            ContextKey::RoleName => self.role_name.clone(),
+            ContextKey::Output => self
+                .output
+                .and_then(|o| o.to_possible_value())
+                .map(|v| v.get_name().to_string()),
+            ContextKey::TicketTtl => self.ticket_ttl.clone(),
+            ContextKey::MaxTicketTtl => self.max_ticket_ttl.clone(),
+            ContextKey::Scope => self.scope.clone(),
+            ContextKey::Reason => self
+                .reason
+                .and_then(|r| r.to_possible_value())
+                .map(|v| v.get_name().to_string()),
+            ContextKey::Identity => self.identity.clone(),
+            ContextKey::Namespace => self.namespace.clone(),
+            ContextKey::ReadableSchemaVersions => self
+                .readable_schema_versions
+                .as_ref()
+                .map(|versions| versions.iter().map(u32::to_string).collect::<Vec<_>>().join(",")),
+            #[cfg(feature = "signed")]
+            ContextKey::SigningKey => self.signing_key.as_ref().map(|p| p.display().to_string()),
+        }
+    }
+
+    /// changes a setting, or removes it if `value` is `None`
+    fn set(&mut self, key: ContextKey, value: Option<&str>) -> anyhow::Result<()> {
+        let value = value.map(str::to_string);
+        match key {
+            ContextKey::Profile => self.profile = value,
+            ContextKey::RoleName => self.role_name = value,
+            ContextKey::Output => self.output = value.as_deref().map(parse_value).transpose()?,
+            ContextKey::TicketTtl => {
+                parse_duration(key, value.as_deref())?;
+                self.ticket_ttl = value;
+            }
+            ContextKey::MaxTicketTtl => {
+                parse_duration(key, value.as_deref())?;
+                self.max_ticket_ttl = value;
+            }
+            ContextKey::Scope => self.scope = value,
+            ContextKey::Reason => self.reason = value.as_deref().map(parse_value).transpose()?,
+            ContextKey::Identity => self.identity = value,
+            ContextKey::Namespace => {
+                value.as_deref().map(parse_namespace).transpose()?;
+                self.namespace = value;
+            }
+            ContextKey::ReadableSchemaVersions => {
+                self.readable_schema_versions = value
+                    .map(|v| {
+                        v.split(',')
+                            .map(|version| version.trim().parse::<u32>())
+                            .collect::<Result<Vec<_>, _>>()
+                            .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("invalid {}: {}", key, e)))
+                    })
+                    .transpose()?;
+                self.schema()?;
+            }
+            #[cfg(feature = "signed")]
+            ContextKey::SigningKey => self.signing_key = value.map(PathBuf::from),
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: ValueEnum>(value: &str) -> anyhow::Result<T> {
+    T::from_str(value, true).map_err(|e| CliError::new(ErrorKind::InvalidInput, e).into())
+}
+
+fn parse_namespace(value: &str) -> anyhow::Result<Namespace> {
+    value
+        .parse()
+        .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("invalid namespace: {}", e)).into())
+}
+
+fn parse_duration(key: ContextKey, value: Option<&str>) -> anyhow::Result<Option<humantime::Duration>> {
+    value
+        .map(|v| {
+            v.parse::<humantime::Duration>()
+                .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("invalid {}: {}", key, e)).into())
+        })
+        .transpose()
+}
+
+/// the configuration file, holding named contexts
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_context: Option<String>,
+    #[serde(default)]
+    contexts: BTreeMap<String, Context>,
+}
+
+impl Config {
+    /// `$TAGCTL_CONFIG`, or `config.toml` in the tagctl directory of the XDG config home
+    fn path() -> anyhow::Result<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_PATH_ENV) {
+            return Ok(path.into());
+        }
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+            .context("cannot locate the home directory")?;
+        Ok(config_home.join("tagctl").join("config.toml"))
+    }
+
+    /// loads the configuration file. a missing file is an empty configuration.
+    pub(crate) fn load() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        toml::from_str(&content).map_err(|e| {
+            CliError::new(
+                ErrorKind::InvalidInput,
+                format!("invalid configuration {}: {}", path.display(), e),
+            )
+            .into()
+        })
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        std::fs::write(&path, toml::to_string_pretty(self)?).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// the context named `name`, or the current context. no context at all selects the defaults.
+    pub(crate) fn select(mut self, name: Option<&str>) -> anyhow::Result<Context> {
+        let Some(name) = name.map(str::to_string).or(self.current_context) else {
+            return Ok(Context::default());
+        };
+        self.contexts
+            .remove(&name)
+            .ok_or_else(|| CliError::new(ErrorKind::NotFound, format!("no context named {}", name)).into())
+    }
+
+    fn context_mut(&mut self, name: Option<String>) -> anyhow::Result<(String, &mut Context)> {
+        let name = name
+            .or_else(|| self.current_context.clone())
+            .ok_or_else(|| CliError::new(ErrorKind::InvalidInput, "no current context, pass --context"))?;
+        Ok((name.clone(), self.contexts.entry(name).or_default()))
+    }
+}
+
+pub(crate) fn handle_config_command(args: ConfigArgs) -> anyhow::Result<()> {
+    let mut config = Config::load()?;
+    match args.command {
+        ConfigCommand::Get { key: None, context } => {
+            let context = config.select(context.as_deref())?;
+            print!("{}", toml::to_string_pretty(&context)?);
+        }
+        ConfigCommand::Get {
+            key: Some(key),
+            context,
+        } => {
+            let value = config
+                .select(context.as_deref())?
+                .get(key)
+                .ok_or_else(|| CliError::new(ErrorKind::NotFound, format!("{} is not set", key)))?;
+            println!("{}", value);
+        }
+        ConfigCommand::Set { key, value, context } => {
+            config.context_mut(context)?.1.set(key, Some(&value))?;
+            config.save()?;
+        }
+        ConfigCommand::Unset { key, context } => {
+            config.context_mut(context)?.1.set(key, None)?;
+            config.save()?;
+        }
+        ConfigCommand::UseContext { name } => {
+            config.contexts.entry(name.clone()).or_default();
+            config.current_context = Some(name);
+            config.save()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ContextKey, ReasonPolicy};
+    use crate::output::OutputFormat;
+
+    #[test]
+    fn test_select_context() {
+        let config: Config = toml::from_str(
+            r#"
+            current_context = "prod"
+
+            [contexts.prod]
+            profile = "prod-admin"
+            output = "json"
+            ticket_ttl = "30m"
+            reason = "required"
+
+            [contexts.dev]
+            role_name = "deployer"
+            "#,
+        )
+        .unwrap();
+
+        let prod = config.select(None).unwrap();
+        assert_eq!(prod.profile.as_deref(), Some("prod-admin"));
+        assert_eq!(prod.output, Some(OutputFormat::Json));
+        assert_eq!(prod.reason, Some(ReasonPolicy::Required));
+        assert_eq!(
+            prod.ticket_ttl().unwrap().map(Into::into),
+            Some(std::time::Duration::from_secs(1800))
+        );
+    }
+
+    #[test]
+    fn test_select_missing_context() {
+        assert!(Config::default().select(Some("nope")).is_err());
+        assert!(Config::default().select(None).unwrap().profile.is_none());
+    }
+
+    #[test]
+    fn test_set_context_key() {
+        let mut config = Config::default();
+        let (_, context) = config.context_mut(Some("prod".to_string())).unwrap();
+        context.set(ContextKey::Output, Some("yaml")).unwrap();
+        assert!(context.set(ContextKey::TicketTtl, Some("soon")).is_err());
+        assert!(context.set(ContextKey::Reason, Some("sometimes")).is_err());
+        assert_eq!(context.get(ContextKey::Output).as_deref(), Some("yaml"));
+
+        context.set(ContextKey::Output, None).unwrap();
+        assert_eq!(context.get(ContextKey::Output), None);
+    }
+}
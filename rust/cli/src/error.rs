@@ -0,0 +1,168 @@
+use approval::{
+    error::{ApiError, ApiErrorKind},
+    iam::{ListPrincipalsError, ListTicketsError, PrincipalError, SetTicketError, UnsetTicketError},
+    ticket::{ApprovalTicket, ExtendError, ParseError},
+};
+use serde::Serialize;
+use thiserror::Error;
+
+/// the kinds of failures reported by the CLI, each with its own exit code
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErrorKind {
+    /// any failure not covered by a more specific kind
+    General,
+    /// the arguments or the state they refer to are invalid, matches clap's usage error code
+    InvalidInput,
+    /// the principal or ticket does not exist
+    NotFound,
+    /// the caller is not allowed to perform the operation
+    AccessDenied,
+    /// the operation would replace state owned by someone else
+    Conflict,
+    /// AWS could not be reached, or did not respond properly
+    Transport,
+    /// the operation did not complete in time
+    Timeout,
+}
+
+impl ErrorKind {
+    pub(crate) fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::General => 1,
+            ErrorKind::InvalidInput => 2,
+            ErrorKind::NotFound => 3,
+            ErrorKind::AccessDenied => 4,
+            ErrorKind::Conflict => 5,
+            ErrorKind::Transport => 6,
+            ErrorKind::Timeout => 7,
+        }
+    }
+
+    /// classifies an error by the first typed error found in its chain
+    pub(crate) fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|e| {
+                if let Some(e) = e.downcast_ref::<CliError>() {
+                    return Some(e.kind);
+                }
+                if let Some(e) = e.downcast_ref::<ApiError>() {
+                    return Some(match e.kind {
+                        ApiErrorKind::AccessDenied => ErrorKind::AccessDenied,
+                        ApiErrorKind::NotFound => ErrorKind::NotFound,
+                        ApiErrorKind::Transport | ApiErrorKind::Throttling => ErrorKind::Transport,
+                        ApiErrorKind::Service => ErrorKind::General,
+                    });
+                }
+                if let Some(SetTicketError::Conflict { .. }) = e.downcast_ref::<SetTicketError>() {
+                    return Some(ErrorKind::Conflict);
+                }
+                if let Some(ListPrincipalsError::NotFound { .. }) = e.downcast_ref::<ListPrincipalsError>() {
+                    return Some(ErrorKind::NotFound);
+                }
+                if principal_error(e).is_some() {
+                    return Some(ErrorKind::InvalidInput);
+                }
+                if e.is::<ExtendError>() || e.is::<ParseError>() {
+                    return Some(ErrorKind::InvalidInput);
+                }
+                #[cfg(feature = "chainable")]
+                if e.is::<approval::ticket::DeriveError>() {
+                    return Some(ErrorKind::InvalidInput);
+                }
+                #[cfg(feature = "signed")]
+                if let Some(e) = e.downcast_ref::<approval::ticket::SignatureError>() {
+                    return Some(match e {
+                        approval::ticket::SignatureError::TooLong(_) => ErrorKind::InvalidInput,
+                        _ => ErrorKind::AccessDenied,
+                    });
+                }
+                None
+            })
+            .unwrap_or(ErrorKind::General)
+    }
+}
+
+/// an error raised by the CLI itself, rather than by the libraries it calls
+#[derive(Error, Debug)]
+#[error("{message}")]
+pub(crate) struct CliError {
+    pub(crate) kind: ErrorKind,
+    message: String,
+}
+
+impl CliError {
+    pub(crate) fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// the principal refused by a manager, as the managers' errors wrap it transparently
+fn principal_error<'a>(e: &'a (dyn std::error::Error + 'static)) -> Option<&'a PrincipalError> {
+    e.downcast_ref::<PrincipalError>()
+        .or_else(|| match e.downcast_ref::<ListPrincipalsError>()? {
+            ListPrincipalsError::Principal(e) => Some(e),
+            _ => None,
+        })
+        .or_else(|| match e.downcast_ref::<ListTicketsError>()? {
+            ListTicketsError::Principal(e) => Some(e),
+            _ => None,
+        })
+        .or_else(|| match e.downcast_ref::<SetTicketError>()? {
+            SetTicketError::Principal(e) => Some(e),
+            _ => None,
+        })
+        .or_else(|| match e.downcast_ref::<UnsetTicketError>()? {
+            UnsetTicketError::Principal(e) => Some(e),
+            _ => None,
+        })
+}
+
+/// the ticket which a conflicting operation would have replaced, if any
+pub(crate) fn conflicting_ticket(err: &anyhow::Error) -> Option<&ApprovalTicket> {
+    err.chain().find_map(|e| match e.downcast_ref::<SetTicketError>() {
+        Some(SetTicketError::Conflict { existing }) => Some(existing),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CliError, ErrorKind};
+    use anyhow::Context;
+    use approval::{
+        iam::{ListPrincipalsError, ListTicketsError, PrincipalError, SetTicketError},
+        principal::{IamPrincipal, PrincipalKind},
+        ticket::{ApprovalTicket, ExtendError},
+    };
+
+    #[test]
+    fn test_error_kind_of() {
+        let existing = "by/alice/exp=1618033988/for/bob".parse::<ApprovalTicket>().unwrap();
+        let conflict = anyhow::Error::new(SetTicketError::Conflict { existing }).context("setting ticket");
+        assert_eq!(ErrorKind::of(&conflict), ErrorKind::Conflict);
+
+        let not_found = anyhow::Error::new(CliError::new(ErrorKind::NotFound, "no ticket"));
+        assert_eq!(ErrorKind::of(&not_found), ErrorKind::NotFound);
+
+        let invalid: anyhow::Result<()> = Err(ExtendError::NonPositiveExtension).context("extending ticket");
+        assert_eq!(ErrorKind::of(&invalid.unwrap_err()), ErrorKind::InvalidInput);
+
+        let missing = anyhow::Error::new(ListPrincipalsError::NotFound {
+            kind: PrincipalKind::Role,
+            name: "admin".to_string(),
+        });
+        assert_eq!(ErrorKind::of(&missing), ErrorKind::NotFound);
+
+        let other_account = anyhow::Error::new(ListTicketsError::Principal(PrincipalError::OtherAccount {
+            principal: Box::new(IamPrincipal::role("210987654321", "admin")),
+            account: "123456789012".to_string(),
+        }));
+        assert_eq!(ErrorKind::of(&other_account), ErrorKind::InvalidInput);
+
+        assert_eq!(ErrorKind::of(&anyhow::anyhow!("boom")), ErrorKind::General);
+    }
+}
@@ -0,0 +1,467 @@
+use crate::{config::ContextArgs, output::OutputFormat};
+use anyhow::Context;
+use approval::{
+    error::ApiError,
+    scp::{self, Certainty, Enforcement, ResourceAccess, Statement, TaggingAttempt},
+    tags::Namespace,
+    ticket::ApprovalTicket,
+};
+use clap::Args;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Read;
+use thiserror::Error;
+
+/// the schema identifier of explanation documents. bump the version on any breaking change to [`Explanation`].
+pub(crate) const EXPLANATION_SCHEMA: &str = "tagctl/explanation/v1";
+
+const ENCODED_MESSAGE_MARKER: &str = "Encoded authorization failure message:";
+
+#[derive(Args)]
+#[command(about)]
+pub(crate) struct ExplainArgs {
+    #[command(flatten)]
+    pub(crate) context: ContextArgs,
+
+    /// the output format
+    #[arg(long, short, value_enum, default_value_t)]
+    pub(crate) output: OutputFormat,
+
+    /// an encoded authorization message, an error message, or a CloudTrail event.
+    /// pass `-` to read from stdin, or `@<path>` to read from a file.
+    pub(crate) input: String,
+}
+
+/// the evidence of a denied request, as given by the user
+#[derive(Debug, PartialEq)]
+enum Evidence {
+    Encoded(String),
+    Decoded(Value),
+    CloudTrail(Value),
+    Message(String),
+}
+
+impl Evidence {
+    fn parse(input: &str) -> Self {
+        let input = input.trim();
+        if let Ok(value) = serde_json::from_str::<Value>(input) {
+            // EventBridge wraps CloudTrail events in `detail`
+            let value = match value.get("detail") {
+                Some(detail) if detail.get("eventName").is_some() => detail.clone(),
+                _ => value,
+            };
+            if value.get("eventName").is_some() {
+                return Evidence::CloudTrail(value);
+            }
+            if value.get("matchedStatements").is_some() || value.get("context").is_some() {
+                return Evidence::Decoded(value);
+            }
+        }
+        if let Some((_, encoded)) = input.split_once(ENCODED_MESSAGE_MARKER) {
+            if let Some(encoded) = encoded.split_whitespace().next() {
+                return Evidence::Encoded(encoded.to_string());
+            }
+        }
+        let looks_encoded = input.len() > 64 && !input.contains(char::is_whitespace);
+        if looks_encoded {
+            return Evidence::Encoded(input.to_string());
+        }
+        Evidence::Message(input.to_string())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct Finding {
+    pub(crate) sid: &'static str,
+    pub(crate) key: &'static str,
+    pub(crate) certainty: Certainty,
+    pub(crate) summary: &'static str,
+    pub(crate) remedy: &'static str,
+}
+
+impl Finding {
+    fn new(statement: &'static Statement, certainty: Certainty) -> Self {
+        Self {
+            sid: statement.sid,
+            key: statement.key,
+            certainty,
+            summary: statement.summary,
+            remedy: statement.remedy,
+        }
+    }
+}
+
+/// the control-tags statements responsible for a denied request
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct Explanation {
+    pub(crate) schema: &'static str,
+    pub(crate) action: Option<String>,
+    pub(crate) resource: Option<String>,
+    pub(crate) principal_arn: Option<String>,
+    /// facts the findings rely on which were inferred rather than read from the request
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) assumptions: Vec<String>,
+    pub(crate) findings: Vec<Finding>,
+}
+
+impl Explanation {
+    fn new() -> Self {
+        Self {
+            schema: EXPLANATION_SCHEMA,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn from_attempt(attempt: &TaggingAttempt) -> Self {
+        Self {
+            findings: scp::evaluate(attempt)
+                .into_iter()
+                .map(|(statement, certainty)| Finding::new(statement, certainty))
+                .collect(),
+            ..Self::new()
+        }
+    }
+
+    /// records a fact the findings rely on which was inferred rather than read from the request
+    pub(crate) fn assuming(mut self, assumption: impl Into<String>) -> Self {
+        self.assumptions.push(assumption.into());
+        self
+    }
+
+    /// the seal statements which would deny an access, given what is known about it
+    pub(crate) fn from_access(access: &ResourceAccess, enforcement: Enforcement) -> Self {
+        Self {
+            action: Some(access.action.clone()),
+            findings: scp::evaluate_access(access, enforcement)
+                .into_iter()
+                .map(|(statement, certainty)| Finding::new(statement, certainty))
+                .collect(),
+            ..Self::new()
+        }
+    }
+
+    /// explains a decoded authorization message, as returned by `sts:DecodeAuthorizationMessage`
+    fn from_decoded(namespace: &Namespace, decoded: &Value) -> Self {
+        let context = &decoded["context"];
+        let matched: Vec<_> = decoded["matchedStatements"]["items"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|item| item["statementId"].as_str())
+            .filter_map(Statement::find)
+            .map(|statement| Finding::new(statement, Certainty::Matched))
+            .collect();
+
+        // without SIDs (`emit_scp_sids = "none"`), fall back to replaying the request context
+        let findings = if matched.is_empty() {
+            Self::from_attempt(&attempt_from_conditions(namespace, context)).findings
+        } else {
+            matched
+        };
+
+        Self {
+            action: context["action"].as_str().map(str::to_string),
+            resource: context["resource"].as_str().map(str::to_string),
+            principal_arn: context["principal"]["arn"].as_str().map(str::to_string),
+            findings,
+            ..Self::new()
+        }
+    }
+
+    /// explains a CloudTrail event of a denied request
+    fn from_cloudtrail(namespace: &Namespace, event: &Value) -> Self {
+        let params = &event["requestParameters"];
+        let tags: Vec<(String, String)> = params["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|tag| Some((tag["key"].as_str()?.to_string(), tag["value"].as_str()?.to_string())))
+            .collect();
+        let untagged = params["tagKeys"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|key| key.as_str().map(str::to_string));
+
+        let session_context = &event["userIdentity"]["sessionContext"];
+        let source_identity = if session_context.is_null() {
+            None
+        } else {
+            Some(session_context["sourceIdentity"].as_str().map(str::to_string))
+        };
+
+        let ticket_key = namespace.ticket_key();
+        let attempt = TaggingAttempt {
+            namespace: namespace.clone(),
+            tag_keys: tags.iter().map(|(k, _)| k.clone()).chain(untagged).collect(),
+            ticket: tags
+                .iter()
+                .find(|(k, _)| *k == ticket_key)
+                .and_then(|(_, v)| v.parse::<ApprovalTicket>().ok()),
+            source_identity,
+            grant_area: None,
+        };
+
+        let mut explanation = match event["errorMessage"].as_str() {
+            Some(message) if !sids_in(message).is_empty() => Self::from_message(message),
+            _ => Self::from_attempt(&attempt),
+        };
+        explanation.action = event["eventSource"]
+            .as_str()
+            .zip(event["eventName"].as_str())
+            .map(|(source, name)| format!("{}:{}", source.trim_end_matches(".amazonaws.com"), name));
+        explanation.principal_arn = event["userIdentity"]["arn"].as_str().map(str::to_string);
+        explanation
+    }
+
+    /// explains an error message. AWS does not name the denying statement in messages, unless it was pasted along.
+    fn from_message(message: &str) -> Self {
+        let action_and_resource = message
+            .split_once("perform: ")
+            .map(|(_, rest)| rest)
+            .and_then(|rest| rest.split_once(" on resource: "));
+        let findings = sids_in(message)
+            .into_iter()
+            .map(|statement| Finding::new(statement, Certainty::Matched))
+            .collect();
+
+        Self {
+            action: action_and_resource.map(|(action, _)| action.trim().to_string()),
+            resource: action_and_resource.and_then(|(_, rest)| rest.split_whitespace().next().map(str::to_string)),
+            principal_arn: message
+                .split_once("User: ")
+                .and_then(|(_, rest)| rest.split_whitespace().next().map(str::to_string)),
+            findings,
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn render(&self, format: OutputFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Yaml => serde_yaml::to_string(self)?.trim_end().to_string(),
+            OutputFormat::Text | OutputFormat::Table => self.render_text(),
+        })
+    }
+
+    pub(crate) fn render_text(&self) -> String {
+        let mut lines = vec![];
+        if let Some(action) = &self.action {
+            lines.push(format!(
+                "Denied {}{}{}",
+                action,
+                self.resource
+                    .as_ref()
+                    .map(|resource| format!(" on {}", resource))
+                    .unwrap_or_default(),
+                self.principal_arn
+                    .as_ref()
+                    .map(|principal| format!(" for {}", principal))
+                    .unwrap_or_default(),
+            ));
+        }
+        for assumption in &self.assumptions {
+            lines.push(format!("Assuming {}", assumption));
+        }
+        if self.findings.is_empty() {
+            lines.push("No control-tags statement could be identified as the cause of the denial".to_string());
+        }
+        for finding in &self.findings {
+            let certainty = match finding.certainty {
+                Certainty::Matched => "matched",
+                Certainty::Likely => "likely",
+                Certainty::Possible => "possible",
+            };
+            lines.push(format!(
+                "[{}] {} ({}): {}",
+                certainty, finding.sid, finding.key, finding.summary
+            ));
+            lines.push(format!("    fix: {}", finding.remedy));
+        }
+        lines.join("\n")
+    }
+}
+
+/// an error, denied by a control-tags SCP, together with its explanation
+#[derive(Error, Debug)]
+#[error("request denied by a control-tags SCP")]
+pub(crate) struct ExplainedError {
+    pub(crate) explanation: Explanation,
+    #[source]
+    source: anyhow::Error,
+}
+
+impl ExplainedError {
+    pub(crate) fn new(explanation: Explanation, source: anyhow::Error) -> Self {
+        Self { explanation, source }
+    }
+
+    pub(crate) fn find(err: &anyhow::Error) -> Option<&ExplainedError> {
+        err.chain().find_map(|e| e.downcast_ref::<ExplainedError>())
+    }
+}
+
+/// the control-tags statements named in a piece of text, by short or long SID
+fn sids_in(text: &str) -> Vec<&'static Statement> {
+    let mut found: Vec<&'static Statement> = vec![];
+    for word in text.split(|c: char| !c.is_ascii_alphanumeric()) {
+        if let Some(statement) = Statement::find(word) {
+            if !found.contains(&statement) {
+                found.push(statement);
+            }
+        }
+    }
+    found
+}
+
+/// rebuilds a tagging attempt from the condition keys of a decoded authorization message
+fn attempt_from_conditions(namespace: &Namespace, context: &Value) -> TaggingAttempt {
+    let conditions: Vec<(&str, Vec<&str>)> = context["conditions"]["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let values = item["values"]["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v["value"].as_str())
+                .collect();
+            Some((item["key"].as_str()?, values))
+        })
+        .collect();
+    let first = |key: &str| {
+        conditions
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .and_then(|(_, values)| values.first().map(|v| v.to_string()))
+    };
+
+    TaggingAttempt {
+        namespace: namespace.clone(),
+        tag_keys: conditions
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("aws:TagKeys"))
+            .map(|(_, values)| values.iter().map(|v| v.to_string()).collect())
+            .unwrap_or_default(),
+        ticket: first(&format!("aws:RequestTag/{}", namespace.ticket_key())).and_then(|v| v.parse().ok()),
+        source_identity: Some(first("aws:SourceIdentity")),
+        grant_area: Some(first(&format!("aws:PrincipalTag/{}", namespace.grant_area_key()))),
+    }
+}
+
+/// reads the input argument, from stdin or a file if requested
+fn read_input(input: &str) -> anyhow::Result<String> {
+    if input == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf)?;
+        return Ok(buf);
+    }
+    if let Some(path) = input.strip_prefix('@') {
+        return std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path));
+    }
+    Ok(input.to_string())
+}
+
+pub(crate) async fn explain(
+    sts: &aws_sdk_sts::Client,
+    namespace: &Namespace,
+    input: &str,
+) -> anyhow::Result<Explanation> {
+    let input = read_input(input)?;
+    Ok(match Evidence::parse(&input) {
+        Evidence::Encoded(encoded) => {
+            let decoded = sts
+                .decode_authorization_message()
+                .encoded_message(encoded)
+                .send()
+                .await
+                .map_err(ApiError::from_sdk)
+                .context("cannot decode authorization message")?
+                .decoded_message
+                .context("no message returned by sts:DecodeAuthorizationMessage")?;
+            Explanation::from_decoded(namespace, &serde_json::from_str(&decoded)?)
+        }
+        Evidence::Decoded(decoded) => Explanation::from_decoded(namespace, &decoded),
+        Evidence::CloudTrail(event) => Explanation::from_cloudtrail(namespace, &event),
+        Evidence::Message(message) => Explanation::from_message(&message),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evidence, Explanation};
+    use approval::{scp::Certainty, tags::Namespace};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_evidence() {
+        let message = "An error occurred (UnauthorizedOperation). Encoded authorization failure message: abc123 (more)";
+        assert_eq!(Evidence::parse(message), Evidence::Encoded("abc123".to_string()));
+        assert!(matches!(
+            Evidence::parse(r#"{"detail": {"eventName": "TagRole"}}"#),
+            Evidence::CloudTrail(_)
+        ));
+        assert!(matches!(
+            Evidence::parse(r#"{"allowed": false, "matchedStatements": {"items": []}}"#),
+            Evidence::Decoded(_)
+        ));
+        assert!(matches!(Evidence::parse("AccessDenied"), Evidence::Message(_)));
+    }
+
+    #[test]
+    fn test_explain_decoded() {
+        let decoded = json!({
+            "allowed": false,
+            "explicitDeny": true,
+            "matchedStatements": {"items": [{"statementId": "AntiReflexive", "effect": "DENY"}]},
+            "context": {
+                "principal": {"arn": "arn:aws:sts::123456789012:assumed-role/tagctl-mirror-admin/alice"},
+                "action": "iam:TagRole",
+                "resource": "arn:aws:iam::123456789012:role/tagctl-mirror-admin"
+            }
+        });
+        let explanation = Explanation::from_decoded(&Namespace::default(), &decoded);
+        assert_eq!(explanation.action.as_deref(), Some("iam:TagRole"));
+        assert_eq!(explanation.findings.len(), 1);
+        assert_eq!(explanation.findings[0].sid, "CT07");
+        assert_eq!(explanation.findings[0].certainty, Certainty::Matched);
+    }
+
+    #[test]
+    fn test_explain_cloudtrail() {
+        let event = json!({
+            "eventSource": "iam.amazonaws.com",
+            "eventName": "TagRole",
+            "errorCode": "AccessDenied",
+            "userIdentity": {
+                "arn": "arn:aws:sts::123456789012:assumed-role/tagctl-mirror-admin/alice",
+                "sessionContext": {"sourceIdentity": "alice"}
+            },
+            "requestParameters": {
+                "roleName": "tagctl-mirror-admin",
+                "tags": [{"key": "tagctl:v1/admin/mpa/ticket", "value": "by/bob/exp=1618033988/for/alice"}]
+            }
+        });
+        let explanation = Explanation::from_cloudtrail(&Namespace::default(), &event);
+        let sids: Vec<_> = explanation.findings.iter().map(|f| f.sid).collect();
+        assert_eq!(explanation.action.as_deref(), Some("iam:TagRole"));
+        assert!(sids.contains(&"CT07"));
+        assert!(sids.contains(&"CT08"));
+        assert!(explanation.assumptions.is_empty());
+    }
+
+    #[test]
+    fn test_render_assumptions() {
+        let explanation = Explanation::new().assuming("the source identity is alice");
+        assert!(explanation
+            .render_text()
+            .starts_with("Assuming the source identity is alice\n"));
+        let json: serde_json::Value = serde_json::to_value(&explanation).unwrap();
+        assert_eq!(json["assumptions"], json!(["the source identity is alice"]));
+        assert!(serde_json::to_value(Explanation::new())
+            .unwrap()
+            .get("assumptions")
+            .is_none());
+    }
+}
@@ -0,0 +1,422 @@
+use crate::{
+    config::ContextArgs,
+    error::{CliError, ErrorKind},
+    load_sdk_config,
+    output::{OutputFormat, TicketView},
+    whoami::{whoami, ScpKind, WhoamiOutput},
+};
+use anyhow::Context;
+use approval::{
+    catalog::ActionCatalog,
+    error::ApiError,
+    guard::{compile, Compilation, GuardedActions, Severity, SpecFile},
+};
+use clap::{Args, Subcommand};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// the schema identifier of guard documents. bump the version on any breaking change to [`GuardOutput`].
+pub(crate) const GUARD_SCHEMA: &str = "tagctl/guard/v1";
+/// the schema identifier of compilation documents. bump the version on any breaking change to [`CompilationOutput`].
+pub(crate) const COMPILATION_SCHEMA: &str = "tagctl/guard-compilation/v1";
+
+#[derive(Args)]
+#[command(about)]
+pub(crate) struct GuardArgs {
+    #[command(flatten)]
+    pub(crate) context: ContextArgs,
+
+    /// the output format
+    #[arg(long, short, global = true, value_enum, default_value_t)]
+    pub(crate) output: OutputFormat,
+
+    #[command(subcommand)]
+    command: GuardCommand,
+}
+
+#[derive(Subcommand)]
+enum GuardCommand {
+    /// lists the guarded-action SCPs applying to the account, and the actions they guard
+    List {
+        /// also list every action of the bundled catalog matched by the guarded patterns
+        #[arg(long, default_value_t = false)]
+        expand: bool,
+    },
+    /// tells whether an action needs a ticket, and whether the caller's ticket satisfies it
+    Check {
+        /// the IAM action, e.g. `s3:DeleteBucket`
+        action: String,
+    },
+    /// validates a guarded-action spec against the bundled IAM action catalog, and compiles its SCPs and
+    /// attachments. works offline.
+    Compile {
+        /// the spec, in YAML or JSON, shaped like the terraform module variables
+        /// `deployment_targets` and `guarded_action_spec`
+        spec: PathBuf,
+        /// writes `<policy name>.json` per SCP and `attachments.json` into the directory
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct GuardView {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) key: String,
+    /// the account, organizational unit or root the SCP is attached to
+    pub(crate) target_id: String,
+    pub(crate) actions: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) not_actions: Vec<String>,
+    /// the actions of the bundled catalog matched by the guarded patterns, with `--expand`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) expanded: Option<Vec<String>>,
+}
+
+/// whether the caller's ticket lets them perform a guarded action
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TicketStatus {
+    /// the action is not guarded
+    NotNeeded,
+    /// the caller's role holds a ticket naming the caller as receiver
+    Satisfied,
+    /// the caller's role holds no ticket
+    Missing,
+    /// the ticket on the caller's role has expired, and is about to be evicted by the retention lambda
+    Expired,
+    /// the ticket on the caller's role names someone else as receiver
+    OtherReceiver,
+    /// the caller's source identity is unknown, so the receiver cannot be compared
+    UnknownIdentity,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct CheckView {
+    pub(crate) action: String,
+    /// the catalog's spelling of the action, if the bundled catalog lists it
+    pub(crate) known_action: Option<String>,
+    pub(crate) needs_ticket: bool,
+    /// the names of the guarded-action SCPs denying the action without a ticket
+    pub(crate) guarded_by: Vec<String>,
+    pub(crate) ticket_status: TicketStatus,
+    pub(crate) ticket: Option<TicketView>,
+}
+
+/// the document emitted by guard commands
+#[derive(Serialize, Debug)]
+pub(crate) struct GuardOutput {
+    pub(crate) schema: &'static str,
+    pub(crate) account_id: String,
+    pub(crate) policies: Vec<GuardView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) check: Option<CheckView>,
+}
+
+impl GuardOutput {
+    pub(crate) fn render(&self, format: OutputFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Yaml => serde_yaml::to_string(self)?.trim_end().to_string(),
+            OutputFormat::Text | OutputFormat::Table => self.render_text(),
+        })
+    }
+
+    fn render_text(&self) -> String {
+        let Some(check) = &self.check else {
+            return self.render_policies();
+        };
+        if !check.needs_ticket {
+            return format!("{} does not need a ticket in {}", check.action, self.account_id);
+        }
+        let mut lines = vec![format!(
+            "{} needs a ticket in {}, guarded by {}",
+            check.action,
+            self.account_id,
+            check.guarded_by.join(", ")
+        )];
+        lines.push(match (check.ticket_status, &check.ticket) {
+            (TicketStatus::Satisfied, Some(ticket)) => format!("Your ticket satisfies it: {}", ticket.value),
+            (TicketStatus::OtherReceiver, Some(ticket)) => {
+                format!(
+                    "Your role's ticket is for {}, not for you: {}",
+                    ticket.receiver, ticket.value
+                )
+            }
+            (TicketStatus::UnknownIdentity, Some(ticket)) => format!(
+                "Your role holds a ticket for {}, but your source identity is unknown: {}",
+                ticket.receiver, ticket.value
+            ),
+            (TicketStatus::Expired, Some(ticket)) => format!(
+                "Your role's ticket expired at {}, ask another human for a new one with `tagctl ticket set <you>`",
+                ticket.expires_at.map(|exp| exp.to_rfc3339()).unwrap_or_default()
+            ),
+            _ => "Your role holds no ticket, ask another human with `tagctl ticket set <you>`".to_string(),
+        });
+        if check.known_action.is_none() {
+            lines.push(format!(
+                "note: {} is not in the bundled IAM action catalog",
+                check.action
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn render_policies(&self) -> String {
+        if self.policies.is_empty() {
+            return format!("No guarded-action SCP applies to {}", self.account_id);
+        }
+        let mut lines = vec![];
+        for policy in &self.policies {
+            lines.push(format!(
+                "{} ({}) attached to {}",
+                policy.name, policy.id, policy.target_id
+            ));
+            lines.extend(policy.actions.iter().map(|action| format!("  {}", action)));
+            lines.extend(policy.not_actions.iter().map(|action| format!("  all but {}", action)));
+            if let Some(expanded) = &policy.expanded {
+                lines.push(format!("  expands to {} actions:", expanded.len()));
+                lines.extend(expanded.iter().map(|action| format!("    {}", action)));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// the document emitted by `tagctl guard compile`
+#[derive(Serialize, Debug)]
+pub(crate) struct CompilationOutput {
+    pub(crate) schema: &'static str,
+    #[serde(flatten)]
+    pub(crate) compilation: Compilation,
+    /// the files written with `--out-dir`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) written: Vec<PathBuf>,
+}
+
+impl CompilationOutput {
+    pub(crate) fn render(&self, format: OutputFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Yaml => serde_yaml::to_string(self)?.trim_end().to_string(),
+            OutputFormat::Text | OutputFormat::Table => self.render_text(),
+        })
+    }
+
+    fn render_text(&self) -> String {
+        let compilation = &self.compilation;
+        let mut lines: Vec<String> = compilation
+            .diagnostics
+            .iter()
+            .map(|d| {
+                let severity = match d.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                format!("{}: {}: {}", severity, d.key, d.message)
+            })
+            .collect();
+        for policy in &compilation.policies {
+            let targets: Vec<&str> = compilation
+                .attachments
+                .iter()
+                .filter(|a| a.key == policy.key)
+                .map(|a| a.target_id.as_str())
+                .collect();
+            lines.push(format!(
+                "{} attached to {}",
+                policy.name,
+                if targets.is_empty() {
+                    "nothing".to_string()
+                } else {
+                    targets.join(", ")
+                }
+            ));
+        }
+        lines.extend(self.written.iter().map(|path| format!("wrote {}", path.display())));
+        lines.push(format!(
+            "Compiled {} SCPs and {} attachments",
+            compilation.policies.len(),
+            compilation.attachments.len()
+        ));
+        lines.join("\n")
+    }
+}
+
+/// writes the compiled SCP documents and the attachment plan into `dir`
+fn write_compilation(compilation: &Compilation, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let mut files = compilation
+        .policies
+        .iter()
+        .map(|policy| {
+            Ok((
+                dir.join(format!("{}.json", policy.name)),
+                serde_json::to_string_pretty(&policy.document)?,
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    files.push((
+        dir.join("attachments.json"),
+        serde_json::to_string_pretty(&compilation.attachments)?,
+    ));
+    for (path, content) in &files {
+        std::fs::write(path, content).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+fn handle_compile_command(args: &GuardArgs, spec: &Path, out_dir: Option<&Path>) -> anyhow::Result<()> {
+    let context = args.context.load()?;
+    let schema = context.schema()?;
+    let content = std::fs::read_to_string(spec).with_context(|| format!("reading {}", spec.display()))?;
+    // YAML is a superset of JSON, so this reads both
+    let spec_file: SpecFile = serde_yaml::from_str(&content).map_err(|e| {
+        CliError::new(
+            ErrorKind::InvalidInput,
+            format!("invalid spec {}: {}", spec.display(), e),
+        )
+    })?;
+
+    let compilation = compile(&spec_file, ActionCatalog::bundled(), schema.current());
+    let has_errors = compilation.has_errors();
+    let written = match out_dir {
+        Some(dir) if !has_errors => write_compilation(&compilation, dir)?,
+        _ => vec![],
+    };
+    let output = CompilationOutput {
+        schema: COMPILATION_SCHEMA,
+        compilation,
+        written,
+    };
+    println!("{}", output.render(args.output)?);
+    if has_errors {
+        return Err(CliError::new(ErrorKind::InvalidInput, format!("{} has errors", spec.display())).into());
+    }
+    Ok(())
+}
+
+/// whether the ticket on the caller's role satisfies a guarded-action SCP, which compares its receiver with
+/// the caller's source identity
+fn ticket_status(needs_ticket: bool, whoami: &WhoamiOutput) -> TicketStatus {
+    match (&whoami.ticket, &whoami.source_identity) {
+        _ if !needs_ticket => TicketStatus::NotNeeded,
+        (None, _) => TicketStatus::Missing,
+        (Some(ticket), _) if ticket.expired => TicketStatus::Expired,
+        (Some(_), None) => TicketStatus::UnknownIdentity,
+        (Some(ticket), Some(identity)) if ticket.receiver == *identity => TicketStatus::Satisfied,
+        (Some(_), Some(_)) => TicketStatus::OtherReceiver,
+    }
+}
+
+pub(crate) async fn handle_guard_command(args: GuardArgs) -> anyhow::Result<()> {
+    if let GuardCommand::Compile { spec, out_dir } = &args.command {
+        return handle_compile_command(&args, spec, out_dir.as_deref());
+    }
+    let context = args.context.load()?;
+    let schema = context.schema()?;
+    let sdk_config = load_sdk_config(context.profile).await;
+    let orgs = aws_sdk_organizations::Client::new(&sdk_config);
+    let catalog = ActionCatalog::bundled();
+
+    // the SCPs only honor tickets under the current ticket key
+    let whoami = whoami(&sdk_config, &schema.current_only()).await?;
+    let Some(scps) = &whoami.scps else {
+        let reason = whoami.notes.join(", ");
+        return Err(CliError::new(
+            ErrorKind::AccessDenied,
+            format!(
+                "cannot find the guarded-action SCPs applying to the account: {}",
+                reason
+            ),
+        )
+        .into());
+    };
+
+    let mut guarded = vec![];
+    for scp in scps.iter().filter(|scp| scp.kind == ScpKind::GuardedActions) {
+        let content = orgs
+            .describe_policy()
+            .policy_id(&scp.id)
+            .send()
+            .await
+            .map_err(ApiError::from_sdk)?
+            .policy
+            .and_then(|policy| policy.content)
+            .with_context(|| format!("no content returned for {}", scp.name))?;
+        let actions =
+            GuardedActions::from_policy(&scp.name, &content).with_context(|| format!("reading {}", scp.name))?;
+        guarded.push((scp, actions));
+    }
+
+    let check = match &args.command {
+        GuardCommand::List { .. } | GuardCommand::Compile { .. } => None,
+        GuardCommand::Check { action } => {
+            let guarded_by: Vec<String> = guarded
+                .iter()
+                .filter(|(_, actions)| actions.guards(action))
+                .map(|(scp, _)| scp.name.clone())
+                .collect();
+            let needs_ticket = !guarded_by.is_empty();
+            Some(CheckView {
+                action: action.clone(),
+                known_action: catalog.find(action).map(str::to_string),
+                needs_ticket,
+                guarded_by,
+                ticket_status: ticket_status(needs_ticket, &whoami),
+                ticket: whoami.ticket.clone(),
+            })
+        }
+    };
+    let expand = matches!(args.command, GuardCommand::List { expand: true });
+
+    let policies = guarded
+        .into_iter()
+        .map(|(scp, actions)| GuardView {
+            id: scp.id.clone(),
+            name: scp.name.clone(),
+            key: actions.key.clone(),
+            target_id: scp.target_id.clone(),
+            expanded: expand.then(|| actions.expand(catalog).into_iter().map(str::to_string).collect()),
+            actions: actions.actions,
+            not_actions: actions.not_actions,
+        })
+        .collect();
+    let output = GuardOutput {
+        schema: GUARD_SCHEMA,
+        account_id: whoami.account_id.clone(),
+        policies,
+        check,
+    };
+    println!("{}", output.render(args.output)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ticket_status, TicketStatus};
+    use crate::{output::TicketView, whoami::WhoamiOutput};
+    use approval::ticket::ApprovalTicket;
+    use chrono::Utc;
+
+    #[test]
+    fn test_ticket_status() {
+        let ticket = "by/alice/exp=4102444800/for/bob".parse::<ApprovalTicket>().unwrap();
+        let mut whoami = WhoamiOutput::default();
+        assert_eq!(ticket_status(false, &whoami), TicketStatus::NotNeeded);
+        assert_eq!(ticket_status(true, &whoami), TicketStatus::Missing);
+
+        whoami.ticket = Some(TicketView::new(&ticket, Utc::now()));
+        assert_eq!(ticket_status(true, &whoami), TicketStatus::UnknownIdentity);
+        whoami.source_identity = Some("bob".to_string());
+        assert_eq!(ticket_status(true, &whoami), TicketStatus::Satisfied);
+        whoami.source_identity = Some("carol".to_string());
+        assert_eq!(ticket_status(true, &whoami), TicketStatus::OtherReceiver);
+
+        let expired = "by/alice/exp=1618033988/for/bob".parse::<ApprovalTicket>().unwrap();
+        whoami.ticket = Some(TicketView::new(&expired, Utc::now()));
+        whoami.source_identity = Some("bob".to_string());
+        assert_eq!(ticket_status(true, &whoami), TicketStatus::Expired);
+    }
+}
@@ -1,20 +1,38 @@
+mod config;
+mod error;
+mod explain;
+mod guard;
+mod migrate;
+mod output;
+mod policy;
 mod types;
+mod whoami;
 
-use anyhow::{bail, Context};
+use anyhow::Context;
 use approval::{
     self,
-    iam::ApprovalManager,
+    error::{ApiError, ApiErrorKind},
+    iam::{ApprovalManager, ListTicketsError, SetTicketError},
+    principal::IamPrincipal,
+    scp::TaggingAttempt,
+    tags::Namespace,
     ticket::{ApprovalTicket, HumanIdentity},
 };
 use aws_arn::ResourceName;
 use aws_config::BehaviorVersion;
 use aws_sdk_iam::config::SharedCredentialsProvider;
+use config::{ConfigArgs, ContextArgs, ReasonPolicy};
+use error::{CliError, ErrorKind};
+use explain::{ExplainArgs, ExplainedError, Explanation};
+use guard::GuardArgs;
+use migrate::MigrateArgs;
+use output::{ErrorOutput, OutputFormat, TicketAction, TicketOutput};
+use policy::PolicyArgs;
+use whoami::WhoamiArgs;
 
 use clap::{Args, Parser, Subcommand};
 
-use serde_json;
 use std::{cmp::min, sync::Arc};
-use tokio;
 
 #[derive(Parser)]
 #[command()]
@@ -29,19 +47,43 @@ enum RootCommand {
     Ticket(TicketArgs),
     /// Interact with mirror roles as an AWS SSO-managed IAM principal.
     Mirror(MirrorArgs),
+    /// Explain an access denial by the control-tags SCPs.
+    Explain(ExplainArgs),
+    /// Manage the configuration contexts of tagctl.
+    Config(ConfigArgs),
+    /// Migrate the control tags of the organization's accounts to another schema version.
+    Migrate(MigrateArgs),
+    /// Show who the caller is to the control-tags SCPs.
+    Whoami(WhoamiArgs),
+    /// Find out which actions need an approval ticket in the account.
+    Guard(GuardArgs),
+    /// Render and verify organization policies for the control tags.
+    Policy(PolicyArgs),
 }
 
 #[derive(Args)]
 #[command(about)]
 struct TicketArgs {
-    /// the AWS profile to use for the operation
-    #[arg(long, global = true)]
-    profile: Option<String>,
+    #[command(flatten)]
+    context: ContextArgs,
 
     /// The name of the role to manage. Default to the calling principal's name.
-    #[arg(long, global = true)]
+    #[arg(long, global = true, env = "TAGCTL_ROLE_NAME")]
     role_name: Option<String>,
 
+    /// the output format. Default to text.
+    #[arg(long, short, global = true, value_enum, env = "TAGCTL_OUTPUT")]
+    output: Option<OutputFormat>,
+
+    /// the human identity of the caller. Default to the caller's session name.
+    #[arg(long, global = true, env = "TAGCTL_IDENTITY")]
+    identity: Option<String>,
+
+    /// a file holding the giver's key, as written by `tagctl ticket keygen`. tickets set, extended or derived are signed with it.
+    #[cfg(feature = "signed")]
+    #[cfg_attr(feature = "signed", arg(long, global = true, env = "TAGCTL_SIGNING_KEY"))]
+    signing_key: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: TicketCommand,
 }
@@ -53,20 +95,82 @@ enum TicketCommand {
     /// sets an approval ticket on the principal
     Set {
         receiver: String,
+        /// replace an existing ticket issued by anyone, instead of failing
+        #[arg(long, default_value_t = false)]
+        force: bool,
+        /// how long the ticket is valid for. Default to 1h.
+        #[arg(long, env = "TAGCTL_TICKET_TTL")]
+        ttl: Option<humantime::Duration>,
+        /// what the ticket is meant to be used for
+        #[arg(long, env = "TAGCTL_SCOPE")]
+        scope: Option<String>,
+        /// why the ticket is given
+        #[arg(long)]
+        reason: Option<String>,
         #[cfg(feature = "chainable")]
         #[cfg_attr(feature = "chainable", arg(long, default_value_t = false))]
         chain: bool,
     },
     /// unsets an approval ticket on the principal
     Unset {},
+    /// extends the expiry of the approval ticket on the principal. only the original giver may extend a ticket.
+    Extend {
+        /// how long to extend the ticket by, e.g. "30m" or "1h 15m"
+        #[arg(long)]
+        by: humantime::Duration,
+    },
+    /// waits until the principal holds a ticket naming the caller as receiver, then optionally runs a command
+    Wait {
+        /// how long to wait for the approval before giving up. waits indefinitely if omitted.
+        #[arg(long)]
+        timeout: Option<humantime::Duration>,
+        /// how often to poll the principal for a ticket
+        #[arg(long, default_value = "5s", value_parser = parse_interval)]
+        interval: humantime::Duration,
+        /// a command to run as soon as the approval lands
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+    /// issues a ticket derived from a chainable ticket naming the caller as receiver.
+    /// the derived ticket never outlives its parent, and is evicted along with it.
+    #[cfg(feature = "chainable")]
+    Derive {
+        receiver: String,
+        /// the role holding the parent ticket. Default to the calling principal's name.
+        #[arg(long)]
+        parent_role_name: Option<String>,
+        /// how long the derived ticket is valid for. defaults to 1h, capped by the parent ticket's expiry.
+        #[arg(long)]
+        ttl: Option<humantime::Duration>,
+        /// replace an existing ticket issued by anyone, instead of failing
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// generates a key to sign tickets with, and prints its public key
+    #[cfg(feature = "signed")]
+    Keygen {
+        /// where to write the signing key. must not exist yet.
+        path: std::path::PathBuf,
+    },
+    /// verifies the signature of the ticket on the principal
+    #[cfg(feature = "signed")]
+    Verify {
+        /// the public keys trusted to sign tickets, as comma separated `<giver>=<public key>` entries
+        #[arg(long)]
+        trusted_keys: approval::signature::Keyring,
+    },
 }
 
+/// how long tickets are valid for, unless configured otherwise
+const DEFAULT_TICKET_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+/// matches the default `max_ticket_ttl_seconds` of the terraform module
+const DEFAULT_MAX_TICKET_TTL: std::time::Duration = std::time::Duration::from_secs(4 * 3600);
+
 #[derive(Args)]
 #[command(about)]
 struct MirrorArgs {
-    /// the AWS profile to use for the operation
-    #[arg(long, global = true)]
-    profile: Option<String>,
+    #[command(flatten)]
+    context: ContextArgs,
 
     #[command(subcommand)]
     command: MirrorCommand,
@@ -82,37 +186,82 @@ enum MirrorCommand {
 async fn main() {
     let program = Cli::parse();
 
-    match program.command {
-        RootCommand::Ticket(args) => match handle_ticket_commands(args).await {
-            Ok(_) => {}
-            Err(e) => eprintln!("Error: {:#}", e),
-        },
-        RootCommand::Mirror(args) => match handle_mirror_commands(args).await {
-            Ok(_) => {}
-            Err(e) => eprintln!("Error: {:#}", e),
+    let (format, result) = match program.command {
+        RootCommand::Ticket(args) => match args.context.load() {
+            Ok(context) => {
+                let format = args.output.or(context.output).unwrap_or_default();
+                (format, handle_ticket_commands(args, context).await)
+            }
+            Err(e) => (args.output.unwrap_or_default(), Err(e)),
         },
+        RootCommand::Mirror(args) => (OutputFormat::Text, handle_mirror_commands(args).await),
+        RootCommand::Explain(args) => (args.output, handle_explain_command(args).await),
+        RootCommand::Config(args) => (OutputFormat::Text, config::handle_config_command(args)),
+        RootCommand::Migrate(args) => (args.output, migrate::handle_migrate_command(args).await),
+        RootCommand::Whoami(args) => (args.output, whoami::handle_whoami_command(args).await),
+        RootCommand::Guard(args) => (args.output, guard::handle_guard_command(args).await),
+        RootCommand::Policy(args) => (args.output, policy::handle_policy_command(args)),
     };
+
+    if let Err(e) = result {
+        let output = ErrorOutput::new(&e);
+        output.print(format);
+        std::process::exit(output.error.exit_code);
+    }
 }
 
-async fn handle_ticket_commands(args: TicketArgs) -> anyhow::Result<()> {
-    let mut sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-
-    if let Some(profile) = args.profile {
-        let provider = aws_config::profile::credentials::Builder::default()
-            .profile_name(profile)
-            .build();
-        sdk_config = sdk_config
-            .into_builder()
-            .credentials_provider(SharedCredentialsProvider::new(provider))
-            .build();
+pub(crate) async fn load_sdk_config(profile: Option<String>) -> aws_config::SdkConfig {
+    let sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+
+    match profile {
+        Some(profile) => {
+            let provider = aws_config::profile::credentials::Builder::default()
+                .profile_name(profile)
+                .build();
+            sdk_config
+                .into_builder()
+                .credentials_provider(SharedCredentialsProvider::new(provider))
+                .build()
+        }
+        None => sdk_config,
     }
+}
+
+async fn handle_explain_command(args: ExplainArgs) -> anyhow::Result<()> {
+    let context = args.context.load()?;
+    let schema = context.schema()?;
+    let sdk_config = load_sdk_config(context.profile).await;
+    let sts_client = aws_sdk_sts::Client::new(&sdk_config);
+
+    let explanation = explain::explain(&sts_client, schema.current(), &args.input).await?;
+    println!("{}", explanation.render(args.output)?);
+    Ok(())
+}
 
+async fn handle_ticket_commands(args: TicketArgs, context: config::Context) -> anyhow::Result<()> {
+    #[cfg(feature = "signed")]
+    if let TicketCommand::Keygen { path } = &args.command {
+        return generate_signing_key(path);
+    }
+    #[cfg(feature = "signed")]
+    let signing_key = args
+        .signing_key
+        .or(context.signing_key.clone())
+        .as_deref()
+        .map(load_signing_key)
+        .transpose()?;
+
+    let sdk_config = load_sdk_config(context.profile.clone()).await;
+
+    let format = args.output.or(context.output).unwrap_or_default();
+    let identity = args.identity.or(context.identity.clone());
     let iam_client = Arc::new(aws_sdk_iam::Client::new(&sdk_config));
-    let manager = approval::iam::RoleApprovalManager::new(iam_client.clone());
+    let schema = context.schema()?;
+    let manager = approval::iam::RoleApprovalManager::new(iam_client.clone()).with_schema(schema.clone());
 
     let sts_client = aws_sdk_sts::Client::new(&sdk_config);
 
-    let (role_name, session_name) = match args.role_name {
+    let (role_name, session_name) = match args.role_name.or(context.role_name.clone()) {
         Some(name) => (CallerRoleName(name), None),
         None => {
             let (role_name, session) = get_caller(&sts_client).await?;
@@ -120,63 +269,330 @@ async fn handle_ticket_commands(args: TicketArgs) -> anyhow::Result<()> {
         }
     };
 
-    match args.command {
-        TicketCommand::Get {} => match manager.get_ticket(&role_name.0).await {
-            Ok(ticket) => {
-                println!("Ticket: {:#?}", ticket);
-            }
-            Err(e) => {
-                eprintln!("Error: {:#}", e);
-            }
-        },
+    let principal = manager.principal(&role_name.0).await?;
+    let principal_arn = principal.to_string();
+    let output = match args.command {
+        TicketCommand::Get {} => {
+            let ticket = manager.get_ticket(&principal).await?;
+            TicketOutput::new(TicketAction::Get, principal_arn, ticket.as_ref())
+        }
         TicketCommand::Set {
             receiver,
+            force,
+            ttl,
+            scope,
+            reason,
             #[cfg(feature = "chainable")]
             chain,
         } => {
-            let expiry = chrono::Utc::now() + chrono::Duration::hours(1);
-            let giver = match session_name {
-                Some(session) => session,
-                None => get_caller(&sts_client).await?.1,
-            };
+            let ttl = ttl.or(context.ticket_ttl()?).map_or(DEFAULT_TICKET_TTL, Into::into);
+            let expiry = chrono::Utc::now() + chrono::Duration::from_std(ttl)?;
+            let giver = caller_identity(&sts_client, identity, session_name).await?;
 
-            let mut ticket = ApprovalTicket::new(HumanIdentity::new(giver.0), HumanIdentity::new(receiver));
+            let mut ticket = ApprovalTicket::new(giver, HumanIdentity::new(receiver));
 
             ticket.set_expiry(expiry);
 
+            if let Some(scope) = scope.or(context.scope.clone()) {
+                ticket.set_scope(spec_value("scope", scope)?);
+            }
+            match reason {
+                Some(reason) => {
+                    ticket.set_reason(spec_value("reason", reason)?);
+                }
+                None if context.reason == Some(ReasonPolicy::Required) => {
+                    return Err(CliError::new(
+                        ErrorKind::InvalidInput,
+                        "the current context requires a reason, pass --reason",
+                    )
+                    .into());
+                }
+                None => {}
+            }
+
             #[cfg(feature = "chainable")]
             if chain {
                 ticket.set_chainable(true);
             }
 
-            if let Err(e) = manager.set_ticket(&role_name.0, ticket).await {
-                eprintln!("Error: {:#}", e);
+            #[cfg(feature = "signed")]
+            if let Some(key) = &signing_key {
+                ticket.sign(key)?;
             }
+
+            let result = manager
+                .set_ticket(&principal, ticket.clone(), force)
+                .await
+                .map_err(|e| match e {
+                    SetTicketError::Conflict { .. } => anyhow::Error::new(e).context(format!(
+                        "{} already holds a ticket, pass --force to replace it",
+                        role_name.0
+                    )),
+                    e => e.into(),
+                });
+            if let Err(e) = result {
+                if !is_tagging_denied(&e) {
+                    return Err(e);
+                }
+                let explanation = explain_ticket_attempt(&iam_client, &sts_client, schema.current(), ticket).await;
+                return Err(ExplainedError::new(explanation, e).into());
+            }
+            TicketOutput::new(TicketAction::Set, principal_arn, Some(&ticket))
         }
         TicketCommand::Unset {} => {
-            if let Err(e) = manager.unset_ticket(&role_name.0).await {
-                eprintln!("Error: {:#}", e);
+            manager.unset_ticket(&principal).await?;
+            TicketOutput::new(TicketAction::Unset, principal_arn, None)
+        }
+        TicketCommand::Extend { by } => {
+            let giver = caller_identity(&sts_client, identity, session_name).await?;
+
+            let mut ticket = manager
+                .get_ticket(&principal)
+                .await?
+                .ok_or_else(|| CliError::new(ErrorKind::NotFound, format!("no ticket found on {}", role_name.0)))?;
+
+            if ticket.giver != giver {
+                return Err(CliError::new(
+                    ErrorKind::AccessDenied,
+                    format!(
+                        "only the original giver ({}) may extend the ticket, caller is {}",
+                        ticket.giver, giver
+                    ),
+                )
+                .into());
+            }
+
+            let by = chrono::Duration::from_std(by.into())?;
+            // the bound is the deployment's, so it is read from the context and cannot be raised per invocation
+            let max_ttl = context.max_ticket_ttl()?.map_or(DEFAULT_MAX_TICKET_TTL, Into::into);
+            let max_ttl = chrono::Duration::from_std(max_ttl)?;
+            ticket.extend(by, max_ttl)?;
+
+            #[cfg(feature = "signed")]
+            match &signing_key {
+                Some(key) => ticket.sign(key)?,
+                None if ticket.is_signed() => {
+                    return Err(CliError::new(
+                        ErrorKind::InvalidInput,
+                        "the ticket is signed, pass --signing-key to sign the extended ticket",
+                    )
+                    .into())
+                }
+                None => {}
             }
+
+            manager.set_ticket(&principal, ticket.clone(), false).await?;
+            TicketOutput::new(TicketAction::Extend, principal_arn, Some(&ticket))
         }
-    }
+        TicketCommand::Wait {
+            timeout,
+            interval,
+            command,
+        } => {
+            let receiver = caller_identity(&sts_client, identity, session_name).await?;
+
+            eprintln!("Waiting for a ticket for {} on {}...", receiver, role_name.0);
+            // a ticket under an older schema version is not honored by the SCPs, so it is no approval
+            let manager = manager.with_schema(schema.current_only());
+            let wait = wait_for_ticket(&manager, &principal, &receiver, interval.into());
+            let ticket = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout.into(), wait).await.map_err(|_| {
+                    CliError::new(
+                        ErrorKind::Timeout,
+                        format!("timed out after {} waiting for approval", timeout),
+                    )
+                })??,
+                None => wait.await?,
+            };
+
+            let output = TicketOutput::new(TicketAction::Wait, principal_arn, Some(&ticket));
+            if let [program, args @ ..] = &command[..] {
+                println!("{}", output.render(format)?);
+                let status = tokio::process::Command::new(program)
+                    .args(args)
+                    .status()
+                    .await
+                    .with_context(|| format!("failed to run {}", program))?;
+                if !status.success() {
+                    std::process::exit(status.code().unwrap_or(1));
+                }
+                return Ok(());
+            }
+            output
+        }
+        #[cfg(feature = "chainable")]
+        TicketCommand::Derive {
+            receiver,
+            parent_role_name,
+            ttl,
+            force,
+        } => {
+            let (caller_role, caller_session) = get_caller(&sts_client).await?;
+            let caller = caller_identity(&sts_client, identity, Some(caller_session)).await?;
+            let parent_role_name = parent_role_name.unwrap_or(caller_role.0);
+            if parent_role_name == role_name.0 {
+                return Err(CliError::new(
+                    ErrorKind::InvalidInput,
+                    "the derived ticket must target another role, pass --role-name",
+                )
+                .into());
+            }
+
+            let parent_principal = manager.principal(&parent_role_name).await?;
+            let parent = manager.get_ticket(&parent_principal).await?.ok_or_else(|| {
+                CliError::new(ErrorKind::NotFound, format!("no ticket found on {}", parent_role_name))
+            })?;
+            if parent.receiver != caller {
+                return Err(CliError::new(
+                    ErrorKind::AccessDenied,
+                    format!(
+                        "only the receiver ({}) of the parent ticket may derive from it, caller is {}",
+                        parent.receiver, caller
+                    ),
+                )
+                .into());
+            }
+
+            let now = chrono::Utc::now();
+            let expiry = match ttl {
+                Some(ttl) => now + chrono::Duration::from_std(ttl.into())?,
+                None => {
+                    let ttl = context.ticket_ttl()?.map_or(DEFAULT_TICKET_TTL, Into::into);
+                    let default = now + chrono::Duration::from_std(ttl)?;
+                    parent.expires_at().map_or(default, |exp| min(exp, default))
+                }
+            };
+            #[allow(unused_mut)]
+            let mut ticket = parent.derive(HumanIdentity::new(receiver), expiry)?;
+
+            #[cfg(feature = "signed")]
+            if let Some(key) = &signing_key {
+                ticket.sign(key)?;
+            }
+
+            manager
+                .set_ticket(&principal, ticket.clone(), force)
+                .await
+                .map_err(|e| match e {
+                    SetTicketError::Conflict { .. } => anyhow::Error::new(e).context(format!(
+                        "{} already holds a ticket, pass --force to replace it",
+                        role_name.0
+                    )),
+                    e => e.into(),
+                })?;
+            TicketOutput::new(TicketAction::Derive, principal_arn, Some(&ticket))
+        }
+        #[cfg(feature = "signed")]
+        TicketCommand::Verify { trusted_keys } => {
+            let ticket = manager
+                .get_ticket(&principal)
+                .await?
+                .ok_or_else(|| CliError::new(ErrorKind::NotFound, format!("no ticket found on {}", role_name.0)))?;
+            trusted_keys
+                .verify(&ticket)
+                .with_context(|| format!("verifying the ticket on {}", role_name.0))?;
+            TicketOutput::new(TicketAction::Verify, principal_arn, Some(&ticket))
+        }
+        #[cfg(feature = "signed")]
+        TicketCommand::Keygen { .. } => unreachable!("handled before loading the sdk config"),
+    };
+
+    println!("{}", output.render(format)?);
     Ok(())
 }
 
+#[cfg(feature = "signed")]
+fn load_signing_key(path: &std::path::Path) -> anyhow::Result<approval::signature::SigningKey> {
+    let encoded = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    approval::signature::parse_signing_key(&encoded)
+        .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("{}: {}", path.display(), e)).into())
+}
+
+/// writes a fresh signing key readable by the owner only, and prints its public key
+#[cfg(feature = "signed")]
+fn generate_signing_key(path: &std::path::Path) -> anyhow::Result<()> {
+    use approval::signature::{encode_key, generate_signing_key};
+    use std::io::Write;
+
+    let key = generate_signing_key();
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("creating {}", path.display()))?;
+    writeln!(file, "{}", encode_key(key.as_bytes()))?;
+
+    println!("{}", encode_key(key.verifying_key().as_bytes()));
+    Ok(())
+}
+
+/// the human identity of the caller: the configured identity, or else the caller's session name
+async fn caller_identity(
+    sts: &aws_sdk_sts::Client,
+    identity: Option<String>,
+    session_name: Option<CallerSessionName>,
+) -> anyhow::Result<HumanIdentity> {
+    if let Some(identity) = identity {
+        return Ok(HumanIdentity::new(identity));
+    }
+    let session_name = match session_name {
+        Some(session_name) => session_name,
+        None => get_caller(sts).await?.1,
+    };
+    Ok(HumanIdentity::new(session_name.0))
+}
+
+/// checks that a value given on the command line can be stored in the spec of a ticket
+fn spec_value(name: &str, value: String) -> anyhow::Result<String> {
+    let valid = value
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || "_.:+-@".contains(c));
+    if !valid {
+        return Err(CliError::new(
+            ErrorKind::InvalidInput,
+            format!("the {} may only hold letters, digits, spaces and _.:+-@", name),
+        )
+        .into());
+    }
+    Ok(value)
+}
+
+/// polls the principal until it holds an unexpired ticket for the receiver
+async fn wait_for_ticket(
+    manager: &impl ApprovalManager,
+    principal: &IamPrincipal,
+    receiver: &HumanIdentity,
+    interval: std::time::Duration,
+) -> anyhow::Result<ApprovalTicket> {
+    loop {
+        if let Some(ticket) = manager.get_ticket(principal).await? {
+            // tickets without an expiry are evicted by the retention lambda, so they are not an approval
+            let unexpired = ticket.expires_at().is_some_and(|exp| exp > chrono::Utc::now());
+            if &ticket.receiver == receiver && unexpired {
+                return Ok(ticket);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn parse_interval(s: &str) -> Result<humantime::Duration, String> {
+    s.parse::<humantime::Duration>()
+        .map_err(|e| e.to_string())
+        .and_then(|interval| match interval.is_zero() {
+            true => Err("the interval must be positive".to_string()),
+            false => Ok(interval),
+        })
+}
+
 const SSO_ROLE_PATH_PREFIX: &str = "/aws-reserved/sso.amazonaws.com/";
-const MIRROR_ROLE_NAME_PREFIX: &str = "tagctl-mirror-";
+pub(crate) const MIRROR_ROLE_NAME_PREFIX: &str = "tagctl-mirror-";
 
 async fn handle_mirror_commands(args: MirrorArgs) -> anyhow::Result<()> {
-    let mut sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-
-    if let Some(profile) = args.profile {
-        let provider = aws_config::profile::credentials::Builder::default()
-            .profile_name(profile)
-            .build();
-        sdk_config = sdk_config
-            .into_builder()
-            .credentials_provider(SharedCredentialsProvider::new(provider))
-            .build();
-    }
+    let context = args.context.load()?;
+    let sdk_config = load_sdk_config(context.profile).await;
 
     match args.command {
         MirrorCommand::Assume {} => {
@@ -188,22 +604,15 @@ async fn handle_mirror_commands(args: MirrorArgs) -> anyhow::Result<()> {
                 .get_role()
                 .role_name(role_name.0)
                 .send()
-                .await?
+                .await
+                .map_err(ApiError::from_sdk)?
                 .role
                 .context("missing role")?;
 
-            if !current_role.path().starts_with(SSO_ROLE_PATH_PREFIX) {
-                bail!("current role is not an SSO role, cannot assume mirror role");
-            };
-
-            let sso_role_name_crumbs: Vec<_> = current_role.role_name.split("_").collect();
-            let ["AWSReservedSSO", permissionset_name, _] = sso_role_name_crumbs[..] else {
-                bail!("role name does not match expected format for SSO role: AWSReservedSSO_<PERMSET>_<UID>");
-            };
-
+            let mirror_role_name = mirror_role_name(&current_role)?;
             let mirror_role = iam_client
                 .get_role()
-                .role_name(format!("{}{}", MIRROR_ROLE_NAME_PREFIX, permissionset_name))
+                .role_name(mirror_role_name)
                 .send()
                 .await?
                 .role
@@ -233,6 +642,68 @@ async fn handle_mirror_commands(args: MirrorArgs) -> anyhow::Result<()> {
     }
 }
 
+/// the name of the mirror role of an SSO role, `tagctl-mirror-<permission set>`
+pub(crate) fn mirror_role_name(role: &aws_sdk_iam::types::Role) -> Result<String, CliError> {
+    if !role.path().starts_with(SSO_ROLE_PATH_PREFIX) {
+        return Err(CliError::new(
+            ErrorKind::InvalidInput,
+            "current role is not an SSO role, cannot assume mirror role",
+        ));
+    };
+
+    let sso_role_name_crumbs: Vec<_> = role.role_name.split("_").collect();
+    let ["AWSReservedSSO", permissionset_name, _] = sso_role_name_crumbs[..] else {
+        return Err(CliError::new(
+            ErrorKind::InvalidInput,
+            "role name does not match expected format for SSO role: AWSReservedSSO_<PERMSET>_<UID>",
+        ));
+    };
+    Ok(format!("{}{}", MIRROR_ROLE_NAME_PREFIX, permissionset_name))
+}
+
+/// whether setting a ticket failed because the tagging call itself was denied.
+/// a denied read of the existing ticket, made before the write, is not explained by the tagging SCPs.
+fn is_tagging_denied(e: &anyhow::Error) -> bool {
+    let denied = ApiError::find(e).is_some_and(|e| e.kind == ApiErrorKind::AccessDenied);
+    denied && !e.chain().any(|cause| cause.is::<ListTicketsError>())
+}
+
+/// explains why setting `ticket` was denied, from what the caller can find out about itself
+async fn explain_ticket_attempt(
+    iam: &aws_sdk_iam::Client,
+    sts: &aws_sdk_sts::Client,
+    namespace: &Namespace,
+    ticket: ApprovalTicket,
+) -> Explanation {
+    let mut attempt = TaggingAttempt::set_ticket(namespace, ticket);
+    let mut assumptions = vec![];
+    let Ok((role_name, session_name)) = get_caller(sts).await else {
+        return Explanation::from_attempt(&attempt);
+    };
+
+    // the source identity of a session cannot be read back, but `tagctl mirror assume` sets it to the session name
+    if role_name.0.starts_with(MIRROR_ROLE_NAME_PREFIX) {
+        assumptions.push(format!(
+            "the source identity is the session name `{}`, as set by `tagctl mirror assume`",
+            session_name.0
+        ));
+        attempt.source_identity = Some(Some(session_name.0));
+    }
+
+    if let Ok(output) = iam.list_role_tags().role_name(&role_name.0).send().await {
+        attempt.grant_area = Some(
+            output
+                .tags
+                .into_iter()
+                .find(|tag| tag.key == namespace.grant_area_key())
+                .map(|tag| tag.value),
+        );
+    }
+    assumptions
+        .into_iter()
+        .fold(Explanation::from_attempt(&attempt), Explanation::assuming)
+}
+
 struct CallerRoleName(String);
 struct CallerSessionName(String);
 
@@ -252,5 +723,5 @@ async fn get_caller(sts: &aws_sdk_sts::Client) -> anyhow::Result<(CallerRoleName
         )),
         _ => None,
     }
-    .context("unsupported caller identity")
+    .ok_or_else(|| CliError::new(ErrorKind::InvalidInput, "unsupported caller identity").into())
 }
@@ -0,0 +1,726 @@
+use crate::{
+    config::ContextArgs,
+    error::{CliError, ErrorKind},
+    load_sdk_config,
+    output::{render_rows, OutputFormat},
+};
+use anyhow::Context;
+use approval::{
+    error::ApiError,
+    org::traverse_accounts_affected_by_policy,
+    principal::{IamPrincipal, PrincipalKind},
+    tags::Namespace,
+};
+use aws_config::sts::AssumeRoleProviderBuilder;
+use aws_sdk_iam::{config::SharedCredentialsProvider, types::Tag};
+use aws_smithy_types_convert::stream::PaginationStreamExt;
+use clap::{Args, Subcommand};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+/// the schema identifier of migration documents and rollback files.
+/// bump the version on any breaking change to [`MigrationOutput`].
+pub(crate) const MIGRATION_SCHEMA: &str = "tagctl/migration/v2";
+
+/// the role AWS Organizations creates in member accounts
+const DEFAULT_ASSUME_ROLE_NAME: &str = "OrganizationAccountAccessRole";
+const SESSION_NAME: &str = "tagctl-migrate";
+
+#[derive(Args)]
+#[command(about)]
+pub(crate) struct MigrateArgs {
+    #[command(flatten)]
+    pub(crate) context: ContextArgs,
+
+    /// the output format
+    #[arg(long, short, global = true, value_enum, default_value_t)]
+    pub(crate) output: OutputFormat,
+
+    #[command(subcommand)]
+    command: MigrateCommand,
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// inventories the control tags of the accounts, and prints how they would be rewritten
+    Plan {
+        #[command(flatten)]
+        versions: VersionArgs,
+        #[command(flatten)]
+        accounts: AccountArgs,
+    },
+    /// rewrites the control tags of the accounts. only prints the rewrites unless --execute is passed.
+    Apply {
+        #[command(flatten)]
+        versions: VersionArgs,
+        #[command(flatten)]
+        accounts: AccountArgs,
+        /// perform the rewrites, instead of a dry run
+        #[arg(long, default_value_t = false)]
+        execute: bool,
+        /// where to record how to undo the rewrites. Default to `tagctl-migrate-<from>-<to>-<timestamp>.json`.
+        #[arg(long)]
+        rollback_file: Option<PathBuf>,
+    },
+    /// undoes the rewrites recorded in a rollback file. only prints the rewrites unless --execute is passed.
+    Rollback {
+        /// the rollback file written by `tagctl migrate apply`
+        path: PathBuf,
+        #[command(flatten)]
+        access: AccessArgs,
+        /// perform the rewrites, instead of a dry run
+        #[arg(long, default_value_t = false)]
+        execute: bool,
+    },
+}
+
+#[derive(Args)]
+struct VersionArgs {
+    /// the schema version to migrate from, e.g. `v1`
+    #[arg(long, value_parser = parse_version)]
+    from: u32,
+    /// the schema version to migrate to, e.g. `v2`
+    #[arg(long, value_parser = parse_version)]
+    to: u32,
+}
+
+#[derive(Args)]
+struct AccountArgs {
+    /// the id of the control-tags SCP. the accounts it applies to are migrated, as found by the retention lambda.
+    #[arg(long, env = "TAGCTL_CONTROL_TAGS_SCP_ID", required_unless_present = "accounts")]
+    scp_id: Option<String>,
+    /// an account to migrate, instead of the accounts the SCP applies to. may be repeated.
+    #[arg(long = "account", conflicts_with = "scp_id")]
+    accounts: Vec<String>,
+    #[command(flatten)]
+    access: AccessArgs,
+}
+
+#[derive(Args)]
+struct AccessArgs {
+    /// the role to assume in each account. it needs a grant area covering both schema versions.
+    #[arg(long, default_value = DEFAULT_ASSUME_ROLE_NAME)]
+    assume_role_name: String,
+    /// the path of the role to assume in each account
+    #[arg(long, default_value = "/")]
+    assume_role_path: String,
+}
+
+impl AccessArgs {
+    fn role_arn(&self, account_id: &str) -> String {
+        let path = match self.assume_role_path.trim_matches('/') {
+            "" => "/".to_string(),
+            path => format!("/{}/", path),
+        };
+        format!("arn:aws:iam::{}:role{}{}", account_id, path, self.assume_role_name)
+    }
+}
+
+fn parse_version(s: &str) -> Result<u32, String> {
+    s.strip_prefix('v')
+        .unwrap_or(s)
+        .parse()
+        .ok()
+        .filter(|&version| version > 0)
+        .ok_or_else(|| format!("expected a schema version like `v2`, got {:?}", s))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct TagPair {
+    pub(crate) key: String,
+    pub(crate) value: String,
+}
+
+impl Display for TagPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+/// a control tag left alone, as its rewritten key already holds another value
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct Conflict {
+    pub(crate) source: TagPair,
+    pub(crate) existing: TagPair,
+}
+
+/// the tags written to, then removed from, a principal
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct Rewrite {
+    /// the rewritten role or user, as its ARN
+    pub(crate) principal: IamPrincipal,
+    pub(crate) tag: Vec<TagPair>,
+    pub(crate) untag: Vec<TagPair>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) conflicts: Vec<Conflict>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+impl Rewrite {
+    fn is_noop(&self) -> bool {
+        self.tag.is_empty() && self.untag.is_empty() && self.conflicts.is_empty()
+    }
+
+    /// the rewrite undoing the steps of this one which were applied
+    fn inverse(&self, tagged: bool, untagged: bool) -> Self {
+        Self {
+            principal: self.principal.clone(),
+            tag: if untagged { self.untag.clone() } else { vec![] },
+            untag: if tagged { self.tag.clone() } else { vec![] },
+            conflicts: vec![],
+            error: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct AccountFailure {
+    pub(crate) account_id: String,
+    pub(crate) error: String,
+}
+
+/// the document emitted by migrate commands, and the format of rollback files
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct MigrationOutput {
+    pub(crate) schema: String,
+    pub(crate) from: String,
+    pub(crate) to: String,
+    pub(crate) dry_run: bool,
+    pub(crate) rewrites: Vec<Rewrite>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) failed_accounts: Vec<AccountFailure>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rollback_file: Option<PathBuf>,
+}
+
+impl MigrationOutput {
+    fn new(from: impl Display, to: impl Display, dry_run: bool) -> Self {
+        Self {
+            schema: MIGRATION_SCHEMA.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            dry_run,
+            rewrites: vec![],
+            failed_accounts: vec![],
+            rollback_file: None,
+        }
+    }
+
+    fn failures(&self) -> usize {
+        self.failed_accounts.len() + self.rewrites.iter().filter(|r| r.error.is_some()).count()
+    }
+
+    pub(crate) fn render(&self, format: OutputFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Yaml => serde_yaml::to_string(self)?.trim_end().to_string(),
+            OutputFormat::Table => self.render_table(),
+            OutputFormat::Text => self.render_text(),
+        })
+    }
+
+    fn render_text(&self) -> String {
+        let mut lines = vec![];
+        for rewrite in &self.rewrites {
+            lines.push(rewrite.principal.to_string());
+            lines.extend(rewrite.tag.iter().map(|tag| format!("  + {}", tag)));
+            lines.extend(rewrite.untag.iter().map(|tag| format!("  - {}", tag)));
+            lines.extend(
+                rewrite
+                    .conflicts
+                    .iter()
+                    .map(|c| format!("  ! {} kept, {} already set", c.source, c.existing)),
+            );
+            if let Some(error) = &rewrite.error {
+                lines.push(format!("  error: {}", error));
+            }
+        }
+        for failure in &self.failed_accounts {
+            lines.push(format!("{} error: {}", failure.account_id, failure.error));
+        }
+
+        let changed = self
+            .rewrites
+            .iter()
+            .filter(|r| !r.tag.is_empty() || !r.untag.is_empty());
+        let summary = format!(
+            "{} principals to rewrite from {} to {}",
+            changed.count(),
+            self.from,
+            self.to
+        );
+        lines.push(match (self.dry_run, &self.rollback_file) {
+            (true, _) => format!("Dry run: {}, pass --execute to apply", summary),
+            (false, Some(path)) => format!("Rewrote {}, rollback written to {}", summary, path.display()),
+            (false, None) => format!("Rewrote {}", summary),
+        });
+        lines.join("\n")
+    }
+
+    fn render_table(&self) -> String {
+        let mut rows = vec![["PRINCIPAL", "CHANGE", "TAG"].map(String::from)];
+        for rewrite in &self.rewrites {
+            let principal = rewrite.principal.to_string();
+            let changes = (rewrite.tag.iter().map(|tag| ("tag", tag.to_string())))
+                .chain(rewrite.untag.iter().map(|tag| ("untag", tag.to_string())))
+                .chain(rewrite.conflicts.iter().map(|c| ("conflict", c.source.to_string())));
+            for (change, tag) in changes {
+                rows.push([principal.clone(), change.to_string(), tag]);
+            }
+        }
+        render_rows(&rows)
+    }
+}
+
+/// rewrites a control tag value naming the `from` namespace, e.g. a grant area, to name the `to` namespace
+fn rewrite_value(from: &Namespace, to: &Namespace, value: &str) -> String {
+    let prefix = from.to_string();
+    match value.strip_prefix(&prefix) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", to, rest),
+        _ => value.to_string(),
+    }
+}
+
+/// plans the rewrite of the control tags of the `from` namespace among `tags` to the `to` namespace.
+/// rewrites already in place only remove the source tag, so planning again after a partial apply resumes it.
+fn plan_rewrite(from: &Namespace, to: &Namespace, tags: &[TagPair]) -> (Vec<TagPair>, Vec<TagPair>, Vec<Conflict>) {
+    let prefix = format!("{}/", from);
+    let (mut tag, mut untag, mut conflicts) = (vec![], vec![], vec![]);
+    for source in tags {
+        let Some(rest) = source.key.strip_prefix(&prefix) else {
+            continue;
+        };
+        let target = TagPair {
+            key: format!("{}/{}", to, rest),
+            value: rewrite_value(from, to, &source.value),
+        };
+        match tags.iter().find(|t| t.key == target.key) {
+            None => {
+                tag.push(target);
+                untag.push(source.clone());
+            }
+            Some(existing) if existing.value == target.value => untag.push(source.clone()),
+            Some(existing) => conflicts.push(Conflict {
+                source: source.clone(),
+                existing: existing.clone(),
+            }),
+        }
+    }
+    (tag, untag, conflicts)
+}
+
+pub(crate) async fn handle_migrate_command(args: MigrateArgs) -> anyhow::Result<()> {
+    let context = args.context.load()?;
+    let schema = context.schema()?;
+    let sdk_config = load_sdk_config(context.profile).await;
+
+    let (mut output, access, execute, rollback_file) = match args.command {
+        MigrateCommand::Plan { versions, accounts } => {
+            let (from, to) = versions.namespaces(schema.current())?;
+            let output = plan(&sdk_config, &accounts, &from, &to).await?;
+            (output, accounts.access, false, None)
+        }
+        MigrateCommand::Apply {
+            versions,
+            accounts,
+            execute,
+            rollback_file,
+        } => {
+            let (from, to) = versions.namespaces(schema.current())?;
+            let rollback_file = rollback_file.unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "tagctl-migrate-v{}-v{}-{}.json",
+                    from.version(),
+                    to.version(),
+                    chrono::Utc::now().timestamp()
+                ))
+            });
+            if execute && rollback_file.exists() {
+                return Err(CliError::new(
+                    ErrorKind::Conflict,
+                    format!("rollback file {} already exists", rollback_file.display()),
+                )
+                .into());
+            }
+            let output = plan(&sdk_config, &accounts, &from, &to).await?;
+            (output, accounts.access, execute, Some(rollback_file))
+        }
+        MigrateCommand::Rollback { path, access, execute } => {
+            let file = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+            let mut output: MigrationOutput =
+                serde_json::from_str(&file).with_context(|| format!("parsing {}", path.display()))?;
+            if output.schema != MIGRATION_SCHEMA {
+                return Err(CliError::new(
+                    ErrorKind::InvalidInput,
+                    format!("unsupported rollback file schema {:?}", output.schema),
+                )
+                .into());
+            }
+            output.failed_accounts.clear();
+            output.rewrites.retain(|rewrite| !rewrite.is_noop());
+            (output, access, execute, None)
+        }
+    };
+
+    output.dry_run = !execute;
+    if execute {
+        apply(&sdk_config, &access, &mut output, rollback_file.as_deref()).await?;
+        output.rollback_file = rollback_file;
+    }
+
+    println!("{}", output.render(args.output)?);
+    match output.failures() {
+        0 => Ok(()),
+        failures => Err(anyhow::anyhow!(
+            "{} accounts or principals could not be migrated",
+            failures
+        )),
+    }
+}
+
+impl VersionArgs {
+    fn namespaces(&self, current: &Namespace) -> anyhow::Result<(Namespace, Namespace)> {
+        if self.from == self.to {
+            return Err(CliError::new(ErrorKind::InvalidInput, "--from and --to must differ").into());
+        }
+        Ok((current.with_version(self.from)?, current.with_version(self.to)?))
+    }
+}
+
+/// inventories the control tags of the `from` namespace in every account, and plans their rewrites
+async fn plan(
+    sdk_config: &aws_config::SdkConfig,
+    args: &AccountArgs,
+    from: &Namespace,
+    to: &Namespace,
+) -> anyhow::Result<MigrationOutput> {
+    let accounts = match &args.scp_id {
+        Some(scp_id) => {
+            let orgs = aws_sdk_organizations::Client::new(sdk_config);
+            traverse_accounts_affected_by_policy(&orgs, scp_id)
+                .try_collect::<Vec<_>>()
+                .await
+                .context("listing the accounts the SCP applies to")?
+        }
+        None => args.accounts.clone(),
+    };
+
+    let mut output = MigrationOutput::new(from, to, true);
+    for account_id in accounts {
+        let iam = account_iam_client(sdk_config, &args.access, &account_id).await;
+        match plan_account(&iam, from, to).await {
+            Ok(rewrites) => output.rewrites.extend(rewrites),
+            Err(e) => output.failed_accounts.push(AccountFailure {
+                account_id,
+                error: format!("{:#}", e),
+            }),
+        }
+    }
+    Ok(output)
+}
+
+async fn plan_account(iam: &aws_sdk_iam::Client, from: &Namespace, to: &Namespace) -> anyhow::Result<Vec<Rewrite>> {
+    let principals = list_principals(iam).await?;
+    stream::iter(principals)
+        .map(|principal| async move {
+            let tags = list_tags(iam, &principal).await?;
+            let (tag, untag, conflicts) = plan_rewrite(from, to, &tags);
+            Ok(Rewrite {
+                principal,
+                tag,
+                untag,
+                conflicts,
+                error: None,
+            })
+        })
+        .buffered(4)
+        .try_filter(|rewrite: &Rewrite| futures::future::ready(!rewrite.is_noop()))
+        .try_collect()
+        .await
+}
+
+/// applies the planned rewrites, tagging before untagging so a principal never lacks its grant area.
+/// the rewrites undoing what was applied are written to `rollback_file` before the first rewrite and after each one,
+/// so an interrupted migration can be rolled back. applying stops if the rollback file cannot be written.
+async fn apply(
+    sdk_config: &aws_config::SdkConfig,
+    access: &AccessArgs,
+    output: &mut MigrationOutput,
+    rollback_file: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut rollback = MigrationOutput::new(&output.to, &output.from, false);
+    let record = |rollback: &MigrationOutput| match rollback_file {
+        Some(path) => write_rollback(path, rollback),
+        None => Ok(()),
+    };
+    record(&rollback)?;
+
+    let mut clients: Vec<(String, aws_sdk_iam::Client)> = vec![];
+    for rewrite in output.rewrites.iter_mut() {
+        let account_id = &rewrite.principal.account_id;
+        let iam = match clients.iter().find(|(id, _)| id == account_id) {
+            Some((_, iam)) => iam.clone(),
+            None => {
+                let iam = account_iam_client(sdk_config, access, account_id).await;
+                clients.push((account_id.clone(), iam.clone()));
+                iam
+            }
+        };
+
+        let mut tagged = false;
+        let mut untagged = false;
+        let mut result = Ok(());
+        if !rewrite.tag.is_empty() {
+            result = tag(&iam, &rewrite.principal, &rewrite.tag).await;
+            tagged = result.is_ok();
+        }
+        if result.is_ok() && !rewrite.untag.is_empty() {
+            result = untag(&iam, &rewrite.principal, &rewrite.untag).await;
+            untagged = result.is_ok();
+        }
+        if let Err(e) = result {
+            rewrite.error = Some(format!("{:#}", e));
+        }
+        if tagged || untagged {
+            rollback.rewrites.push(rewrite.inverse(tagged, untagged));
+            record(&rollback)?;
+        }
+    }
+    Ok(())
+}
+
+/// replaces the rollback file, through a temporary file so it is never left half written
+fn write_rollback(path: &Path, rollback: &MigrationOutput) -> anyhow::Result<()> {
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, serde_json::to_string_pretty(rollback)?)
+        .and_then(|()| std::fs::rename(&partial, path))
+        .with_context(|| format!("writing rollback file {}", path.display()))
+}
+
+async fn account_iam_client(
+    sdk_config: &aws_config::SdkConfig,
+    access: &AccessArgs,
+    account_id: &str,
+) -> aws_sdk_iam::Client {
+    let provider = AssumeRoleProviderBuilder::new(access.role_arn(account_id))
+        .session_name(SESSION_NAME)
+        .configure(sdk_config)
+        .build()
+        .await;
+    let config = sdk_config
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(provider))
+        .build();
+    aws_sdk_iam::Client::new(&config)
+}
+
+/// the roles and users of the account, with their path and account as listed by IAM
+async fn list_principals(iam: &aws_sdk_iam::Client) -> anyhow::Result<Vec<IamPrincipal>> {
+    let roles = iam
+        .list_roles()
+        .into_paginator()
+        .items()
+        .send()
+        .into_stream_03x()
+        .map_ok(|role| role.arn)
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(ApiError::from_sdk)?;
+    let users = iam
+        .list_users()
+        .into_paginator()
+        .items()
+        .send()
+        .into_stream_03x()
+        .map_ok(|user| user.arn)
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(ApiError::from_sdk)?;
+    roles
+        .into_iter()
+        .chain(users)
+        .map(|arn| Ok(arn.parse::<IamPrincipal>()?))
+        .collect()
+}
+
+pub(crate) async fn list_tags(iam: &aws_sdk_iam::Client, principal: &IamPrincipal) -> anyhow::Result<Vec<TagPair>> {
+    let name = &principal.name;
+    let tags = match principal.kind {
+        PrincipalKind::Role => {
+            iam.list_role_tags()
+                .role_name(name)
+                .send()
+                .await
+                .map_err(ApiError::from_sdk)?
+                .tags
+        }
+        PrincipalKind::User => {
+            iam.list_user_tags()
+                .user_name(name)
+                .send()
+                .await
+                .map_err(ApiError::from_sdk)?
+                .tags
+        }
+    };
+    Ok(tags
+        .into_iter()
+        .map(|tag| TagPair {
+            key: tag.key,
+            value: tag.value,
+        })
+        .collect())
+}
+
+async fn tag(iam: &aws_sdk_iam::Client, principal: &IamPrincipal, tags: &[TagPair]) -> anyhow::Result<()> {
+    let name = &principal.name;
+    let tags = tags
+        .iter()
+        .map(|tag| Tag::builder().key(&tag.key).value(&tag.value).build())
+        .collect::<Result<Vec<_>, _>>()?;
+    match principal.kind {
+        PrincipalKind::Role => {
+            iam.tag_role()
+                .role_name(name)
+                .set_tags(Some(tags))
+                .send()
+                .await
+                .map_err(ApiError::from_sdk)?;
+        }
+        PrincipalKind::User => {
+            iam.tag_user()
+                .user_name(name)
+                .set_tags(Some(tags))
+                .send()
+                .await
+                .map_err(ApiError::from_sdk)?;
+        }
+    }
+    Ok(())
+}
+
+async fn untag(iam: &aws_sdk_iam::Client, principal: &IamPrincipal, tags: &[TagPair]) -> anyhow::Result<()> {
+    let name = &principal.name;
+    let keys = tags.iter().map(|tag| tag.key.clone()).collect();
+    match principal.kind {
+        PrincipalKind::Role => {
+            iam.untag_role()
+                .role_name(name)
+                .set_tag_keys(Some(keys))
+                .send()
+                .await
+                .map_err(ApiError::from_sdk)?;
+        }
+        PrincipalKind::User => {
+            iam.untag_user()
+                .user_name(name)
+                .set_tag_keys(Some(keys))
+                .send()
+                .await
+                .map_err(ApiError::from_sdk)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_version, plan_rewrite, rewrite_value, MigrationOutput, Rewrite, TagPair};
+    use crate::output::OutputFormat;
+    use approval::{principal::IamPrincipal, tags::Namespace};
+
+    fn pair(key: &str, value: &str) -> TagPair {
+        TagPair {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_plan_rewrite() {
+        let from = Namespace::default();
+        let to = from.with_version(2).unwrap();
+        assert_eq!(rewrite_value(&from, &to, "tagctl:v1/team"), "tagctl:v2/team");
+        assert_eq!(rewrite_value(&from, &to, "tagctl:v10/team"), "tagctl:v10/team");
+
+        let tags = [
+            pair("tagctl:v1/meta/grant_area", "tagctl:v1/team"),
+            pair("tagctl:v1/admin/mpa/ticket", "by/alice/exp=1618033988/for/bob"),
+            pair("tagctl:v1/admin/mpa/seal/kind", "total"),
+            pair("tagctl:v2/admin/mpa/seal/kind", "total"),
+            pair("tagctl:v1/meta/id_broker", "true"),
+            pair("tagctl:v2/meta/id_broker", "false"),
+            pair("owner", "tagctl:v1/team"),
+        ];
+        let (tag, untag, conflicts) = plan_rewrite(&from, &to, &tags);
+        assert_eq!(
+            tag,
+            vec![
+                pair("tagctl:v2/meta/grant_area", "tagctl:v2/team"),
+                pair("tagctl:v2/admin/mpa/ticket", "by/alice/exp=1618033988/for/bob"),
+            ]
+        );
+        assert_eq!(untag, vec![tags[0].clone(), tags[1].clone(), tags[2].clone()]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].source, tags[4]);
+
+        // once applied, planning again finds nothing left to do
+        let applied = [tag, vec![tags[3].clone(), tags[4].clone(), tags[5].clone()]].concat();
+        let (tag, untag, _) = plan_rewrite(&from, &to, &applied);
+        assert!(tag.is_empty() && untag.is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_inverse() {
+        let rewrite = Rewrite {
+            principal: "arn:aws:iam::123456789012:role/team/tagctl-mirror-admin"
+                .parse()
+                .unwrap(),
+            tag: vec![pair("tagctl:v2/meta/grant_area", "tagctl:v2/team")],
+            untag: vec![pair("tagctl:v1/meta/grant_area", "tagctl:v1/team")],
+            conflicts: vec![],
+            error: None,
+        };
+        let inverse = rewrite.inverse(true, true);
+        assert_eq!((&inverse.tag, &inverse.untag), (&rewrite.untag, &rewrite.tag));
+        assert_eq!(inverse.principal, rewrite.principal);
+
+        let inverse = rewrite.inverse(true, false);
+        assert!(inverse.tag.is_empty());
+        assert_eq!(inverse.untag, rewrite.tag);
+
+        assert_eq!(parse_version("v2"), Ok(2));
+        assert_eq!(parse_version("3"), Ok(3));
+        assert!(parse_version("v0").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_names_principal_by_arn() {
+        let principal: IamPrincipal = "arn:aws-us-gov:iam::123456789012:user/ci/deployer".parse().unwrap();
+        let mut output = MigrationOutput::new("tagctl:v1", "tagctl:v2", true);
+        output.rewrites.push(Rewrite {
+            principal: principal.clone(),
+            tag: vec![pair("tagctl:v2/meta/grant_area", "tagctl:v2/team")],
+            untag: vec![],
+            conflicts: vec![],
+            error: None,
+        });
+        let json = output.render(OutputFormat::Json).unwrap();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            document["rewrites"][0]["principal"],
+            "arn:aws-us-gov:iam::123456789012:user/ci/deployer"
+        );
+        let parsed: MigrationOutput = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.rewrites[0].principal, principal);
+        assert!(output
+            .render(OutputFormat::Text)
+            .unwrap()
+            .starts_with("arn:aws-us-gov:iam::123456789012:user/ci/deployer\n"));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    error::{conflicting_ticket, ErrorKind},
+    explain::{ExplainedError, Explanation},
+};
+use approval::ticket::ApprovalTicket;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// the schema identifier of ticket documents. bump the version on any breaking change to [`TicketOutput`].
+pub(crate) const TICKET_SCHEMA: &str = "tagctl/ticket/v1";
+/// the schema identifier of error documents. bump the version on any breaking change to [`ErrorOutput`].
+pub(crate) const ERROR_SCHEMA: &str = "tagctl/error/v1";
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OutputFormat {
+    /// human readable text
+    #[default]
+    Text,
+    /// a single JSON document
+    Json,
+    /// a single YAML document
+    Yaml,
+    /// a table with a row per ticket
+    Table,
+}
+
+/// the action a ticket command performed
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TicketAction {
+    Get,
+    Set,
+    Unset,
+    Extend,
+    Wait,
+    #[cfg(feature = "chainable")]
+    Derive,
+    #[cfg(feature = "signed")]
+    Verify,
+}
+
+/// the document emitted by ticket commands
+#[derive(Serialize, Debug)]
+pub(crate) struct TicketOutput {
+    pub(crate) schema: &'static str,
+    pub(crate) action: TicketAction,
+    pub(crate) principal_arn: String,
+    pub(crate) ticket: Option<TicketView>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct TicketView {
+    pub(crate) id: Option<String>,
+    pub(crate) giver: String,
+    pub(crate) receiver: String,
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+    pub(crate) expires_in_seconds: Option<i64>,
+    pub(crate) expired: bool,
+    pub(crate) extensions: Vec<DateTime<Utc>>,
+    pub(crate) spec: BTreeMap<String, String>,
+    pub(crate) value: String,
+}
+
+impl TicketView {
+    pub(crate) fn new(ticket: &ApprovalTicket, now: DateTime<Utc>) -> Self {
+        let expires_at = ticket.expires_at();
+        let expires_in_seconds = expires_at.map(|exp| (exp - now).num_seconds().max(0));
+        Self {
+            id: ticket.id().map(str::to_string),
+            giver: ticket.giver.to_string(),
+            receiver: ticket.receiver.to_string(),
+            expires_at,
+            expires_in_seconds,
+            expired: expires_at.is_some_and(|exp| exp <= now),
+            extensions: ticket.extensions(),
+            spec: ticket.spec().iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            value: ticket.to_string(),
+        }
+    }
+
+    /// the expiry as RFC 3339 together with the time remaining
+    pub(crate) fn expiry_summary(&self) -> String {
+        match (self.expires_at, self.expires_in_seconds) {
+            (Some(_), _) if self.expired => "expired".to_string(),
+            (Some(exp), Some(seconds)) => format!(
+                "{} ({} remaining)",
+                exp.to_rfc3339(),
+                humantime::format_duration(std::time::Duration::from_secs(seconds as u64))
+            ),
+            _ => "never".to_string(),
+        }
+    }
+}
+
+impl TicketOutput {
+    pub(crate) fn new(action: TicketAction, principal_arn: impl Into<String>, ticket: Option<&ApprovalTicket>) -> Self {
+        let now = Utc::now();
+        Self {
+            schema: TICKET_SCHEMA,
+            action,
+            principal_arn: principal_arn.into(),
+            ticket: ticket.map(|t| TicketView::new(t, now)),
+        }
+    }
+
+    pub(crate) fn render(&self, format: OutputFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Yaml => serde_yaml::to_string(self)?.trim_end().to_string(),
+            OutputFormat::Table => self.render_table(),
+            OutputFormat::Text => self.render_text(),
+        })
+    }
+
+    fn render_text(&self) -> String {
+        let Some(ticket) = &self.ticket else {
+            return match self.action {
+                TicketAction::Unset => format!("Ticket unset on {}", self.principal_arn),
+                _ => format!("No ticket on {}", self.principal_arn),
+            };
+        };
+
+        let mut lines = vec![
+            format!("Principal:  {}", self.principal_arn),
+            format!("Giver:      {}", ticket.giver),
+            format!("Receiver:   {}", ticket.receiver),
+        ];
+        if let Some(id) = &ticket.id {
+            lines.push(format!("Id:         {}", id));
+        }
+        lines.push(format!("Expires:    {}", ticket.expiry_summary()));
+        if !ticket.extensions.is_empty() {
+            lines.push(format!("Extensions: {}", ticket.extensions.len()));
+        }
+        let spec = ticket
+            .spec
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(format!("Spec:       {}", spec));
+        lines.join("\n")
+    }
+
+    fn render_table(&self) -> String {
+        let header = ["PRINCIPAL", "GIVER", "RECEIVER", "EXPIRES", "ID"].map(String::from);
+        let rows = match &self.ticket {
+            Some(ticket) => vec![
+                header,
+                [
+                    self.principal_arn.clone(),
+                    ticket.giver.clone(),
+                    ticket.receiver.clone(),
+                    ticket.expiry_summary(),
+                    ticket.id.clone().unwrap_or_else(|| "-".to_string()),
+                ],
+            ],
+            None => vec![header],
+        };
+        render_rows(&rows)
+    }
+}
+
+/// the document emitted when a command fails
+#[derive(Serialize, Debug)]
+pub(crate) struct ErrorOutput<'a> {
+    pub(crate) schema: &'static str,
+    pub(crate) error: ErrorView<'a>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct ErrorView<'a> {
+    pub(crate) kind: ErrorKind,
+    pub(crate) exit_code: i32,
+    pub(crate) message: String,
+    pub(crate) causes: Vec<String>,
+    pub(crate) existing_ticket: Option<TicketView>,
+    pub(crate) explanation: Option<&'a Explanation>,
+}
+
+impl<'a> ErrorOutput<'a> {
+    pub(crate) fn new(err: &'a anyhow::Error) -> Self {
+        let kind = ErrorKind::of(err);
+        Self {
+            schema: ERROR_SCHEMA,
+            error: ErrorView {
+                kind,
+                exit_code: kind.exit_code(),
+                message: err.to_string(),
+                causes: err.chain().skip(1).map(|e| e.to_string()).collect(),
+                existing_ticket: conflicting_ticket(err).map(|t| TicketView::new(t, Utc::now())),
+                explanation: ExplainedError::find(err).map(|e| &e.explanation),
+            },
+        }
+    }
+
+    /// prints machine readable formats to stdout, where the successful output would go, and text to stderr
+    pub(crate) fn print(&self, format: OutputFormat) {
+        let document = match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self).ok(),
+            OutputFormat::Yaml => serde_yaml::to_string(self).ok().map(|s| s.trim_end().to_string()),
+            OutputFormat::Text | OutputFormat::Table => None,
+        };
+        match document {
+            Some(document) => println!("{}", document),
+            None => eprintln!("{}", self.render_text()),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut lines = vec![format!("Error: {}", self.error.message)];
+        lines.extend(self.error.causes.iter().map(|cause| format!("  caused by: {}", cause)));
+        if let Some(existing) = &self.error.existing_ticket {
+            lines.push(format!(
+                "Existing ticket given by {} to {}, expires {}",
+                existing.giver,
+                existing.receiver,
+                existing.expiry_summary()
+            ));
+        }
+        if let Some(explanation) = self.error.explanation {
+            lines.push(explanation.render_text());
+        }
+        lines.join("\n")
+    }
+}
+
+/// left-aligns the cells of each column, separated by 3 spaces
+pub(crate) fn render_rows<const N: usize>(rows: &[[String; N]]) -> String {
+    let widths: Vec<usize> = (0..N)
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0))
+        .collect();
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("   ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TicketAction, TicketOutput};
+    use approval::ticket::ApprovalTicket;
+
+    #[test]
+    fn test_ticket_json_schema() {
+        let ticket = "by/alice/id=00000000000000ff/exp=1618033988/for/bob"
+            .parse::<ApprovalTicket>()
+            .unwrap();
+        let output = TicketOutput::new(
+            TicketAction::Get,
+            "arn:aws:iam::123456789012:role/myrole",
+            Some(&ticket),
+        );
+        let json = serde_json::to_value(&output).unwrap();
+
+        assert_eq!(json["schema"], "tagctl/ticket/v1");
+        assert_eq!(json["action"], "get");
+        assert_eq!(json["principal_arn"], "arn:aws:iam::123456789012:role/myrole");
+        assert_eq!(json["ticket"]["id"], "00000000000000ff");
+        assert_eq!(json["ticket"]["giver"], "alice");
+        assert_eq!(json["ticket"]["receiver"], "bob");
+        assert_eq!(json["ticket"]["expires_at"], "2021-04-10T05:53:08Z");
+        assert_eq!(json["ticket"]["expires_in_seconds"], 0);
+        assert_eq!(json["ticket"]["expired"], true);
+        assert_eq!(json["ticket"]["spec"]["exp"], "1618033988");
+        assert_eq!(json["ticket"]["value"], ticket.to_string());
+    }
+}
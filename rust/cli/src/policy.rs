@@ -0,0 +1,155 @@
+use crate::{
+    config::ContextArgs,
+    error::{CliError, ErrorKind},
+    explain::Explanation,
+    output::OutputFormat,
+};
+use approval::{
+    catalog::ActionCatalog,
+    rcp,
+    scp::{Enforcement, ResourceAccess, SealKind},
+    tag_policy::TagPolicy,
+    ticket::ApprovalTicket,
+};
+use clap::{ArgGroup, Args, Subcommand, ValueEnum};
+
+#[derive(Args)]
+#[command(about)]
+pub(crate) struct PolicyArgs {
+    #[command(flatten)]
+    pub(crate) context: ContextArgs,
+
+    /// the output format of `verify`. policies are always rendered as JSON.
+    #[arg(long, short, global = true, value_enum, default_value_t)]
+    pub(crate) output: OutputFormat,
+
+    #[command(subcommand)]
+    command: PolicyCommand,
+}
+
+#[derive(Subcommand)]
+enum PolicyCommand {
+    /// renders an organization policy for the control tags of the configured namespace, as JSON
+    Render(RenderArgs),
+    /// replays the seal kinds of the policies against an access to a sealed resource
+    Verify(VerifyArgs),
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("kind").required(true)))]
+struct RenderArgs {
+    /// renders an AWS Organizations tag policy, for control tags to show up in tag compliance reports
+    #[arg(long, group = "kind")]
+    tag_policy: bool,
+
+    /// the resource types on which the tag policy denies noncompliant tagging, e.g. `iam:role`.
+    /// noncompliant tags are only reported without it.
+    #[arg(long, requires = "tag_policy")]
+    enforced_for: Vec<String>,
+
+    /// renders a resource control policy mirroring the seal kinds, for seals to hold against principals
+    /// outside the organization
+    #[arg(long, group = "kind", requires = "organization_id")]
+    rcp: bool,
+
+    /// the id of the organization whose principals the RCP leaves to the control-tags SCP
+    #[arg(long, env = "TAGCTL_ORGANIZATION_ID")]
+    organization_id: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum EnforcementArg {
+    /// the control-tags SCP only
+    #[default]
+    Scp,
+    /// the control-tags SCP and the RCP, once it is attached
+    Rcp,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// the action performed on the resource, e.g. `s3:PutObject`
+    action: String,
+
+    /// the seal kind of the resource. any builtin kind is considered when omitted.
+    #[arg(long)]
+    seal_kind: Option<String>,
+
+    /// the caller is a principal outside the organization
+    #[arg(long, default_value_t = false)]
+    outside_org: bool,
+
+    /// the ticket on the calling principal. the principal holds none when omitted.
+    #[arg(long)]
+    ticket: Option<String>,
+
+    /// the source identity of the caller. unknown when omitted.
+    #[arg(long)]
+    source_identity: Option<String>,
+
+    /// the policies to replay
+    #[arg(long, value_enum, default_value_t)]
+    enforcement: EnforcementArg,
+}
+
+pub(crate) fn handle_policy_command(args: PolicyArgs) -> anyhow::Result<()> {
+    let context = args.context.load()?;
+    let schema = context.schema()?;
+    let namespace = schema.current();
+
+    match args.command {
+        PolicyCommand::Render(render) => {
+            let document = match (render.tag_policy, render.organization_id) {
+                (true, _) => {
+                    serde_json::to_string_pretty(&TagPolicy::for_control_tags(namespace, &render.enforced_for))?
+                }
+                (false, Some(organization_id)) => {
+                    let document = rcp::render(namespace, &organization_id, ActionCatalog::bundled())
+                        .map_err(|e| CliError::new(ErrorKind::InvalidInput, e.to_string()))?;
+                    serde_json::to_string_pretty(&document)?
+                }
+                (false, None) => unreachable!("clap requires a policy kind, and an organization id with --rcp"),
+            };
+            println!("{}", document);
+        }
+        PolicyCommand::Verify(verify) => {
+            if let Some(kind) = verify
+                .seal_kind
+                .as_deref()
+                .filter(|kind| SealKind::find(kind).is_none())
+            {
+                return Err(CliError::new(ErrorKind::InvalidInput, format!("unknown seal kind {:?}", kind)).into());
+            }
+            let ticket = verify
+                .ticket
+                .map(|ticket| ticket.parse::<ApprovalTicket>())
+                .transpose()
+                .map_err(|e| CliError::new(ErrorKind::InvalidInput, format!("invalid ticket: {}", e)))?;
+            let access = ResourceAccess {
+                action: verify.action,
+                seal_kind: verify.seal_kind.map(Some),
+                principal_in_org: Some(!verify.outside_org),
+                source_identity: verify.source_identity.map(Some),
+                ticket: Some(ticket),
+            };
+            let enforcement = match verify.enforcement {
+                EnforcementArg::Scp => Enforcement::Scp,
+                EnforcementArg::Rcp => Enforcement::Rcp,
+            };
+            let mut explanation = Explanation::from_access(&access, enforcement);
+            let rendered = match args.output {
+                OutputFormat::Text | OutputFormat::Table if explanation.findings.is_empty() => {
+                    format!("{} is not denied by the seal kinds", access.action)
+                }
+                OutputFormat::Text | OutputFormat::Table => {
+                    // the explanation reads as a denial, while verify tells what could deny the access
+                    explanation.action = None;
+                    format!("{} is denied by\n{}", access.action, explanation.render_text())
+                }
+                format => explanation.render(format)?,
+            };
+            println!("{}", rendered);
+        }
+    }
+    Ok(())
+}
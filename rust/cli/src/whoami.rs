@@ -0,0 +1,310 @@
+use crate::{
+    config::ContextArgs,
+    load_sdk_config,
+    migrate::list_tags,
+    mirror_role_name,
+    output::{OutputFormat, TicketView},
+    MIRROR_ROLE_NAME_PREFIX,
+};
+use anyhow::Context;
+use approval::{
+    error::ApiError,
+    guard::GUARDED_ACTIONS_SCP_PREFIX,
+    org::list_policies_applying_to_account,
+    principal::{IamPrincipal, PrincipalKind},
+    tags::TagSchema,
+};
+use chrono::Utc;
+use clap::Args;
+use serde::Serialize;
+
+/// the schema identifier of whoami documents. bump the version on any breaking change to [`WhoamiOutput`].
+pub(crate) const WHOAMI_SCHEMA: &str = "tagctl/whoami/v1";
+
+/// the name of the control-tags SCP, as created by the terraform module
+const CONTROL_TAGS_SCP_NAME: &str = "control_tags";
+
+#[derive(Args)]
+#[command(about)]
+pub(crate) struct WhoamiArgs {
+    #[command(flatten)]
+    pub(crate) context: ContextArgs,
+
+    /// the output format
+    #[arg(long, short, value_enum, default_value_t)]
+    pub(crate) output: OutputFormat,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ScpKind {
+    ControlTags,
+    GuardedActions,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct ScpView {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) kind: ScpKind,
+    /// the account, organizational unit or root the SCP is attached to
+    pub(crate) target_id: String,
+}
+
+/// the document emitted by `tagctl whoami`. facts which cannot be looked up are left empty, and the reason noted.
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct WhoamiOutput {
+    pub(crate) schema: &'static str,
+    pub(crate) caller_arn: String,
+    pub(crate) account_id: String,
+    pub(crate) principal_kind: Option<PrincipalKind>,
+    pub(crate) principal_name: Option<String>,
+    pub(crate) session_name: Option<String>,
+    /// the role or user of the caller, at path `/` for an assumed role as its session ARN omits the path
+    #[serde(skip)]
+    pub(crate) principal: Option<IamPrincipal>,
+    /// only known for sessions of mirror roles, which carry their session name as source identity
+    pub(crate) source_identity: Option<String>,
+    pub(crate) grant_area: Option<String>,
+    pub(crate) identity_broker: bool,
+    pub(crate) mirror_role: Option<String>,
+    pub(crate) ticket: Option<TicketView>,
+    /// the guarded-action and control-tags SCPs applying to the account
+    pub(crate) scps: Option<Vec<ScpView>>,
+    pub(crate) notes: Vec<String>,
+}
+
+impl WhoamiOutput {
+    /// the facts told by the caller ARN: the account, the kind and name of the principal,
+    /// and the session name of assumed roles
+    fn from_caller_arn(caller_arn: String) -> anyhow::Result<Self> {
+        let ["arn", partition, _, "", account_id, resource] = caller_arn.splitn(6, ':').collect::<Vec<_>>()[..] else {
+            anyhow::bail!("not a caller ARN: {}", caller_arn);
+        };
+        // sessions of a role are named after the role they assume, other callers are IAM users or not principals
+        let (principal, session_name) = match resource.split('/').collect::<Vec<_>>()[..] {
+            ["assumed-role", role, session] => (
+                Some(IamPrincipal {
+                    partition: partition.to_string(),
+                    ..IamPrincipal::role(account_id, role)
+                }),
+                Some(session.to_string()),
+            ),
+            _ => (caller_arn.parse::<IamPrincipal>().ok(), None),
+        };
+        Ok(Self {
+            schema: WHOAMI_SCHEMA,
+            account_id: account_id.to_string(),
+            principal_kind: principal.as_ref().map(|principal| principal.kind),
+            principal_name: principal.as_ref().map(|principal| principal.name.clone()),
+            session_name,
+            principal,
+            caller_arn,
+            ..Default::default()
+        })
+    }
+
+    pub(crate) fn render(&self, format: OutputFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            OutputFormat::Json => serde_json::to_string_pretty(self)?,
+            OutputFormat::Yaml => serde_yaml::to_string(self)?.trim_end().to_string(),
+            OutputFormat::Text | OutputFormat::Table => self.render_text(),
+        })
+    }
+
+    fn render_text(&self) -> String {
+        let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "none".to_string());
+        let mut lines = vec![
+            format!("Caller:          {}", self.caller_arn),
+            format!("Account:         {}", self.account_id),
+        ];
+        if let Some(session_name) = &self.session_name {
+            lines.push(format!("Session:         {}", session_name));
+        }
+        lines.extend([
+            format!(
+                "Source identity: {}",
+                self.source_identity.clone().unwrap_or_else(|| "unknown".to_string())
+            ),
+            format!("Grant area:      {}", or_none(&self.grant_area)),
+            format!("Identity broker: {}", if self.identity_broker { "yes" } else { "no" }),
+            format!("Mirror role:     {}", or_none(&self.mirror_role)),
+        ]);
+        lines.push(match &self.ticket {
+            Some(ticket) => format!(
+                "Ticket:          {} (expires {})",
+                ticket.value,
+                ticket.expiry_summary()
+            ),
+            None => "Ticket:          none".to_string(),
+        });
+        match &self.scps {
+            Some(scps) if !scps.is_empty() => {
+                for (i, scp) in scps.iter().enumerate() {
+                    let label = if i == 0 { "SCPs:" } else { "" };
+                    lines.push(format!(
+                        "{:17}{} ({}) attached to {}",
+                        label, scp.name, scp.id, scp.target_id
+                    ));
+                }
+            }
+            Some(_) => lines.push("SCPs:            none".to_string()),
+            None => lines.push("SCPs:            unknown".to_string()),
+        }
+        lines.extend(self.notes.iter().map(|note| format!("note: {}", note)));
+        lines.join("\n")
+    }
+}
+
+fn scp_kind(name: &str) -> Option<ScpKind> {
+    match name {
+        CONTROL_TAGS_SCP_NAME => Some(ScpKind::ControlTags),
+        name if name.starts_with(GUARDED_ACTIONS_SCP_PREFIX) => Some(ScpKind::GuardedActions),
+        _ => None,
+    }
+}
+
+pub(crate) async fn handle_whoami_command(args: WhoamiArgs) -> anyhow::Result<()> {
+    let context = args.context.load()?;
+    let schema = context.schema()?;
+    let sdk_config = load_sdk_config(context.profile).await;
+
+    let output = whoami(&sdk_config, &schema).await?;
+    println!("{}", output.render(args.output)?);
+    Ok(())
+}
+
+/// looks up what the control-tags SCPs see of the caller
+pub(crate) async fn whoami(sdk_config: &aws_config::SdkConfig, schema: &TagSchema) -> anyhow::Result<WhoamiOutput> {
+    let sts = aws_sdk_sts::Client::new(sdk_config);
+    let iam = aws_sdk_iam::Client::new(sdk_config);
+    let orgs = aws_sdk_organizations::Client::new(sdk_config);
+
+    let caller_arn = sts
+        .get_caller_identity()
+        .send()
+        .await
+        .map_err(ApiError::from_sdk)?
+        .arn
+        .context("no arn returned by sts:GetCallerIdentity")?;
+    let mut output = WhoamiOutput::from_caller_arn(caller_arn)?;
+
+    if let Some(principal) = output.principal.clone() {
+        describe_principal(&iam, schema, &principal, &mut output).await;
+    }
+
+    match list_policies_applying_to_account(&orgs, &output.account_id).await {
+        Ok(policies) => {
+            let scps = policies
+                .into_iter()
+                .filter_map(|policy| {
+                    Some(ScpView {
+                        kind: scp_kind(&policy.name)?,
+                        id: policy.id,
+                        name: policy.name,
+                        target_id: policy.target_id,
+                    })
+                })
+                .collect();
+            output.scps = Some(scps);
+        }
+        Err(e) => output.notes.push(format!("cannot list the SCPs of the account: {}", e)),
+    }
+    Ok(output)
+}
+
+/// looks up the control tags and mirror role of the calling principal
+async fn describe_principal(
+    iam: &aws_sdk_iam::Client,
+    schema: &TagSchema,
+    principal: &IamPrincipal,
+    output: &mut WhoamiOutput,
+) {
+    let name = principal.name.as_str();
+    let namespace = schema.current();
+    match list_tags(iam, principal).await {
+        Ok(tags) => {
+            let value_of = |key: String| tags.iter().find(|tag| tag.key == key).map(|tag| tag.value.clone());
+            output.grant_area = value_of(namespace.grant_area_key());
+            output.identity_broker = value_of(namespace.identity_broker_key()).as_deref() == Some("true");
+            output.ticket = schema
+                .find_ticket(tags.iter().map(|tag| (tag.key.as_str(), tag.value.as_str())))
+                .map(|ticket| TicketView::new(&ticket, Utc::now()));
+        }
+        Err(e) => output.notes.push(format!("cannot list the tags of {}: {:#}", name, e)),
+    }
+
+    if principal.kind != PrincipalKind::Role {
+        return;
+    }
+    // sessions created by `tagctl mirror assume` carry their session name as source identity
+    if name.starts_with(MIRROR_ROLE_NAME_PREFIX) {
+        output.source_identity.clone_from(&output.session_name);
+        output.mirror_role = Some(name.to_string());
+        return;
+    }
+    let role = match iam.get_role().role_name(name).send().await {
+        Ok(response) => response.role,
+        Err(e) => {
+            output
+                .notes
+                .push(format!("cannot get role {}: {}", name, ApiError::from_sdk(e)));
+            return;
+        }
+    };
+    let Some(mirror_role) = role.as_ref().and_then(|role| mirror_role_name(role).ok()) else {
+        return;
+    };
+    match iam.get_role().role_name(&mirror_role).send().await {
+        Ok(_) => output.mirror_role = Some(mirror_role),
+        Err(e) => output.notes.push(format!(
+            "cannot get mirror role {}: {}",
+            mirror_role,
+            ApiError::from_sdk(e)
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scp_kind, ScpKind, WhoamiOutput};
+    use approval::principal::{IamPrincipal, PrincipalKind};
+
+    #[test]
+    fn test_whoami_from_caller_arn() {
+        let output = WhoamiOutput::from_caller_arn(
+            "arn:aws:sts::123456789012:assumed-role/tagctl-mirror-admin/alice".to_string(),
+        )
+        .unwrap();
+        assert_eq!(output.account_id, "123456789012");
+        assert_eq!(output.principal_kind, Some(PrincipalKind::Role));
+        assert_eq!(output.principal_name.as_deref(), Some("tagctl-mirror-admin"));
+        assert_eq!(output.session_name.as_deref(), Some("alice"));
+
+        let output = WhoamiOutput::from_caller_arn("arn:aws:iam::123456789012:user/ci/deployer".to_string()).unwrap();
+        assert_eq!(output.principal_kind, Some(PrincipalKind::User));
+        assert_eq!(output.principal_name.as_deref(), Some("deployer"));
+        assert_eq!(output.session_name, None);
+        assert_eq!(
+            output.principal,
+            Some(IamPrincipal::user("123456789012", "deployer").with_path("ci"))
+        );
+
+        let output =
+            WhoamiOutput::from_caller_arn("arn:aws-cn:sts::123456789012:assumed-role/deployer/alice".to_string())
+                .unwrap();
+        assert_eq!(
+            output.principal.map(|principal| principal.to_string()).as_deref(),
+            Some("arn:aws-cn:iam::123456789012:role/deployer")
+        );
+
+        let output = WhoamiOutput::from_caller_arn("arn:aws:iam::123456789012:root".to_string()).unwrap();
+        assert_eq!(output.account_id, "123456789012");
+        assert_eq!((output.principal_kind, output.principal), (None, None));
+        assert!(WhoamiOutput::from_caller_arn("deployer".to_string()).is_err());
+
+        assert_eq!(scp_kind("control_tags"), Some(ScpKind::ControlTags));
+        assert_eq!(scp_kind("guarded_actions_prod"), Some(ScpKind::GuardedActions));
+        assert_eq!(scp_kind("FullAWSAccess"), None);
+    }
+}
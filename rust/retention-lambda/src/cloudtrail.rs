@@ -0,0 +1,118 @@
+use approval::{principal::PrincipalKind, tags::TagSchema};
+use serde::Deserialize;
+
+/// the detail type of the CloudTrail events delivered by EventBridge
+pub(crate) const CLOUDTRAIL_DETAIL_TYPE: &str = "AWS API Call via CloudTrail";
+
+/// a CloudTrail event, as delivered by EventBridge. only the fields needed to validate tickets are read.
+#[derive(Deserialize, Debug)]
+pub(crate) struct CloudTrailNotification {
+    #[serde(rename = "detail-type")]
+    pub(crate) detail_type: String,
+    pub(crate) detail: CloudTrailDetail,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CloudTrailDetail {
+    pub(crate) event_name: String,
+    pub(crate) recipient_account_id: String,
+    /// set when the call failed
+    pub(crate) error_code: Option<String>,
+    pub(crate) request_parameters: Option<TaggingParameters>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaggingParameters {
+    pub(crate) role_name: Option<String>,
+    pub(crate) user_name: Option<String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<EventTag>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct EventTag {
+    pub(crate) key: String,
+    pub(crate) value: String,
+}
+
+/// a ticket tag set by a successful `TagRole` or `TagUser` call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TicketTagging {
+    pub(crate) account_id: String,
+    /// the event names the principal only, its path is resolved by the validation
+    pub(crate) kind: PrincipalKind,
+    pub(crate) name: String,
+    /// the ticket key the value was tagged under
+    pub(crate) key: String,
+    /// the raw tag value, which may not parse as a ticket
+    pub(crate) value: String,
+}
+
+impl CloudTrailNotification {
+    /// the ticket tag set by the call, if it set one of the ticket keys of `schema`.
+    /// the key of the current schema version wins, like [`TagSchema::find_ticket`].
+    pub(crate) fn ticket_tagging(&self, schema: &TagSchema) -> Option<TicketTagging> {
+        let detail = &self.detail;
+        if self.detail_type != CLOUDTRAIL_DETAIL_TYPE || detail.error_code.is_some() {
+            return None;
+        }
+        let params = detail.request_parameters.as_ref()?;
+        let (kind, name) = match (detail.event_name.as_str(), &params.role_name, &params.user_name) {
+            ("TagRole", Some(role), _) => (PrincipalKind::Role, role.clone()),
+            ("TagUser", _, Some(user)) => (PrincipalKind::User, user.clone()),
+            _ => return None,
+        };
+        let tag = schema
+            .ticket_keys()
+            .iter()
+            .find_map(|key| params.tags.iter().find(|tag| tag.key == *key))?;
+        Some(TicketTagging {
+            account_id: detail.recipient_account_id.clone(),
+            kind,
+            name,
+            key: tag.key.clone(),
+            value: tag.value.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CloudTrailNotification;
+    use approval::{principal::PrincipalKind, tags::TagSchema};
+
+    #[test]
+    fn test_ticket_tagging() {
+        let event = r#"{
+            "version": "0",
+            "detail-type": "AWS API Call via CloudTrail",
+            "source": "aws.iam",
+            "detail": {
+                "eventSource": "iam.amazonaws.com",
+                "eventName": "TagRole",
+                "recipientAccountId": "123456789012",
+                "requestParameters": {
+                    "roleName": "deployer",
+                    "tags": [
+                        {"key": "team", "value": "platform"},
+                        {"key": "tagctl:v1/admin/mpa/ticket", "value": "by/alice/exp=1618033988/for/bob"}
+                    ]
+                }
+            }
+        }"#;
+        let mut event: CloudTrailNotification = serde_json::from_str(event).unwrap();
+        let tagging = event.ticket_tagging(&TagSchema::default()).unwrap();
+        assert_eq!(tagging.account_id, "123456789012");
+        assert_eq!((tagging.kind, tagging.name.as_str()), (PrincipalKind::Role, "deployer"));
+        assert_eq!(tagging.key, "tagctl:v1/admin/mpa/ticket");
+        assert_eq!(tagging.value, "by/alice/exp=1618033988/for/bob");
+
+        let v2 = TagSchema::new("tagctl:v2".parse().unwrap());
+        assert!(event.ticket_tagging(&v2).is_none());
+
+        event.detail.error_code = Some("AccessDenied".to_string());
+        assert!(event.ticket_tagging(&TagSchema::default()).is_none());
+    }
+}
@@ -1,33 +1,233 @@
+mod cloudtrail;
+mod scheduler;
+mod summary;
+mod sweep;
+
 use anyhow::{Context, Result};
-use approval::{self, iam::ApprovalManager, ticket::ApprovalTicket};
-use async_stream::try_stream;
+use approval::{
+    self,
+    chain::{broken_chains, ChainBreak},
+    error::ApiError,
+    iam::{ApprovalManager, ListPrincipalsError, RoleApprovalManager, UserApprovalManager, DEFAULT_CONCURRENCY},
+    org::traverse_accounts_affected_by_policy,
+    principal::{IamPrincipal, PrincipalKind},
+    retry::RetryMetrics,
+    signature::Keyring,
+    tags::{MalformedTicket, TagSchema, TicketTag},
+    ticket::ApprovalTicket,
+};
 use aws_config::{sts::AssumeRoleProviderBuilder, BehaviorVersion};
-use aws_sdk_iam;
 use aws_sdk_iam::config::SharedCredentialsProvider;
 use aws_sdk_iam::primitives::Blob;
 use aws_sdk_lambda::{self, types::InvocationType};
-use aws_sdk_organizations::types::{ChildType, TargetType};
-use aws_smithy_types_convert::stream::PaginationStreamExt;
-use chrono::Duration;
-use futures::{future, stream, Stream, StreamExt, TryStreamExt};
-use lambda_runtime::{service_fn, tracing, Error, LambdaEvent};
+use chrono::{DateTime, Duration, Utc};
+use cloudtrail::{CloudTrailNotification, TicketTagging};
+use futures::{future, stream, StreamExt};
+use lambda_runtime::{service_fn, tracing, Diagnostic, Error, LambdaEvent};
+use scheduler::{eviction_schedule_name, OneTimeSchedules, Scheduled};
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::{env::var, sync::Arc};
+use std::{collections::HashSet, env::var, sync::Arc};
+use summary::{
+    AccountFailure, EvictionOutcome, EvictionReason, EvictionSummary, FlaggedTag, FlaggedTicket, ListingError,
+    SchedulingSummary,
+};
+use sweep::{AccountReport, ResultSink, SweepManifest, SweepReport};
+use thiserror::Error;
 
 #[derive(Serialize, Deserialize)]
 enum Request {
-    ScheduleApprovalEviction {},
-    EvictStaleApprovals { account_id: String },
+    ScheduleApprovalEviction {
+        #[serde(flatten)]
+        flags: ModeFlags,
+    },
+    EvictStaleApprovals {
+        account_id: String,
+        #[serde(flatten)]
+        flags: ModeFlags,
+        /// the sweep the account reports its summary to
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sweep_id: Option<String>,
+    },
+    /// removes the ticket from the principal if it still holds it and it expired, invoked by a one-time schedule
+    /// at its expiry
+    EvictPrincipalTicket {
+        account_id: String,
+        principal: ScheduledPrincipal,
+        ticket_id: String,
+        /// the expiry the eviction was scheduled at, missing from schedules created before it was recorded
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at: Option<DateTime<Utc>>,
+        #[serde(flatten)]
+        flags: ModeFlags,
+    },
+    /// aggregates the summaries the accounts of a sweep reported, of the latest sweep by default
+    GetSweepReport {
+        #[serde(default)]
+        sweep_id: Option<String>,
+    },
+}
+
+/// the principal of a scheduled eviction, as its ARN.
+/// schedules created before principals were ARNs name a role or user of the account of the request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum ScheduledPrincipal {
+    Arn(IamPrincipal),
+    Named(NamedPrincipal),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+enum NamedPrincipal {
+    Role(String),
+    User(String),
+}
+
+impl ScheduledPrincipal {
+    /// the principal, at path `/` if it is only named, as only its name is needed to evict its ticket
+    fn in_account(self, account_id: &str) -> IamPrincipal {
+        match self {
+            ScheduledPrincipal::Arn(principal) => principal,
+            ScheduledPrincipal::Named(NamedPrincipal::Role(name)) => IamPrincipal::role(account_id, name),
+            ScheduledPrincipal::Named(NamedPrincipal::User(name)) => IamPrincipal::user(account_id, name),
+        }
+    }
+}
+
+/// how tickets breaking policy are handled
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum Mode {
+    /// tickets breaking policy are untagged
+    #[default]
+    Enforce,
+    /// nothing is changed, tickets breaking policy are logged as warnings for alerting
+    ReportOnly,
+    /// nothing is changed, tickets breaking policy are only returned
+    DryRun,
+}
+
+/// the `dry_run` and `report_only` flags of a request, unset flags fall back to the environment
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ModeFlags {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dry_run: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    report_only: Option<bool>,
+}
+
+impl ModeFlags {
+    /// the defaults set by the `DRY_RUN` and `REPORT_ONLY` environment variables
+    fn from_env() -> anyhow::Result<Self> {
+        let flag = |name: &str| -> anyhow::Result<Option<bool>> {
+            var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    value
+                        .parse::<bool>()
+                        .with_context(|| format!("{name} is not a boolean"))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            dry_run: flag("DRY_RUN")?,
+            report_only: flag("REPORT_ONLY")?,
+        })
+    }
+
+    /// the flags of `mode`, set explicitly so that they are not overridden by the environment
+    fn of(mode: Mode) -> Self {
+        Self {
+            dry_run: Some(mode == Mode::DryRun),
+            report_only: Some(mode == Mode::ReportOnly),
+        }
+    }
+
+    /// the mode of these flags, falling back to `defaults`. a dry run takes precedence over reporting.
+    fn mode(self, defaults: Self) -> Mode {
+        if self.dry_run.or(defaults.dry_run).unwrap_or(false) {
+            Mode::DryRun
+        } else if self.report_only.or(defaults.report_only).unwrap_or(false) {
+            Mode::ReportOnly
+        } else {
+            Mode::Enforce
+        }
+    }
+}
+
+/// the payloads the lambda is invoked with: requests from the scheduler and itself,
+/// or `TagRole`/`TagUser` CloudTrail events from EventBridge
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Invocation {
+    Request(Request),
+    CloudTrail(Box<CloudTrailNotification>),
 }
 
 #[derive(Serialize)]
 enum Response {
     DiscoveredAccounts(Vec<String>),
-    EvictionSummary {
-        users: Vec<(String, ApprovalTicket)>,
-        roles: Vec<(String, ApprovalTicket)>,
+    /// the tickets breaking policy, untagged unless in dry-run or report-only mode
+    EvictionSummary(EvictionSummary),
+    SweepReport(SweepReport),
+    TicketValidation {
+        mode: Mode,
+        account_id: String,
+        principal: IamPrincipal,
+        ticket: String,
+        outcome: ValidationOutcome,
+        /// whether an eviction was scheduled at the expiry of a valid ticket
+        scheduled: Option<Scheduled>,
+    },
+    TicketEviction {
+        mode: Mode,
+        account_id: String,
+        principal: IamPrincipal,
+        ticket_id: String,
+        /// whether the principal still held the ticket, expired rather than extended
+        held: bool,
+        /// false if the principal no longer held the ticket, or in dry-run or report-only mode
+        evicted: bool,
     },
+    /// the event set no ticket, the call failed, or the principal is gone
+    NoTicketTagged {},
+}
+
+/// the failure of an invocation, reported to the runtime so that asynchronous invocations are retried
+/// and eventually sent to their failure destination. partial failures carry their summary as json.
+#[derive(Error, Debug)]
+enum InvocationError {
+    #[error("{}", serde_json::to_string(.0).unwrap_or_default())]
+    PartialEviction(EvictionSummary),
+    #[error("{}", serde_json::to_string(.0).unwrap_or_default())]
+    PartialScheduling(SchedulingSummary),
+    #[error("{0:#}")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl From<InvocationError> for Diagnostic {
+    fn from(error: InvocationError) -> Self {
+        let error_type = match error {
+            InvocationError::PartialEviction(_) => "PartialEviction",
+            InvocationError::PartialScheduling(_) => "PartialScheduling",
+            InvocationError::Internal(_) => "InternalError",
+        };
+        Diagnostic {
+            error_type: error_type.to_string(),
+            error_message: error.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ValidationOutcome {
+    Valid,
+    Evicted(EvictionReason),
+    /// the ticket broke policy, but was already replaced or removed
+    Superseded(EvictionReason),
+    /// the ticket breaks policy, and was left in place in dry-run or report-only mode
+    Flagged(EvictionReason),
 }
 
 struct AppState {
@@ -36,6 +236,17 @@ struct AppState {
     role_path: String,
     control_tags_scp_id: String,
     max_ticket_ttl_seconds: chrono::Duration,
+    /// when set, tickets not signed by the trusted key of their giver are evicted
+    trusted_giver_keys: Option<Keyring>,
+    tag_schema: TagSchema,
+    /// when set, tickets are evicted at their expiry by one-time schedules, the sweep being a backstop
+    schedules: Option<OneTimeSchedules>,
+    /// the mode of requests which do not set it, and of CloudTrail events
+    default_flags: ModeFlags,
+    /// when set, the accounts of a sweep report their summary there, for the sweep report
+    results: Option<ResultSink>,
+    /// the number of principals listed or untagged at once in an account
+    iam_concurrency: usize,
 }
 
 #[tokio::main]
@@ -56,8 +267,11 @@ async fn init_appstate() -> anyhow::Result<AppState> {
         .filter(|&x| x > 0)
         .context("ttl is not possitive")?;
 
+    let sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
     Ok(AppState {
-        sdk_config: aws_config::load_defaults(BehaviorVersion::latest()).await,
+        schedules: OneTimeSchedules::from_env(&sdk_config).context("one-time schedules")?,
+        results: ResultSink::from_env(&sdk_config).context("sweep results")?,
+        sdk_config,
         role_name: var("WORKER_ROLE_NAME").context("WORKER_ROLE_NAME")?,
         role_path: var("WORKER_ROLE_PATH")
             .context("WORKER_ROLE_PATH")?
@@ -65,226 +279,817 @@ async fn init_appstate() -> anyhow::Result<AppState> {
             .to_owned(),
         control_tags_scp_id: var("CONTROL_TAGS_SCP_ID").context("CONTROL_TAGS_SCP_ID")?,
         max_ticket_ttl_seconds: Duration::seconds(ttl),
+        trusted_giver_keys: var("TRUSTED_GIVER_KEYS")
+            .ok()
+            .map(|keys| keys.parse::<Keyring>())
+            .transpose()
+            .context("TRUSTED_GIVER_KEYS")?
+            .filter(|keyring| !keyring.is_empty()),
+        tag_schema: TagSchema::from_env().context("tag schema")?,
+        default_flags: ModeFlags::from_env()?,
+        iam_concurrency: var("IAM_CONCURRENCY")
+            .ok()
+            .map(|n| {
+                n.parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .context("IAM_CONCURRENCY is not positive")
+            })
+            .transpose()?
+            .unwrap_or(DEFAULT_CONCURRENCY),
     })
 }
 
-pub(crate) async fn my_handler(event: LambdaEvent<Request>) -> anyhow::Result<Response, Error> {
+pub(crate) async fn my_handler(event: LambdaEvent<Invocation>) -> Result<Response, InvocationError> {
     let appstate = init_appstate().await?;
-    match event.payload {
-        Request::ScheduleApprovalEviction {} => {
-            tracing::info!("scheduling eviction of approval tickets");
+    let lambda_arn = event.context.invoked_function_arn;
+    let request_id = event.context.request_id;
+    let request = match event.payload {
+        Invocation::Request(request) => request,
+        Invocation::CloudTrail(notification) => {
+            let Some(tagging) = notification.ticket_tagging(&appstate.tag_schema) else {
+                return Ok(Response::NoTicketTagged {});
+            };
+            return Ok(validate_tagged_ticket(&appstate, &lambda_arn, tagging).await?);
+        }
+    };
+    match request {
+        Request::ScheduleApprovalEviction { flags } => {
+            let mode = flags.mode(appstate.default_flags);
+            let started_at = chrono::Utc::now();
+            let sweep_id = appstate.results.as_ref().map(|_| {
+                let suffix = request_id.get(..8).unwrap_or(&request_id);
+                format!("{}-{}", started_at.format("%Y%m%dT%H%M%SZ"), suffix)
+            });
+            tracing::info!(msg = "scheduling eviction of approval tickets", mode = ?mode, sweep_id = ?sweep_id);
             let orgs_client = aws_sdk_organizations::Client::new(&appstate.sdk_config);
             let mut accounts = traverse_accounts_affected_by_policy(&orgs_client, appstate.control_tags_scp_id);
-            let mut affected = vec![];
-            let lambda_arn = event.context.invoked_function_arn;
-
-            let lambda_client = aws_sdk_lambda::Client::new(&appstate.sdk_config);
+            let mut listed = vec![];
+            let mut failed = vec![];
             while let Some(x) = accounts.next().await {
                 match x {
-                    Ok(account_id) => {
-                        tracing::debug!(msg = "scheduling eviction", account_id = %account_id);
-                        if let Err(e) = schedule_eviction(&lambda_client, &account_id, &lambda_arn).await {
-                            tracing::error!(msg = "scheduling eviction", account_id = %account_id, error = %e);
-                            continue;
-                        }
-                        affected.push(account_id);
-                    }
+                    Ok(account_id) => listed.push(account_id),
                     Err(e) => {
                         tracing::error!(msg = "traversing accounts", error = %e);
+                        failed.push(AccountFailure {
+                            account_id: None,
+                            error: e.to_string(),
+                        });
+                    }
+                }
+            }
+
+            // the manifest names the accounts expected to report before any is invoked, the sweep fails without it
+            let mut manifest = sweep_id.as_ref().map(|sweep_id| SweepManifest {
+                sweep_id: sweep_id.clone(),
+                started_at,
+                mode,
+                scheduled: listed.clone(),
+                failed: failed.clone(),
+            });
+            if let (Some(results), Some(manifest)) = (&appstate.results, &manifest) {
+                results.start(manifest).await.context("recording the sweep manifest")?;
+            }
+
+            let lambda_client = aws_sdk_lambda::Client::new(&appstate.sdk_config);
+            let mut affected = vec![];
+            let mut unscheduled = vec![];
+            for account_id in listed {
+                tracing::debug!(msg = "scheduling eviction", account_id = %account_id);
+                match schedule_eviction(&lambda_client, &account_id, &lambda_arn, mode, sweep_id.as_deref()).await {
+                    Ok(()) => affected.push(account_id),
+                    Err(e) => {
+                        tracing::error!(msg = "scheduling eviction", account_id = %account_id, error = %e);
+                        unscheduled.push(AccountFailure {
+                            account_id: Some(account_id),
+                            error: format!("{e:#}"),
+                        });
                     }
                 }
             }
 
-            return Ok(Response::DiscoveredAccounts(affected));
+            // accounts which could not be invoked move from the scheduled accounts to the failed ones
+            if !unscheduled.is_empty() {
+                failed.extend(unscheduled);
+                if let (Some(results), Some(manifest)) = (&appstate.results, &mut manifest) {
+                    manifest.scheduled = affected.clone();
+                    manifest.failed = failed.clone();
+                    if let Err(e) = results.start(manifest).await {
+                        tracing::error!(msg = "recording the sweep manifest", sweep_id = %manifest.sweep_id, error = %e);
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                return Err(InvocationError::PartialScheduling(SchedulingSummary {
+                    scheduled: affected,
+                    failed,
+                }));
+            }
+            Ok(Response::DiscoveredAccounts(affected))
         }
-        Request::EvictStaleApprovals { account_id } => {
-            let role_arn = format!(
-                "arn:aws:iam::{account}:role/{path}/{name}",
-                account = &account_id,
-                path = appstate.role_path,
-                name = appstate.role_name,
+        Request::EvictStaleApprovals {
+            account_id,
+            flags,
+            sweep_id,
+        } => {
+            let mode = flags.mode(appstate.default_flags);
+            let iam_client = worker_iam_client(&appstate, &account_id).await;
+            let concurrency = appstate.iam_concurrency;
+            let metrics = Arc::new(RetryMetrics::default());
+            let user_manager = UserApprovalManager::new(iam_client.clone())
+                .with_schema(appstate.tag_schema.clone())
+                .with_account(&account_id)
+                .with_concurrency(concurrency)
+                .with_retry_metrics(metrics.clone());
+            let role_manager = RoleApprovalManager::new(iam_client.clone())
+                .with_schema(appstate.tag_schema.clone())
+                .with_account(&account_id)
+                .with_concurrency(concurrency)
+                .with_retry_metrics(metrics.clone());
+
+            let ((users_tickets, users_malformed, users_errors), (roles_tickets, roles_malformed, roles_errors)) =
+                future::join(
+                    list_tickets(&user_manager, "users"),
+                    list_tickets(&role_manager, "roles"),
+                )
+                .await;
+            let tickets = users_tickets.into_iter().chain(roles_tickets).collect();
+            // a parent missing from an incomplete listing may be held by a principal whose tags could not be read
+            let complete = users_errors.is_empty() && roles_errors.is_empty();
+            let (mut flagged, valid) = flag_tickets(
+                tickets,
+                complete,
+                appstate.max_ticket_ttl_seconds,
+                appstate.trusted_giver_keys.as_ref(),
+            );
+            // nothing is scheduled when nothing may change
+            let remaining = if mode == Mode::Enforce { valid } else { vec![] };
+            flagged.extend(
+                users_malformed
+                    .into_iter()
+                    .chain(roles_malformed)
+                    .map(|(principal, malformed)| FlaggedTicket {
+                        principal,
+                        tag: FlaggedTag::Malformed { malformed },
+                        reason: EvictionReason::Malformed,
+                    }),
             );
-            let provider = AssumeRoleProviderBuilder::new(role_arn).build().await;
+            let (users_flagged, roles_flagged) = flagged
+                .into_iter()
+                .partition(|flagged| flagged.principal.kind == PrincipalKind::User);
+            let (users_outcomes, roles_outcomes) = future::join(
+                evict_flagged(&user_manager, users_flagged, mode, concurrency),
+                evict_flagged(&role_manager, roles_flagged, mode, concurrency),
+            )
+            .await;
 
-            let config = appstate
-                .sdk_config
-                .into_builder()
-                .credentials_provider(SharedCredentialsProvider::new(provider))
-                .build();
-            let iam_client = Arc::new(aws_sdk_iam::Client::new(&config));
+            stream::iter(remaining)
+                .for_each_concurrent(concurrency, |(principal, ticket)| {
+                    let (appstate, lambda_arn, account_id) = (&appstate, &lambda_arn, &account_id);
+                    async move {
+                        if let Err(e) = schedule_expiry(appstate, lambda_arn, account_id, &principal, &ticket).await {
+                            tracing::error!(msg = "scheduling ticket expiry", error = %e, principal = %principal);
+                        }
+                    }
+                })
+                .await;
 
-            let user_manager = approval::iam::UserApprovalManager::new(iam_client.clone());
-            let role_manager = approval::iam::RoleApprovalManager::new(iam_client.clone());
+            let mut summary = EvictionSummary::new(mode, users_errors.into_iter().chain(roles_errors).collect());
+            for (flagged, outcome) in users_outcomes.into_iter().chain(roles_outcomes) {
+                summary.record(flagged, outcome);
+            }
+            summary.retries = metrics.stats();
+            if summary.retries.throttled > 0 {
+                tracing::warn!(msg = "throttled by IAM", account_id = %account_id, stats = ?summary.retries);
+            }
+            let report = AccountReport {
+                account_id: account_id.clone(),
+                reported_at: chrono::Utc::now(),
+                summary,
+            };
+            if let (Some(results), Some(sweep_id)) = (&appstate.results, &sweep_id) {
+                if let Err(e) = results.report_account(sweep_id, &report).await {
+                    tracing::error!(msg = "reporting the account summary", sweep_id = %sweep_id, account_id = %report.account_id, error = %e);
+                }
+            }
+            let summary = report.summary;
+            if summary.is_partial() {
+                tracing::error!(msg = "partial eviction", account_id = %account_id, failed = summary.failed.len(), listing_errors = summary.listing_errors.len());
+                return Err(InvocationError::PartialEviction(summary));
+            }
+            Ok(Response::EvictionSummary(summary))
+        }
+        Request::GetSweepReport { sweep_id } => {
+            let results = appstate
+                .results
+                .as_ref()
+                .context("sweep results are not collected, SWEEP_RESULTS_URL is not set")?;
+            Ok(Response::SweepReport(results.sweep_report(sweep_id.as_deref()).await?))
+        }
+        Request::EvictPrincipalTicket {
+            account_id,
+            principal,
+            ticket_id,
+            expires_at,
+            flags,
+        } => {
+            let mode = flags.mode(appstate.default_flags);
+            let principal = principal.in_account(&account_id);
+            let now = Utc::now();
+            let iam_client = worker_iam_client(&appstate, &account_id).await;
+            let schema = appstate.tag_schema.clone();
+            let held = match principal.kind {
+                PrincipalKind::Role => {
+                    evict_ticket_by_id(
+                        &RoleApprovalManager::new(iam_client)
+                            .with_schema(schema)
+                            .with_account(&account_id),
+                        &principal,
+                        &ticket_id,
+                        now,
+                        mode,
+                    )
+                    .await?
+                }
+                PrincipalKind::User => {
+                    evict_ticket_by_id(
+                        &UserApprovalManager::new(iam_client)
+                            .with_schema(schema)
+                            .with_account(&account_id),
+                        &principal,
+                        &ticket_id,
+                        now,
+                        mode,
+                    )
+                    .await?
+                }
+            };
+            let evicted = held && mode == Mode::Enforce;
+            if held && mode == Mode::ReportOnly {
+                tracing::warn!(msg = "ticket held past its expiry", account_id = %account_id, principal = %principal, ticket_id = %ticket_id);
+            }
+            tracing::info!(msg = "evicted ticket at expiry", account_id = %account_id, principal = %principal, ticket_id = %ticket_id, expires_at = ?expires_at, held = held, evicted = evicted, mode = ?mode);
+            Ok(Response::TicketEviction {
+                mode,
+                account_id,
+                principal,
+                ticket_id,
+                held,
+                evicted,
+            })
+        }
+    }
+}
 
-            let users_tickets_fut = evict_invalid_tickets(user_manager, appstate.max_ticket_ttl_seconds);
-            let roles_tickets_fut = evict_invalid_tickets(role_manager, appstate.max_ticket_ttl_seconds);
+/// an IAM client acting as the worker role of the account
+async fn worker_iam_client(appstate: &AppState, account_id: &str) -> Arc<aws_sdk_iam::Client> {
+    let role_arn = format!(
+        "arn:aws:iam::{account}:role/{path}/{name}",
+        account = account_id,
+        path = appstate.role_path,
+        name = appstate.role_name,
+    );
+    let provider = AssumeRoleProviderBuilder::new(role_arn).build().await;
 
-            let (users_tickets, roles_tickets) = future::try_join(users_tickets_fut, roles_tickets_fut).await?;
-            return Ok(Response::EvictionSummary {
-                users: users_tickets,
-                roles: roles_tickets,
-            });
+    let config = appstate
+        .sdk_config
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(provider))
+        .build();
+    Arc::new(aws_sdk_iam::Client::new(&config))
+}
+
+/// untags a ticket as soon as it is set, if it breaks policy, instead of waiting for the next sweep.
+/// the eviction of valid tickets is scheduled at their expiry.
+async fn validate_tagged_ticket(
+    appstate: &AppState,
+    lambda_arn: &str,
+    tagging: TicketTagging,
+) -> anyhow::Result<Response> {
+    let iam_client = worker_iam_client(appstate, &tagging.account_id).await;
+    let schema = appstate.tag_schema.clone();
+    match tagging.kind {
+        PrincipalKind::Role => {
+            let manager = RoleApprovalManager::new(iam_client)
+                .with_schema(schema)
+                .with_account(&tagging.account_id);
+            validate_with(appstate, lambda_arn, &manager, tagging).await
+        }
+        PrincipalKind::User => {
+            let manager = UserApprovalManager::new(iam_client)
+                .with_schema(schema)
+                .with_account(&tagging.account_id);
+            validate_with(appstate, lambda_arn, &manager, tagging).await
+        }
+    }
+}
+
+/// validates the tagged ticket with the manager of the kind of the tagged principal,
+/// whose ARN is resolved first as the event only names it
+async fn validate_with<T: ApprovalManager>(
+    appstate: &AppState,
+    lambda_arn: &str,
+    manager: &T,
+    tagging: TicketTagging,
+) -> anyhow::Result<Response> {
+    let principal = match manager.principal(&tagging.name).await {
+        Ok(principal) => principal,
+        Err(ListPrincipalsError::NotFound { .. }) => {
+            tracing::info!(msg = "tagged principal is gone", account_id = %tagging.account_id, name = %tagging.name);
+            return Ok(Response::NoTicketTagged {});
+        }
+        Err(e) => return Err(anyhow::Error::from(e).context("resolving the tagged principal")),
+    };
+    let ticket = tagging.value.parse::<ApprovalTicket>().ok();
+    let reason = match &ticket {
+        Some(ticket) => ticket_violation(
+            ticket,
+            appstate.max_ticket_ttl_seconds,
+            appstate.trusted_giver_keys.as_ref(),
+        ),
+        None => Some(EvictionReason::Malformed),
+    };
+    let mode = ModeFlags::default().mode(appstate.default_flags);
+    let mut scheduled = None;
+    let outcome = match (reason, ticket) {
+        (None, Some(ticket)) => {
+            if mode == Mode::Enforce {
+                // the ticket is valid whether or not its eviction is scheduled, the sweep evicts it otherwise
+                scheduled = schedule_expiry(appstate, lambda_arn, &tagging.account_id, &principal, &ticket)
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::error!(msg = "scheduling ticket expiry", error = %e, principal = %principal);
+                        None
+                    });
+            }
+            ValidationOutcome::Valid
+        }
+        (None, None) => unreachable!("unparsed tickets are malformed"),
+        (Some(reason), _) if mode != Mode::Enforce => {
+            if mode == Mode::ReportOnly {
+                tracing::warn!(
+                    msg = "ticket breaks policy",
+                    account_id = %tagging.account_id,
+                    principal = %principal,
+                    ticket = %tagging.value,
+                    reason = ?reason,
+                );
+            }
+            ValidationOutcome::Flagged(reason)
+        }
+        (Some(reason), _) => {
+            if evict_if_current(manager, &principal, &tagging.key, &tagging.value).await? {
+                ValidationOutcome::Evicted(reason)
+            } else {
+                ValidationOutcome::Superseded(reason)
+            }
         }
+    };
+    tracing::info!(
+        msg = "validated tagged ticket",
+        account_id = %tagging.account_id,
+        principal = %principal,
+        outcome = ?outcome,
+    );
+    Ok(Response::TicketValidation {
+        mode,
+        account_id: tagging.account_id,
+        principal,
+        ticket: tagging.value,
+        outcome,
+        scheduled,
+    })
+}
+
+/// schedules the eviction of the ticket at its expiry, unless one-time schedules are disabled.
+/// tickets without id or expiry cannot be targeted, and are left to the sweep.
+async fn schedule_expiry(
+    appstate: &AppState,
+    lambda_arn: &str,
+    account_id: &str,
+    principal: &IamPrincipal,
+    ticket: &ApprovalTicket,
+) -> anyhow::Result<Option<Scheduled>> {
+    let (Some(schedules), Some(ticket_id), Some(expires_at)) = (&appstate.schedules, ticket.id(), ticket.expires_at())
+    else {
+        return Ok(None);
+    };
+    let request = Request::EvictPrincipalTicket {
+        account_id: account_id.to_string(),
+        principal: ScheduledPrincipal::Arn(principal.clone()),
+        ticket_id: ticket_id.to_string(),
+        expires_at: Some(expires_at),
+        // the mode is that of the environment at the expiry
+        flags: ModeFlags::default(),
+    };
+    // schedules are named after the ticket and its expiry, so that observing it again does not schedule it twice,
+    // while extending it schedules its new expiry
+    let Some(name) = eviction_schedule_name(account_id, ticket_id, expires_at) else {
+        tracing::warn!(msg = "ticket id cannot name a schedule, leaving it to the sweep", ticket_id = %ticket_id);
+        return Ok(None);
+    };
+    let at = expires_at + Duration::seconds(1);
+    Ok(Some(schedules.create(&name, at, lambda_arn, &request).await?))
+}
+
+/// whether the principal still holds the ticket with id `ticket_id`, expired at `now`, which is unset in enforce mode.
+/// a ticket extended since its eviction was scheduled keeps its id, and is left to the schedule of its new expiry.
+async fn evict_ticket_by_id<T: ApprovalManager>(
+    manager: &T,
+    principal: &IamPrincipal,
+    ticket_id: &str,
+    now: DateTime<Utc>,
+    mode: Mode,
+) -> anyhow::Result<bool> {
+    let current = manager.get_ticket(principal).await?;
+    let Some(current) = current.filter(|ticket| ticket.id() == Some(ticket_id)) else {
+        return Ok(false);
+    };
+    if current.expires_at().is_some_and(|expiry| expiry > now) {
+        tracing::info!(msg = "ticket extended since its eviction was scheduled", principal = %principal, ticket_id = %ticket_id);
+        return Ok(false);
     }
+    if mode == Mode::Enforce {
+        manager.unset_ticket(principal).await?;
+    }
+    Ok(true)
+}
+
+/// unsets the ticket of the principal, unless it was replaced since `tagged` was tagged under `key`.
+/// malformed values are compared as they are and only their key is untagged, tickets are compared as parsed.
+async fn evict_if_current<T: ApprovalManager>(
+    manager: &T,
+    principal: &IamPrincipal,
+    key: &str,
+    tagged: &str,
+) -> anyhow::Result<bool> {
+    let Ok(tagged) = tagged.parse::<ApprovalTicket>() else {
+        return Ok(manager.unset_malformed_ticket(principal, key, tagged).await?);
+    };
+    if manager.get_ticket(principal).await?.as_ref() != Some(&tagged) {
+        return Ok(false);
+    }
+    manager.unset_ticket(principal).await?;
+    Ok(true)
 }
 
-async fn schedule_eviction(client: &aws_sdk_lambda::Client, account_id: &str, lambda_arn: &str) -> anyhow::Result<()> {
+async fn schedule_eviction(
+    client: &aws_sdk_lambda::Client,
+    account_id: &str,
+    lambda_arn: &str,
+    mode: Mode,
+    sweep_id: Option<&str>,
+) -> anyhow::Result<()> {
     let payload = Request::EvictStaleApprovals {
         account_id: account_id.to_string(),
+        flags: ModeFlags::of(mode),
+        sweep_id: sweep_id.map(str::to_string),
     };
 
-    let _ = client
+    client
         .invoke()
         .function_name(lambda_arn)
         .invocation_type(InvocationType::Event)
         .payload(Blob::new(serde_json::to_vec(&payload)?))
         .send()
         .await
-        .expect("failed to invoke lambda");
+        .map_err(ApiError::from_sdk)
+        .context("invoking the lambda")?;
 
     Ok(())
 }
 
-async fn evict_invalid_tickets<T: ApprovalManager>(
-    manager: T,
-    max_ttl: chrono::Duration,
-) -> anyhow::Result<Vec<(String, ApprovalTicket)>> {
-    let evicted = manager
-        .list_all_tickets()
-        .inspect_err(|e| tracing::error!(msg = "listing account tickets", error = %e))
-        .filter_map(|result| async {
-            match result {
-                Ok((principal, ticket)) if is_evictable(&ticket, max_ttl) => Some((principal, ticket)),
-                _ => None,
+/// the tickets of the principals, the ticket tags which do not parse, and the errors listing them
+async fn list_tickets<T: ApprovalManager>(
+    manager: &T,
+    principals: &'static str,
+) -> (
+    Vec<(IamPrincipal, ApprovalTicket)>,
+    Vec<(IamPrincipal, MalformedTicket)>,
+    Vec<ListingError>,
+) {
+    let results: Vec<_> = manager.list_all_tickets().collect().await;
+    let mut tickets = vec![];
+    let mut malformed = vec![];
+    let mut errors = vec![];
+    for result in results {
+        match result {
+            Ok((principal, TicketTag::Valid(ticket))) => tickets.push((principal, ticket)),
+            Ok((principal, TicketTag::Malformed(tag))) => malformed.push((principal, tag)),
+            Err(e) => {
+                tracing::error!(msg = "listing account tickets", principals = principals, error = %e);
+                errors.push(ListingError {
+                    principals: principals.to_string(),
+                    error: e.to_string(),
+                });
             }
-        })
-        .map(|(principal, ticket)| async {
-            if let Err(e) = manager.unset_ticket(&principal).await {
-                tracing::error!(msg = "unset ticket", error = %e, principal = %principal, ticket = ?ticket)
+        }
+    }
+    (tickets, malformed, errors)
+}
+
+/// unsets the flagged tickets in enforce mode, a derived ticket is flagged once its chain is broken.
+/// the value of a malformed ticket tag is logged first, and kept in the summary as the forensic record.
+async fn evict_flagged<T: ApprovalManager>(
+    manager: &T,
+    flagged: Vec<FlaggedTicket>,
+    mode: Mode,
+    concurrency: usize,
+) -> Vec<(FlaggedTicket, EvictionOutcome)> {
+    stream::iter(flagged)
+        .map(|flagged| async move {
+            let principal = &flagged.principal;
+            match (&flagged.tag, mode) {
+                (_, Mode::DryRun) => {}
+                (FlaggedTag::Malformed { malformed }, _) => {
+                    tracing::warn!(msg = "malformed ticket", principal = %principal, key = %malformed.key, value = %malformed.value, error = %malformed.error, mode = ?mode)
+                }
+                (FlaggedTag::Ticket { ticket }, Mode::ReportOnly) => {
+                    tracing::warn!(msg = "ticket breaks policy", principal = %principal, ticket = %ticket, reason = ?flagged.reason)
+                }
+                (FlaggedTag::Ticket { .. }, Mode::Enforce) => {}
+            }
+            if mode != Mode::Enforce {
+                return (flagged, EvictionOutcome::Skipped);
+            }
+            // a malformed value is untagged alone, a valid ticket under another key stays in place
+            let unset = match &flagged.tag {
+                FlaggedTag::Malformed { malformed } => manager
+                    .unset_malformed_ticket(principal, &malformed.key, &malformed.value)
+                    .await
+                    .map(|_| ()),
+                FlaggedTag::Ticket { .. } => manager.unset_ticket(principal).await,
+            };
+            let outcome = match unset {
+                Ok(()) => EvictionOutcome::Evicted,
+                Err(e) => {
+                    tracing::error!(msg = "unset ticket", error = %e, principal = %principal, tag = ?flagged.tag);
+                    EvictionOutcome::Failed(e.to_string())
+                }
             };
-            (principal, ticket)
+            (flagged, outcome)
         })
-        .buffer_unordered(4)
+        .buffer_unordered(concurrency)
         .collect()
-        .await;
+        .await
+}
 
-    Ok(evicted)
+/// the tickets breaking policy or whose chain is broken, and the valid tickets.
+/// a derived ticket whose parent is unknown is only flagged after a `complete` listing.
+fn flag_tickets(
+    tickets: Vec<(IamPrincipal, ApprovalTicket)>,
+    complete: bool,
+    max_ttl: chrono::Duration,
+    keyring: Option<&Keyring>,
+) -> (Vec<FlaggedTicket>, Vec<(IamPrincipal, ApprovalTicket)>) {
+    let evicted: HashSet<&str> = tickets
+        .iter()
+        .filter(|(_, ticket)| ticket_violation(ticket, max_ttl, keyring).is_some())
+        .filter_map(|(_, ticket)| ticket.id())
+        .collect();
+    let broken = broken_chains(tickets.iter().map(|(_, t)| t), &evicted);
+    let reason = |ticket: &ApprovalTicket| {
+        ticket_violation(ticket, max_ttl, keyring).or_else(|| {
+            ticket
+                .id()
+                .and_then(|id| broken.get(id))
+                .is_some_and(|chain_break| complete || *chain_break == ChainBreak::Broken)
+                .then_some(EvictionReason::BrokenChain)
+        })
+    };
+
+    let mut flagged = vec![];
+    let mut valid = vec![];
+    for (principal, ticket) in tickets {
+        match reason(&ticket) {
+            Some(reason) => flagged.push(FlaggedTicket {
+                principal,
+                tag: FlaggedTag::Ticket { ticket },
+                reason,
+            }),
+            None => valid.push((principal, ticket)),
+        }
+    }
+    (flagged, valid)
 }
 
-fn is_evictable(ticket: &ApprovalTicket, max_ttl: chrono::Duration) -> bool {
+/// why the ticket breaks policy, if it does. with a keyring, tickets must be signed by the key of their giver.
+fn ticket_violation(
+    ticket: &ApprovalTicket,
+    max_ttl: chrono::Duration,
+    keyring: Option<&Keyring>,
+) -> Option<EvictionReason> {
     let now = chrono::Utc::now();
     let ttl = ticket.expires_at().map(|ts| ts - now);
     match ttl {
-        Some(ttl) => ttl <= Duration::zero() || ttl >= max_ttl,
-        None => true,
+        None => Some(EvictionReason::NoExpiry),
+        Some(ttl) if ttl <= Duration::zero() => Some(EvictionReason::Expired),
+        Some(ttl) if ttl >= max_ttl => Some(EvictionReason::TooLong),
+        Some(_) if keyring.is_some_and(|keys| !is_trusted(ticket, keys)) => Some(EvictionReason::UntrustedGiver),
+        Some(_) => None,
     }
 }
 
-fn traverse_accounts_affected_by_policy<'a>(
-    client: &'a aws_sdk_organizations::Client,
-    policy_id: impl Into<String>,
-) -> impl Stream<Item = anyhow::Result<String>> + 'a {
-    let targets = client
-        .list_targets_for_policy()
-        .policy_id(policy_id)
-        .into_paginator()
-        .send()
-        .into_stream_03x()
-        .flat_map(|output| match output {
-            Ok(output) => stream::iter(
-                output
-                    .targets
-                    .unwrap_or_default()
-                    .into_iter()
-                    .map(Ok)
-                    .collect::<Vec<_>>(),
-            ),
-            Err(err) => stream::iter(vec![Err(err)]),
-        });
-
-    targets
-        .map_ok(|target| {
-            let Some(target_id) = target.target_id else {
-                return stream::empty().boxed();
-            };
-            match target.r#type {
-                Some(TargetType::Account) => stream::iter(vec![Ok(target_id)]).boxed(),
-                Some(TargetType::Root | TargetType::OrganizationalUnit) => {
-                    traverse_account_tree(client, target_id).boxed()
-                }
-                _ => {
-                    tracing::warn!(msg = "Unknown target type", target_id =% target_id);
-                    stream::empty().boxed()
-                }
-            }
-        })
-        .try_flatten()
+fn is_trusted(ticket: &ApprovalTicket, keyring: &Keyring) -> bool {
+    match keyring.verify(ticket) {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!(msg = "untrusted ticket", error = %e, ticket = %ticket);
+            false
+        }
+    }
 }
 
-fn traverse_account_tree<'a>(
-    client: &'a aws_sdk_organizations::Client,
-    target_id: String,
-) -> impl Stream<Item = Result<String, anyhow::Error>> + Send + 'a {
-    try_stream! {
-        let accounts = list_accounts_for_target(client, &target_id);
-        for await account in accounts {
-            yield account?;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheduled_principal() {
+        let legacy = r#"{"EvictPrincipalTicket": {"account_id": "111111111111", "principal": {"Role": "deployer"}, "ticket_id": "t1"}}"#;
+        let Request::EvictPrincipalTicket {
+            account_id, principal, ..
+        } = serde_json::from_str(legacy).unwrap()
+        else {
+            panic!("unexpected request");
+        };
+        assert_eq!(
+            principal.in_account(&account_id),
+            IamPrincipal::role("111111111111", "deployer")
+        );
+
+        let principal: IamPrincipal = "arn:aws:iam::111111111111:user/team/alice".parse().unwrap();
+        let request = Request::EvictPrincipalTicket {
+            account_id: "111111111111".to_string(),
+            principal: ScheduledPrincipal::Arn(principal.clone()),
+            ticket_id: "t1".to_string(),
+            expires_at: None,
+            flags: ModeFlags::default(),
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["EvictPrincipalTicket"]["principal"], principal.to_string());
+        let Request::EvictPrincipalTicket {
+            principal: scheduled, ..
+        } = serde_json::from_value(json).unwrap()
+        else {
+            panic!("unexpected request");
+        };
+        assert_eq!(scheduled.in_account("222222222222"), principal);
+    }
 
-        let org_units = list_org_units_for_target(client, &target_id);
-        for await ou in org_units {
-            let nested = traverse_account_tree(client, ou?).boxed();
-            for await account in nested {
-                yield account?;
+    #[test]
+    fn test_mode_flags() {
+        let request: Request =
+            serde_json::from_str(r#"{"EvictStaleApprovals": {"account_id": "111111111111"}}"#).unwrap();
+        let Request::EvictStaleApprovals { flags, .. } = request else {
+            panic!("unexpected request");
+        };
+        assert_eq!(flags, ModeFlags::default());
+        assert_eq!(flags.mode(ModeFlags::default()), Mode::Enforce);
+
+        let report_only = ModeFlags {
+            dry_run: None,
+            report_only: Some(true),
+        };
+        assert_eq!(flags.mode(report_only), Mode::ReportOnly);
+        // explicit flags override the environment
+        assert_eq!(ModeFlags::of(Mode::Enforce).mode(report_only), Mode::Enforce);
+        assert_eq!(
+            ModeFlags {
+                dry_run: Some(true),
+                report_only: None
             }
-        }
+            .mode(report_only),
+            Mode::DryRun
+        );
+
+        let request: Request = serde_json::from_str(r#"{"ScheduleApprovalEviction": {"dry_run": true}}"#).unwrap();
+        let Request::ScheduleApprovalEviction { flags } = request else {
+            panic!("unexpected request");
+        };
+        assert_eq!(flags.mode(ModeFlags::default()), Mode::DryRun);
+        assert_eq!(
+            serde_json::to_value(ModeFlags::of(Mode::DryRun)).unwrap(),
+            serde_json::json!({"dry_run": true, "report_only": false})
+        );
     }
-}
 
-fn list_accounts_for_target(
-    client: &aws_sdk_organizations::Client,
-    target_id: &str,
-) -> impl Stream<Item = Result<String, anyhow::Error>> {
-    let child_accounts = client
-        .list_children()
-        .parent_id(target_id)
-        .child_type(ChildType::Account)
-        .into_paginator()
-        .send()
-        .into_stream_03x()
-        .map_ok(|output| {
-            let account_ids = output
-                .children
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|child| child.id)
-                .map(Ok);
-            stream::iter(account_ids)
-        })
-        .try_flatten();
-    child_accounts
-}
+    #[tokio::test]
+    async fn test_evict_if_current() {
+        use approval::testing::{MockIam, ACCOUNT_ID};
 
-fn list_org_units_for_target(
-    client: &aws_sdk_organizations::Client,
-    target_id: &str,
-) -> impl Stream<Item = Result<String, anyhow::Error>> {
-    let org_units = client
-        .list_children()
-        .parent_id(target_id)
-        .child_type(ChildType::OrganizationalUnit)
-        .into_paginator()
-        .send()
-        .into_stream_03x()
-        .map_ok(|output| {
-            let ou_ids = output
-                .children
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|child| child.id)
-                .map(Ok);
-            stream::iter(ou_ids)
-        })
-        .try_flatten();
-    org_units
+        let (key, old_key) = ("tagctl:v2/admin/mpa/ticket", "tagctl:v1/admin/mpa/ticket");
+        let ticket = "by/alice/exp=1618033988/for/bob";
+        let mock = MockIam::new()
+            .with_role("malformed", &[(key, "garbage")])
+            .with_role("replaced", &[(key, ticket)])
+            .with_role("mixed", &[(key, ticket), (old_key, "garbage")]);
+        let schema = TagSchema::new("tagctl:v2".parse().unwrap())
+            .with_readable_versions([1])
+            .unwrap();
+        let manager = RoleApprovalManager::new(mock.client()).with_schema(schema);
+
+        let replaced = IamPrincipal::role(ACCOUNT_ID, "replaced");
+        assert!(!evict_if_current(&manager, &replaced, key, "garbage").await.unwrap());
+        assert!(
+            !evict_if_current(&manager, &replaced, key, "by/carol/exp=1618033988/for/bob")
+                .await
+                .unwrap()
+        );
+        assert_eq!(mock.role_tags("replaced").unwrap().len(), 1);
+
+        let malformed = IamPrincipal::role(ACCOUNT_ID, "malformed");
+        assert!(!evict_if_current(&manager, &malformed, key, "other garbage")
+            .await
+            .unwrap());
+        assert!(evict_if_current(&manager, &malformed, key, "garbage").await.unwrap());
+        assert!(mock.role_tags("malformed").unwrap().is_empty());
+        assert!(evict_if_current(&manager, &replaced, key, ticket).await.unwrap());
+
+        let mixed = IamPrincipal::role(ACCOUNT_ID, "mixed");
+        assert!(evict_if_current(&manager, &mixed, old_key, "garbage").await.unwrap());
+        assert_eq!(
+            mock.role_tags("mixed").unwrap(),
+            vec![(key.to_string(), ticket.to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_evict_extended_ticket() {
+        use approval::{
+            testing::{MockIam, ACCOUNT_ID},
+            ticket::HumanIdentity,
+        };
+
+        let key = "tagctl:v1/admin/mpa/ticket";
+        let mut ticket = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        let now = Utc::now();
+        ticket.set_expiry(now + Duration::minutes(10));
+        let expires_at = ticket.expires_at().unwrap();
+        let scheduled = eviction_schedule_name(ACCOUNT_ID, ticket.id().unwrap(), expires_at).unwrap();
+        ticket.extend(Duration::hours(1), Duration::hours(2)).unwrap();
+        let extended = eviction_schedule_name(ACCOUNT_ID, ticket.id().unwrap(), ticket.expires_at().unwrap());
+        assert_ne!(Some(scheduled), extended);
+
+        let mock = MockIam::new().with_role("admin", &[(key, &ticket.to_string())]);
+        let manager = RoleApprovalManager::new(mock.client());
+        let admin = IamPrincipal::role(ACCOUNT_ID, "admin");
+        let id = ticket.id().unwrap();
+
+        // the schedule of the original expiry fires, the extended ticket is kept
+        let at = expires_at + Duration::seconds(1);
+        assert!(!evict_ticket_by_id(&manager, &admin, id, at, Mode::Enforce)
+            .await
+            .unwrap());
+        assert_eq!(mock.role_tags("admin").unwrap().len(), 1);
+
+        // the schedule of the extended expiry evicts it
+        let at = ticket.expires_at().unwrap() + Duration::seconds(1);
+        assert!(!evict_ticket_by_id(&manager, &admin, "other", at, Mode::Enforce)
+            .await
+            .unwrap());
+        assert!(evict_ticket_by_id(&manager, &admin, id, at, Mode::Enforce)
+            .await
+            .unwrap());
+        assert!(mock.role_tags("admin").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flag_tickets_with_unlisted_parent() {
+        use approval::{
+            iam::Listing,
+            testing::{MockIam, ACCOUNT_ID},
+            ticket::HumanIdentity,
+        };
+
+        let key = "tagctl:v1/admin/mpa/ticket";
+        let expiry = chrono::Utc::now() + Duration::hours(1);
+        let mut parent = ApprovalTicket::new(HumanIdentity::new("alice"), HumanIdentity::new("bob"));
+        parent.set_expiry(expiry);
+        parent.set_chainable(true);
+        let child = parent.derive(HumanIdentity::new("carol"), expiry).unwrap();
+        let (parent, child) = (parent.to_string(), child.to_string());
+        let mock = MockIam::new()
+            .with_role("holder", &[(key, &parent)])
+            .with_role("child", &[(key, &child)])
+            .with_role("stale", &[(key, "by/dave/exp=1618033988/for/erin")])
+            .deny_principal("holder");
+        let manager = RoleApprovalManager::new(mock.client()).with_listing(Listing::PerPrincipal);
+
+        let (tickets, _, errors) = list_tickets(&manager, "roles").await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(tickets.len(), 2);
+        let (flagged, valid) = flag_tickets(tickets.clone(), errors.is_empty(), Duration::hours(8), None);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].principal, IamPrincipal::role(ACCOUNT_ID, "stale"));
+        assert_eq!(flagged[0].reason, EvictionReason::Expired);
+        assert_eq!(valid.len(), 1);
+        assert_eq!(valid[0].0, IamPrincipal::role(ACCOUNT_ID, "child"));
+
+        // after a complete listing, the parent is known to be gone
+        let (flagged, valid) = flag_tickets(tickets, true, Duration::hours(8), None);
+        assert_eq!(flagged.len(), 2);
+        assert!(flagged.iter().any(|f| f.reason == EvictionReason::BrokenChain));
+        assert!(valid.is_empty());
+    }
 }
@@ -0,0 +1,227 @@
+use anyhow::{bail, Context};
+use aws_credential_types::{provider::ProvideCredentials, Credentials};
+use aws_sigv4::{
+    http_request::{sign, SignableBody, SignableRequest, SigningSettings},
+    sign::v4,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::HttpsConnector;
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::{env::var, time::SystemTime};
+
+/// the signing name of EventBridge Scheduler
+const SERVICE: &str = "scheduler";
+/// schedule names are at most 64 letters, digits, `-`, `_` and `.`
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Scheduled {
+    Created,
+    /// a schedule with the same name exists, e.g. created by an earlier sweep
+    AlreadyScheduled,
+}
+
+/// creates one-time EventBridge Scheduler schedules, which invoke the lambda at a given time.
+/// the lambda has no SDK for EventBridge Scheduler, so the single `CreateSchedule` call is signed here, and sent over https only.
+pub(crate) struct OneTimeSchedules {
+    sdk_config: aws_config::SdkConfig,
+    http: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    group_name: String,
+    role_arn: String,
+}
+
+impl OneTimeSchedules {
+    /// reads the schedule group and the role the schedules invoke the lambda with from
+    /// `SCHEDULE_GROUP_NAME` and `SCHEDULER_ROLE_ARN`. one-time schedules are disabled unless both are set.
+    pub(crate) fn from_env(sdk_config: &aws_config::SdkConfig) -> anyhow::Result<Option<Self>> {
+        let (Ok(group_name), Ok(role_arn)) = (var("SCHEDULE_GROUP_NAME"), var("SCHEDULER_ROLE_ARN")) else {
+            return Ok(None);
+        };
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .context("loading the native root certificates")?
+            .https_only()
+            .enable_http1()
+            .build();
+        Ok(Some(Self {
+            sdk_config: sdk_config.clone(),
+            http: Client::builder(TokioExecutor::new()).build(connector),
+            group_name,
+            role_arn,
+        }))
+    }
+
+    /// creates a schedule named `name`, invoking `lambda_arn` with `payload` at `at`, and deleted once done
+    pub(crate) async fn create(
+        &self,
+        name: &str,
+        at: DateTime<Utc>,
+        lambda_arn: &str,
+        payload: &impl Serialize,
+    ) -> anyhow::Result<Scheduled> {
+        // the name is part of the path, so it is never sent unless it is a valid schedule name
+        if !is_valid_name(name) {
+            bail!("invalid schedule name {:?}", name);
+        }
+        let body = create_body(&self.group_name, &self.role_arn, at, lambda_arn, payload)?;
+        let region = self.sdk_config.region().context("no region configured")?.to_string();
+        let uri = format!("https://{}.{}.amazonaws.com/schedules/{}", SERVICE, region, name);
+        let credentials = self
+            .sdk_config
+            .credentials_provider()
+            .context("no credentials provider configured")?
+            .provide_credentials()
+            .await?;
+        let request = signed_request(
+            &credentials,
+            &region,
+            &uri,
+            serde_json::to_vec(&body)?,
+            SystemTime::now(),
+        )?;
+        let response = self.http.request(request).await?;
+        let status = response.status().as_u16();
+        let response = response.into_body().collect().await?.to_bytes();
+        match status {
+            200..=299 => Ok(Scheduled::Created),
+            409 => Ok(Scheduled::AlreadyScheduled),
+            _ => bail!(
+                "scheduler:CreateSchedule failed with status {}: {}",
+                status,
+                String::from_utf8_lossy(&response)
+            ),
+        }
+    }
+}
+
+/// a `POST` of the json `body` to `uri`, signed with SigV4 for EventBridge Scheduler in `region` at `time`
+fn signed_request(
+    credentials: &Credentials,
+    region: &str,
+    uri: &str,
+    body: Vec<u8>,
+    time: SystemTime,
+) -> anyhow::Result<http::Request<Full<Bytes>>> {
+    let mut request = http::Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Full::from(body.clone()))?;
+    let identity = credentials.clone().into();
+    let params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region)
+        .name(SERVICE)
+        .time(time)
+        .settings(SigningSettings::default())
+        .build()?
+        .into();
+    let signable = SignableRequest::new(
+        "POST",
+        uri,
+        [("content-type", "application/json")].into_iter(),
+        SignableBody::Bytes(&body),
+    )?;
+    let (instructions, _) = sign(signable, &params)?.into_parts();
+    instructions.apply_to_request_http1x(&mut request);
+    Ok(request)
+}
+
+/// the name of the schedule evicting a ticket at `expires_at`, unless the ticket id cannot be part of a schedule name.
+/// an extended ticket keeps its id, so the expiry tells its schedules apart.
+pub(crate) fn eviction_schedule_name(account_id: &str, ticket_id: &str, expires_at: DateTime<Utc>) -> Option<String> {
+    Some(format!("evict-{}-{}-{}", account_id, ticket_id, expires_at.timestamp())).filter(|name| is_valid_name(name))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+/// the body of `scheduler:CreateSchedule`
+fn create_body(
+    group_name: &str,
+    role_arn: &str,
+    at: DateTime<Utc>,
+    lambda_arn: &str,
+    payload: &impl Serialize,
+) -> anyhow::Result<serde_json::Value> {
+    Ok(json!({
+        "GroupName": group_name,
+        "ScheduleExpression": format!("at({})", at.format("%Y-%m-%dT%H:%M:%S")),
+        "ScheduleExpressionTimezone": "UTC",
+        "FlexibleTimeWindow": { "Mode": "OFF" },
+        "ActionAfterCompletion": "DELETE",
+        "Target": {
+            "Arn": lambda_arn,
+            "RoleArn": role_arn,
+            "Input": serde_json::to_string(payload)?,
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_body, eviction_schedule_name, signed_request};
+    use aws_credential_types::Credentials;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_eviction_schedule_name() {
+        let at = Utc.timestamp_opt(1_792_324_800, 0).unwrap();
+        assert_eq!(
+            eviction_schedule_name("111111111111", "a1B2-c3", at).as_deref(),
+            Some("evict-111111111111-a1B2-c3-1792324800")
+        );
+        assert_ne!(
+            eviction_schedule_name("111111111111", "a1B2-c3", at),
+            eviction_schedule_name("111111111111", "a1B2-c3", at + chrono::Duration::hours(1))
+        );
+        assert!(eviction_schedule_name("111111111111", "../../schedule-groups/default", at).is_none());
+        assert!(eviction_schedule_name("111111111111", "a?b", at).is_none());
+        assert!(eviction_schedule_name("111111111111", &"a".repeat(40), at).is_none());
+        assert!(eviction_schedule_name("111111111111", &"a".repeat(16), at).is_some());
+    }
+
+    #[test]
+    fn test_create_body() {
+        let at = Utc.with_ymd_and_hms(2026, 10, 18, 12, 30, 0).unwrap();
+        let body = create_body(
+            "tagctl",
+            "arn:aws:iam::1:role/s",
+            at,
+            "arn:aws:lambda:f",
+            &json!({"a": 1}),
+        )
+        .unwrap();
+        assert_eq!(body["ScheduleExpression"], "at(2026-10-18T12:30:00)");
+        assert_eq!(body["ActionAfterCompletion"], "DELETE");
+        assert_eq!(body["Target"]["Input"], r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn test_signed_request() {
+        let credentials = Credentials::new("AKIDMOCK", "secret", None, None, "test");
+        let time = UNIX_EPOCH + Duration::from_secs(1_792_324_800);
+        let uri = "https://scheduler.us-east-1.amazonaws.com/schedules/evict-111111111111-a1";
+        let request = signed_request(&credentials, "us-east-1", uri, b"{}".to_vec(), time).unwrap();
+        assert_eq!(request.method(), "POST");
+        assert_eq!(request.headers()["x-amz-date"], "20261018T120000Z");
+        let authorization = request.headers()["authorization"].to_str().unwrap();
+        assert!(
+            authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDMOCK/20261018/us-east-1/scheduler/aws4_request")
+        );
+        assert!(authorization.contains("SignedHeaders=content-type;host;x-amz-date"));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::Mode;
+use approval::{principal::IamPrincipal, retry::RetryStats, tags::MalformedTicket, ticket::ApprovalTicket};
+use serde::{Deserialize, Serialize};
+
+/// why a ticket breaks policy
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EvictionReason {
+    Expired,
+    TooLong,
+    NoExpiry,
+    Malformed,
+    UntrustedGiver,
+    /// an ancestor of the derived ticket is gone or breaks policy
+    BrokenChain,
+}
+
+/// the ticket tag of a flagged principal
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum FlaggedTag {
+    Ticket {
+        ticket: ApprovalTicket,
+    },
+    /// the value under a ticket key which does not parse, kept here as the forensic record once it is untagged.
+    /// the summary of a sweep is persisted with the account report.
+    Malformed {
+        malformed: MalformedTicket,
+    },
+}
+
+/// a ticket breaking policy, and why
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct FlaggedTicket {
+    pub principal: IamPrincipal,
+    #[serde(flatten)]
+    pub tag: FlaggedTag,
+    pub reason: EvictionReason,
+}
+
+/// a flagged ticket which could not be untagged
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct FailedEviction {
+    #[serde(flatten)]
+    pub flagged: FlaggedTicket,
+    pub error: String,
+}
+
+/// principals whose tickets could not be listed, e.g. when the worker role cannot be assumed
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ListingError {
+    /// `users` or `roles`
+    pub principals: String,
+    pub error: String,
+}
+
+pub(crate) enum EvictionOutcome {
+    Evicted,
+    /// left in place in dry-run or report-only mode
+    Skipped,
+    Failed(String),
+}
+
+/// the tickets breaking policy in an account, and what became of them
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct EvictionSummary {
+    pub mode: Mode,
+    pub evicted: Vec<FlaggedTicket>,
+    pub failed: Vec<FailedEviction>,
+    pub skipped: Vec<FlaggedTicket>,
+    pub listing_errors: Vec<ListingError>,
+    /// the IAM calls made in the account, and how many were throttled and retried
+    #[serde(default)]
+    pub retries: RetryStats,
+}
+
+impl EvictionSummary {
+    pub fn new(mode: Mode, listing_errors: Vec<ListingError>) -> Self {
+        Self {
+            mode,
+            evicted: vec![],
+            failed: vec![],
+            skipped: vec![],
+            listing_errors,
+            retries: RetryStats::default(),
+        }
+    }
+
+    pub fn record(&mut self, flagged: FlaggedTicket, outcome: EvictionOutcome) {
+        match outcome {
+            EvictionOutcome::Evicted => self.evicted.push(flagged),
+            EvictionOutcome::Skipped => self.skipped.push(flagged),
+            EvictionOutcome::Failed(error) => self.failed.push(FailedEviction { flagged, error }),
+        }
+    }
+
+    /// whether some tickets could not be listed or untagged, so that the sweep of the account should be retried
+    pub fn is_partial(&self) -> bool {
+        !self.failed.is_empty() || !self.listing_errors.is_empty()
+    }
+}
+
+/// an account whose sweep could not be requested, or a failure to traverse the organization
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct AccountFailure {
+    pub account_id: Option<String>,
+    pub error: String,
+}
+
+/// the accounts a sweep was requested for, and those it could not be
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SchedulingSummary {
+    pub scheduled: Vec<String>,
+    pub failed: Vec<AccountFailure>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let ticket: ApprovalTicket = "by/alice/exp=1618033988/for/bob".parse().unwrap();
+        let mut summary = EvictionSummary::new(Mode::Enforce, vec![]);
+        summary.record(
+            FlaggedTicket {
+                principal: IamPrincipal::role("123456789012", "admin"),
+                tag: FlaggedTag::Ticket { ticket },
+                reason: EvictionReason::Expired,
+            },
+            EvictionOutcome::Evicted,
+        );
+        assert!(!summary.is_partial());
+
+        summary.record(
+            FlaggedTicket {
+                principal: IamPrincipal::user("123456789012", "carol"),
+                tag: FlaggedTag::Malformed {
+                    malformed: MalformedTicket {
+                        key: "tagctl:v1/admin/mpa/ticket".to_string(),
+                        value: "garbage".to_string(),
+                        error: "cannot parse giver".to_string(),
+                    },
+                },
+                reason: EvictionReason::Malformed,
+            },
+            EvictionOutcome::Failed("access denied".to_string()),
+        );
+        assert!(summary.is_partial());
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["evicted"][0]["reason"], "expired");
+        assert_eq!(json["evicted"][0]["ticket"]["giver"], "alice");
+        assert_eq!(json["failed"][0]["malformed"]["value"], "garbage");
+        assert_eq!(json["failed"][0]["malformed"]["error"], "cannot parse giver");
+        assert_eq!(json["failed"][0]["reason"], "malformed");
+        assert_eq!(json["failed"][0]["error"], "access denied");
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    summary::{AccountFailure, EvictionReason, EvictionSummary},
+    Mode,
+};
+use anyhow::{bail, Context};
+use approval::error::ApiError;
+use aws_sdk_s3::primitives::ByteStream;
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, env::var};
+
+/// the key of the manifest of the latest sweep
+const LATEST_KEY: &str = "latest.json";
+
+/// where the accounts of a sweep report their summary: a bucket of S3, or of an S3-compatible store
+pub(crate) struct ResultSink {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    prefix: String,
+}
+
+impl ResultSink {
+    /// reads `SWEEP_RESULTS_URL`, as `s3://<bucket>/<prefix>`.
+    /// `SWEEP_RESULTS_ENDPOINT` selects an S3-compatible store, whose buckets are addressed in path style.
+    /// sweep results are not collected unless the url is set.
+    pub(crate) fn from_env(sdk_config: &aws_config::SdkConfig) -> anyhow::Result<Option<Self>> {
+        let url = match var("SWEEP_RESULTS_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let Some(location) = url.strip_prefix("s3://") else {
+            bail!("SWEEP_RESULTS_URL must start with s3://, got {url}");
+        };
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            bail!("SWEEP_RESULTS_URL has no bucket: {url}");
+        }
+        let mut config = aws_sdk_s3::config::Builder::from(sdk_config);
+        if let Ok(endpoint) = var("SWEEP_RESULTS_ENDPOINT") {
+            if !endpoint.is_empty() {
+                config = config.endpoint_url(endpoint).force_path_style(true);
+            }
+        }
+        let client = aws_sdk_s3::Client::from_conf(config.build());
+        Ok(Some(Self::new(client, bucket, prefix)))
+    }
+
+    pub(crate) fn new(client: aws_sdk_s3::Client, bucket: &str, prefix: &str) -> Self {
+        Self {
+            client,
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        }
+    }
+
+    pub(crate) async fn put(&self, key: &str, value: &impl Serialize) -> anyhow::Result<()> {
+        let key = self.object_key(key);
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .content_type("application/json")
+            .body(ByteStream::from(serde_json::to_vec_pretty(value)?))
+            .send()
+            .await
+            .map_err(ApiError::from_sdk)
+            .with_context(|| format!("s3:PutObject {key}"))?;
+        Ok(())
+    }
+
+    /// the value at `key`, if any
+    pub(crate) async fn get<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        let key = self.object_key(key);
+        let output = match self.client.get_object().bucket(&self.bucket).key(&key).send().await {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+            Err(e) => return Err(ApiError::from_sdk(e)).with_context(|| format!("s3:GetObject {key}")),
+        };
+        let body = output
+            .body
+            .collect()
+            .await
+            .with_context(|| format!("reading {key}"))?
+            .into_bytes();
+        Ok(Some(
+            serde_json::from_slice(&body).with_context(|| format!("parsing {key}"))?,
+        ))
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", self.prefix, key)
+        }
+    }
+
+    /// records the accounts a sweep was requested for, as the latest sweep
+    pub(crate) async fn start(&self, manifest: &SweepManifest) -> anyhow::Result<()> {
+        self.put(&manifest_key(&manifest.sweep_id), manifest).await?;
+        self.put(LATEST_KEY, manifest).await
+    }
+
+    pub(crate) async fn report_account(&self, sweep_id: &str, report: &AccountReport) -> anyhow::Result<()> {
+        self.put(&account_key(sweep_id, &report.account_id), report).await
+    }
+
+    /// the report of the sweep, or of the latest one
+    pub(crate) async fn sweep_report(&self, sweep_id: Option<&str>) -> anyhow::Result<SweepReport> {
+        let key = sweep_id.map(manifest_key).unwrap_or_else(|| LATEST_KEY.to_string());
+        let manifest: SweepManifest = self
+            .get(&key)
+            .await?
+            .with_context(|| format!("no sweep manifest at {key}"))?;
+        let keys = manifest
+            .scheduled
+            .iter()
+            .map(|account_id| account_key(&manifest.sweep_id, account_id))
+            .collect::<Vec<_>>();
+        let reports: Vec<Option<AccountReport>> = stream::iter(&keys)
+            .map(|key| self.get(key))
+            .buffered(16)
+            .try_collect()
+            .await?;
+        Ok(SweepReport::aggregate(manifest, reports.into_iter().flatten()))
+    }
+}
+
+fn manifest_key(sweep_id: &str) -> String {
+    format!("sweeps/{}/manifest.json", sweep_id)
+}
+
+fn account_key(sweep_id: &str, account_id: &str) -> String {
+    format!("sweeps/{}/accounts/{}.json", sweep_id, account_id)
+}
+
+/// the accounts a sweep was requested for, and those it could not be
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SweepManifest {
+    pub sweep_id: String,
+    pub started_at: DateTime<Utc>,
+    pub mode: Mode,
+    pub scheduled: Vec<String>,
+    pub failed: Vec<AccountFailure>,
+}
+
+/// the summary an account reports to the sweep
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct AccountReport {
+    pub account_id: String,
+    pub reported_at: DateTime<Utc>,
+    pub summary: EvictionSummary,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub(crate) struct SweepTotals {
+    pub accounts: usize,
+    pub reported: usize,
+    pub evicted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub listing_errors: usize,
+    /// IAM calls retried across the accounts, and those throttled
+    pub retries: u64,
+    pub throttled: u64,
+    /// the flagged tickets by reason, whatever became of them
+    pub reasons: BTreeMap<EvictionReason, usize>,
+}
+
+/// what became of the tickets flagged in an account
+#[derive(Serialize, Debug)]
+pub(crate) struct AccountTotals {
+    pub account_id: String,
+    pub reported_at: DateTime<Utc>,
+    pub evicted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub listing_errors: usize,
+}
+
+/// the org-wide outcome of a sweep, from the summaries the accounts reported
+#[derive(Serialize, Debug)]
+pub(crate) struct SweepReport {
+    pub sweep_id: String,
+    pub started_at: DateTime<Utc>,
+    pub mode: Mode,
+    pub totals: SweepTotals,
+    /// accounts which never reported back, e.g. still running or out of retries
+    pub missing: Vec<String>,
+    /// accounts whose last attempt had failures
+    pub partial: Vec<String>,
+    /// accounts whose sweep could not be requested
+    pub unscheduled: Vec<AccountFailure>,
+    pub accounts: Vec<AccountTotals>,
+}
+
+impl SweepReport {
+    pub(crate) fn aggregate(manifest: SweepManifest, reports: impl IntoIterator<Item = AccountReport>) -> Self {
+        let mut reports: BTreeMap<String, AccountReport> = reports
+            .into_iter()
+            .map(|report| (report.account_id.clone(), report))
+            .collect();
+        let mut totals = SweepTotals {
+            accounts: manifest.scheduled.len(),
+            ..Default::default()
+        };
+        let (mut missing, mut partial, mut accounts) = (vec![], vec![], vec![]);
+        for account_id in manifest.scheduled {
+            let Some(AccountReport {
+                account_id,
+                reported_at,
+                summary,
+            }) = reports.remove(&account_id)
+            else {
+                missing.push(account_id);
+                continue;
+            };
+            totals.reported += 1;
+            totals.evicted += summary.evicted.len();
+            totals.failed += summary.failed.len();
+            totals.skipped += summary.skipped.len();
+            totals.listing_errors += summary.listing_errors.len();
+            totals.retries += summary.retries.retries;
+            totals.throttled += summary.retries.throttled;
+            let reasons = summary
+                .evicted
+                .iter()
+                .chain(&summary.skipped)
+                .chain(summary.failed.iter().map(|failed| &failed.flagged))
+                .map(|flagged| flagged.reason);
+            for reason in reasons {
+                *totals.reasons.entry(reason).or_default() += 1;
+            }
+            if summary.is_partial() {
+                partial.push(account_id.clone());
+            }
+            accounts.push(AccountTotals {
+                account_id,
+                reported_at,
+                evicted: summary.evicted.len(),
+                failed: summary.failed.len(),
+                skipped: summary.skipped.len(),
+                listing_errors: summary.listing_errors.len(),
+            });
+        }
+        Self {
+            sweep_id: manifest.sweep_id,
+            started_at: manifest.started_at,
+            mode: manifest.mode,
+            totals,
+            missing,
+            partial,
+            unscheduled: manifest.failed,
+            accounts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::{EvictionOutcome, FlaggedTag, FlaggedTicket};
+    use approval::principal::IamPrincipal;
+    use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+    use aws_smithy_runtime_api::{
+        client::{
+            http::{HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector},
+            orchestrator::{HttpRequest, HttpResponse},
+            runtime_components::RuntimeComponents,
+        },
+        http::StatusCode,
+    };
+    use aws_smithy_types::body::SdkBody;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    /// an S3-compatible store keeping objects by path in memory
+    #[derive(Debug, Clone, Default)]
+    struct MockS3 {
+        objects: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    }
+
+    impl HttpConnector for MockS3 {
+        fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+            let path = request
+                .uri()
+                .parse::<http::Uri>()
+                .expect("valid uri")
+                .path()
+                .to_string();
+            let mut objects = self.objects.lock().unwrap();
+            let (status, body) = match request.method() {
+                "PUT" => {
+                    objects.insert(path, request.body().bytes().unwrap_or_default().to_vec());
+                    (200, vec![])
+                }
+                "GET" => match objects.get(&path) {
+                    Some(body) => (200, body.clone()),
+                    None => (
+                        404,
+                        b"<Error><Code>NoSuchKey</Code><Message>not found</Message></Error>".to_vec(),
+                    ),
+                },
+                _ => (405, vec![]),
+            };
+            HttpConnectorFuture::ready(Ok(HttpResponse::new(
+                StatusCode::try_from(status).expect("valid status"),
+                SdkBody::from(body),
+            )))
+        }
+    }
+
+    impl HttpClient for MockS3 {
+        fn http_connector(&self, _: &HttpConnectorSettings, _: &RuntimeComponents) -> SharedHttpConnector {
+            SharedHttpConnector::new(self.clone())
+        }
+    }
+
+    fn flagged(reason: EvictionReason) -> FlaggedTicket {
+        FlaggedTicket {
+            principal: IamPrincipal::role("123456789012", "admin"),
+            tag: FlaggedTag::Ticket {
+                ticket: "by/alice/exp=1618033988/for/bob".parse().unwrap(),
+            },
+            reason,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sweep_report() {
+        let mock = MockS3::default();
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("AKIDMOCK", "secret", None, None, "mock"))
+            .endpoint_url("https://s3.mock")
+            .force_path_style(true)
+            .http_client(mock.clone())
+            .build();
+        let sink = ResultSink::new(aws_sdk_s3::Client::from_conf(config), "results", "/retention/");
+        let manifest = SweepManifest {
+            sweep_id: "20261018T120000Z".to_string(),
+            started_at: Utc::now(),
+            mode: Mode::Enforce,
+            scheduled: vec!["111111111111".to_string(), "222222222222".to_string()],
+            failed: vec![AccountFailure {
+                account_id: Some("333333333333".to_string()),
+                error: "throttled".to_string(),
+            }],
+        };
+        sink.start(&manifest).await.unwrap();
+
+        let mut summary = EvictionSummary::new(Mode::Enforce, vec![]);
+        summary.record(flagged(EvictionReason::Expired), EvictionOutcome::Evicted);
+        summary.record(
+            flagged(EvictionReason::TooLong),
+            EvictionOutcome::Failed("access denied".to_string()),
+        );
+        let report = AccountReport {
+            account_id: "111111111111".to_string(),
+            reported_at: Utc::now(),
+            summary,
+        };
+        sink.report_account(&manifest.sweep_id, &report).await.unwrap();
+
+        let report = sink.sweep_report(None).await.unwrap();
+        assert_eq!(report.sweep_id, "20261018T120000Z");
+        assert_eq!(report.totals.accounts, 2);
+        assert_eq!(report.totals.reported, 1);
+        assert_eq!(report.totals.evicted, 1);
+        assert_eq!(report.totals.failed, 1);
+        assert_eq!(report.totals.reasons[&EvictionReason::TooLong], 1);
+        assert_eq!(report.missing, vec!["222222222222"]);
+        assert_eq!(report.partial, vec!["111111111111"]);
+        assert_eq!(report.unscheduled.len(), 1);
+
+        assert!(sink.sweep_report(Some("19700101T000000Z")).await.is_err());
+        assert!(mock
+            .objects
+            .lock()
+            .unwrap()
+            .contains_key("/results/retention/sweeps/20261018T120000Z/accounts/111111111111.json"));
+    }
+}
@@ -1,16 +1,22 @@
 # tagging related
 locals {
-  control_prefix = "tagctl:"
+  control_prefix = "${var.control_tag_namespace}:"
   disallowed_control_prefix_lookalikes = [for ch in split("", ".+=@_/-") :
-    "tagctl${ch}"
+    "${var.control_tag_namespace}${ch}"
   ]
-  control_v1 = "${local.control_prefix}v1"
+  control_versioned = "${local.control_prefix}v${var.control_tag_schema_version}"
 
-  grant_area_tag_key      = "${local.control_v1}/meta/grant_area"
-  identity_broker_tag_key = "${local.control_v1}/meta/id_broker"
-  mpa_tag_key             = "${local.control_v1}/admin/mpa"
+  grant_area_tag_key      = "${local.control_versioned}/meta/grant_area"
+  identity_broker_tag_key = "${local.control_versioned}/meta/id_broker"
+  mpa_tag_key             = "${local.control_versioned}/admin/mpa"
   approval_ticket_tag_key = "${local.mpa_tag_key}/ticket"
 
+  # tickets written with older schema versions, still read and evicted while accounts are migrated
+  readable_approval_ticket_tag_keys = concat([local.approval_ticket_tag_key], [
+    for version in var.readable_schema_versions : "${local.control_prefix}v${version}/admin/mpa/ticket"
+    if version != var.control_tag_schema_version
+  ])
+
   resource_seal_tag_key       = "${local.mpa_tag_key}/seal"
   resource_seal_kind_tag_key  = "${local.resource_seal_tag_key}/kind"
   resource_seal_grant_tag_key = "${local.resource_seal_tag_key}/grant"
@@ -2,3 +2,13 @@ output "control_tags_scp_id" {
   value       = aws_organizations_policy.control_tags.id
   description = "policy ID for the generated control tags policy"
 }
+
+output "retention_failures_queue_url" {
+  value       = aws_sqs_queue.retention_failures.url
+  description = "queue of the retention lambda invocations which failed after their retries, e.g. partial sweeps"
+}
+
+output "retention_results_bucket" {
+  value       = aws_s3_bucket.retention_results.id
+  description = "bucket the accounts of each retention sweep report their summary to"
+}
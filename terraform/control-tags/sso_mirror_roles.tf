@@ -108,7 +108,7 @@ resource "aws_cloudformation_stack_set" "mirror_role" {
               # place a grant area control tag on the mirror role, as specified by the permission set
               {
                 Key   = local.grant_area_tag_key,
-                Value = "${local.control_v1}/${var.sso_mirror_spec[each.value.permission_set_arn].grant_area_suffix}"
+                Value = "${local.control_versioned}/${var.sso_mirror_spec[each.value.permission_set_arn].grant_area_suffix}"
               }
             ]
           },
@@ -24,6 +24,40 @@ data "aws_iam_policy_document" "retention_manager" {
     actions   = ["lambda:InvokeFunction"]
     resources = [local.retention_lambda_arn]
   }
+  # allows the lambda to schedule the eviction of tickets at their expiry
+  statement {
+    sid       = "ScheduleTicketEviction"
+    effect    = "Allow"
+    actions   = ["scheduler:CreateSchedule"]
+    resources = ["arn:aws:scheduler:*:${local.account_id}:schedule/${local.retention_name_base}/*"]
+  }
+  statement {
+    sid       = "PassSchedulerRole"
+    effect    = "Allow"
+    actions   = ["iam:PassRole"]
+    resources = ["arn:aws:iam::${local.account_id}:role${local.retention_role.scheduler.path}${local.retention_role.scheduler.name}"]
+  }
+  # allows the lambda to record invocations which failed after their retries
+  statement {
+    sid       = "RecordFailedInvocations"
+    effect    = "Allow"
+    actions   = ["sqs:SendMessage"]
+    resources = [aws_sqs_queue.retention_failures.arn]
+  }
+  # allows the lambda to collect the summaries of the accounts of a sweep
+  statement {
+    sid       = "CollectSweepResults"
+    effect    = "Allow"
+    actions   = ["s3:PutObject", "s3:GetObject"]
+    resources = ["${aws_s3_bucket.retention_results.arn}/*"]
+  }
+  # tells missing summaries apart from denied reads
+  statement {
+    sid       = "ListSweepResults"
+    effect    = "Allow"
+    actions   = ["s3:ListBucket"]
+    resources = [aws_s3_bucket.retention_results.arn]
+  }
   # allows the lambda (in worker mode) to assume the worker role
   statement {
     sid       = "AssumeWorkerRole"
@@ -77,10 +111,37 @@ resource "aws_lambda_function" "retention" {
 
   environment {
     variables = {
-      "MAX_TICKET_TTL_SECONDS" = var.max_ticket_ttl_seconds
-      "WORKER_ROLE_NAME"       = local.retention_role.worker.name
-      "WORKER_ROLE_PATH"       = local.retention_role.worker.path
-      "CONTROL_TAGS_SCP_ID"    = aws_organizations_policy.control_tags.id
+      "MAX_TICKET_TTL_SECONDS"          = var.max_ticket_ttl_seconds
+      "WORKER_ROLE_NAME"                = local.retention_role.worker.name
+      "WORKER_ROLE_PATH"                = local.retention_role.worker.path
+      "CONTROL_TAGS_SCP_ID"             = aws_organizations_policy.control_tags.id
+      "TRUSTED_GIVER_KEYS"              = join(",", [for giver, key in var.trusted_giver_keys : "${giver}=${key}"])
+      "TAGCTL_NAMESPACE"                = local.control_versioned
+      "TAGCTL_READABLE_SCHEMA_VERSIONS" = join(",", var.readable_schema_versions)
+      "SCHEDULE_GROUP_NAME"             = aws_scheduler_schedule_group.retention.name
+      "SCHEDULER_ROLE_ARN"              = aws_iam_role.retention_scheduler.arn
+      "DRY_RUN"                         = var.retention_mode == "dry_run"
+      "REPORT_ONLY"                     = var.retention_mode == "report_only"
+      "SWEEP_RESULTS_URL"               = "s3://${aws_s3_bucket.retention_results.id}"
+      "IAM_CONCURRENCY"                 = var.retention_iam_concurrency
+    }
+  }
+}
+
+# partial sweeps fail the invocation with their summary, so that they are retried and eventually recorded here
+resource "aws_sqs_queue" "retention_failures" {
+  name                      = "${local.retention_name_base}-failures"
+  message_retention_seconds = 14 * 24 * 3600
+  sqs_managed_sse_enabled   = true
+}
+
+resource "aws_lambda_function_event_invoke_config" "retention" {
+  function_name          = aws_lambda_function.retention.function_name
+  maximum_retry_attempts = 2
+
+  destination_config {
+    on_failure {
+      destination = aws_sqs_queue.retention_failures.arn
     }
   }
 }
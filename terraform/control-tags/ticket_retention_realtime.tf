@@ -0,0 +1,159 @@
+locals {
+  realtime_count = var.realtime_ticket_validation ? 1 : 0
+
+  # successful TagRole and TagUser calls setting a ticket
+  ticket_tagging_event_pattern = jsonencode({
+    source      = ["aws.iam"]
+    detail-type = ["AWS API Call via CloudTrail"]
+    detail = {
+      eventSource = ["iam.amazonaws.com"]
+      eventName   = ["TagRole", "TagUser"]
+      errorCode   = [{ exists = false }]
+      requestParameters = {
+        tags = {
+          key = local.readable_approval_ticket_tag_keys
+        }
+      }
+    }
+  })
+}
+
+# receives the ticket tagging events of the organization's accounts
+resource "aws_cloudwatch_event_bus" "retention" {
+  count = local.realtime_count
+
+  name = local.retention_name_base
+}
+
+resource "aws_cloudwatch_event_bus_policy" "retention" {
+  count = local.realtime_count
+
+  event_bus_name = aws_cloudwatch_event_bus.retention[0].name
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Sid       = "OrganizationPutEvents"
+        Effect    = "Allow"
+        Principal = "*"
+        Action    = "events:PutEvents"
+        Resource  = aws_cloudwatch_event_bus.retention[0].arn
+        Condition = {
+          StringEquals = {
+            "aws:PrincipalOrgID" = data.aws_organizations_organization.main.id
+          }
+        }
+      }
+    ]
+  })
+}
+
+resource "aws_cloudwatch_event_rule" "retention_ticket_tagging" {
+  count = local.realtime_count
+
+  name           = "${local.retention_name_base}-ticket-tagging"
+  description    = "Validate approval tickets as soon as they are set"
+  event_bus_name = aws_cloudwatch_event_bus.retention[0].name
+  event_pattern  = local.ticket_tagging_event_pattern
+}
+
+resource "aws_cloudwatch_event_target" "retention_ticket_tagging" {
+  count = local.realtime_count
+
+  rule           = aws_cloudwatch_event_rule.retention_ticket_tagging[0].name
+  event_bus_name = aws_cloudwatch_event_bus.retention[0].name
+  arn            = aws_lambda_function.retention.arn
+}
+
+resource "aws_lambda_permission" "retention_ticket_tagging" {
+  count = local.realtime_count
+
+  statement_id  = "ValidateTaggedTickets"
+  action        = "lambda:InvokeFunction"
+  function_name = aws_lambda_function.retention.function_name
+  principal     = "events.amazonaws.com"
+  source_arn    = aws_cloudwatch_event_rule.retention_ticket_tagging[0].arn
+}
+
+# forwards the ticket tagging events of each account, which IAM only emits in us-east-1
+resource "aws_cloudformation_stack_set" "retention_realtime" {
+  count = local.realtime_count
+
+  name             = "${local.retention_name_base}-realtime"
+  permission_model = "SERVICE_MANAGED"
+  auto_deployment {
+    enabled                          = true
+    retain_stacks_on_account_removal = false
+  }
+  capabilities = ["CAPABILITY_NAMED_IAM"]
+  template_body = jsonencode({
+    Resources = {
+      ForwardingRole = {
+        Type = "AWS::IAM::Role"
+        Properties = {
+          RoleName = "${local.retention_name_base}-forwarder"
+          Path     = local.retention_role.worker.path
+          AssumeRolePolicyDocument = jsonencode({
+            Version = "2012-10-17"
+            Statement = [
+              {
+                Effect    = "Allow"
+                Principal = { Service = "events.amazonaws.com" }
+                Action    = "sts:AssumeRole"
+              }
+            ]
+          })
+          Policies = [
+            {
+              PolicyName = "${local.retention_name_base}-forwarder"
+              PolicyDocument = jsonencode({
+                Version = "2012-10-17"
+                Statement = [
+                  {
+                    Effect   = "Allow"
+                    Action   = "events:PutEvents"
+                    Resource = aws_cloudwatch_event_bus.retention[0].arn
+                  }
+                ]
+              })
+            }
+          ]
+        }
+      }
+      ForwardingRule = {
+        Type = "AWS::Events::Rule"
+        Properties = {
+          Name         = "${local.retention_name_base}-ticket-tagging"
+          Description  = "Forward approval ticket tagging to the retention lambda"
+          EventPattern = jsondecode(local.ticket_tagging_event_pattern)
+          Targets = [
+            {
+              Id      = "RetentionEventBus"
+              Arn     = aws_cloudwatch_event_bus.retention[0].arn
+              RoleArn = { "Fn::GetAtt" = ["ForwardingRole", "Arn"] }
+            }
+          ]
+        }
+      }
+    }
+  })
+
+  lifecycle {
+    # perpetual diff suppression, this value cannot be specifies as it conflicts with the auto_deployment block
+    ignore_changes = [administration_role_arn]
+  }
+}
+
+resource "aws_cloudformation_stack_set_instance" "retention_realtime" {
+  count = var.realtime_ticket_validation && length(values(var.deployment_targets)) > 0 ? 1 : 0
+
+  stack_set_name = aws_cloudformation_stack_set.retention_realtime[0].name
+  region         = "us-east-1"
+  dynamic "deployment_targets" {
+    for_each = length(local.dyn_deployment_targets) > 0 ? [null] : []
+    content {
+      accounts                = try(local.dyn_deployment_targets.account_ids, null)
+      organizational_unit_ids = try(local.dyn_deployment_targets.organizational_unit_ids, null)
+    }
+  }
+}
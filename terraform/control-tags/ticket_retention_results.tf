@@ -0,0 +1,30 @@
+# the accounts of each sweep report their summary to this bucket, aggregated by the `GetSweepReport` request
+resource "aws_s3_bucket" "retention_results" {
+  bucket_prefix = "${local.retention_name_base}-"
+  force_destroy = true
+}
+
+resource "aws_s3_bucket_public_access_block" "retention_results" {
+  bucket                  = aws_s3_bucket.retention_results.id
+  block_public_acls       = true
+  block_public_policy     = true
+  ignore_public_acls      = true
+  restrict_public_buckets = true
+}
+
+resource "aws_s3_bucket_lifecycle_configuration" "retention_results" {
+  bucket = aws_s3_bucket.retention_results.id
+
+  rule {
+    id     = "expire-sweeps"
+    status = "Enabled"
+
+    filter {
+      prefix = "sweeps/"
+    }
+
+    expiration {
+      days = var.sweep_results_retention_days
+    }
+  }
+}
@@ -11,6 +11,11 @@ data "aws_iam_policy_document" "retention_worker" {
       "iam:ListRoles",
       "iam:ListRoleTags",
       "iam:ListUserTags",
+      # reads the tags of all roles and users in a few pages
+      "iam:GetAccountAuthorizationDetails",
+      # resolves the ARN of a principal named by a tagging event
+      "iam:GetRole",
+      "iam:GetUser",
     ]
     resources = ["*"]
   }
@@ -25,7 +30,7 @@ data "aws_iam_policy_document" "retention_worker" {
     condition {
       test     = "ForAllValues:StringEquals"
       variable = "aws:TagKeys"
-      values   = [local.approval_ticket_tag_key]
+      values   = local.readable_approval_ticket_tag_keys
     }
   }
 }
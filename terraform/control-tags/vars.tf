@@ -60,6 +60,40 @@ variable "max_ticket_ttl_seconds" {
   }
 }
 
+variable "control_tag_namespace" {
+  default     = "tagctl"
+  description = "The root of the control tag keys, e.g. `tagctl` for `tagctl:v1/...`."
+  type        = string
+
+  validation {
+    condition     = can(regex("^[a-zA-Z0-9]+$", var.control_tag_namespace))
+    error_message = "The control_tag_namespace must be alphanumeric."
+  }
+}
+
+variable "control_tag_schema_version" {
+  default     = 1
+  description = "The schema version of the control tag keys, e.g. `1` for `tagctl:v1/...`."
+  type        = number
+
+  validation {
+    condition     = var.control_tag_schema_version >= 1 && floor(var.control_tag_schema_version) == var.control_tag_schema_version
+    error_message = "The control_tag_schema_version must be a positive integer."
+  }
+}
+
+variable "readable_schema_versions" {
+  default     = []
+  description = "Older schema versions whose approval tickets the retention lambda still reads and evicts, while accounts are migrated to `control_tag_schema_version`."
+  type        = list(number)
+}
+
+variable "trusted_giver_keys" {
+  default     = {}
+  description = "The public keys givers sign approval tickets with, by giver, as printed by `tagctl ticket keygen`. When set, the retention lambda evicts tickets which are not signed by the key of their giver."
+  type        = map(string)
+}
+
 variable "lambda_archive_file" {
   description = "The path to the lambda archive file."
   type        = string
@@ -131,3 +165,52 @@ variable "guarded_action_spec" {
     error_message = "Each spec must contain at least one deployment target."
   }
 }
+
+variable "sweep_results_retention_days" {
+  default     = 30
+  description = "The number of days the summaries the accounts report to each retention sweep are kept."
+  type        = number
+
+  validation {
+    condition     = var.sweep_results_retention_days > 0
+    error_message = "The sweep_results_retention_days must be greater than 0."
+  }
+}
+
+variable "retention_mode" {
+  default     = "enforce"
+  description = <<-EOT
+    How the retention lambda handles tickets breaking policy: `enforce` untags them, `report_only` logs them as warnings
+    and `dry_run` only returns them, both without any IAM change. Requests to the lambda may override the mode.
+  EOT
+  type        = string
+
+  validation {
+    condition     = contains(["enforce", "report_only", "dry_run"], var.retention_mode)
+    error_message = "The retention_mode must be one of enforce, report_only or dry_run."
+  }
+}
+
+variable "retention_iam_concurrency" {
+  default     = 4
+  description = <<-EOT
+    The number of principals the retention lambda lists or untags at once in an account.
+    Throttled IAM calls are retried with backoff, lower it if the sweep reports many of them.
+  EOT
+  type        = number
+
+  validation {
+    condition     = var.retention_iam_concurrency >= 1
+    error_message = "The retention_iam_concurrency must be at least 1."
+  }
+}
+
+variable "realtime_ticket_validation" {
+  default     = false
+  description = <<-EOT
+    Whether the retention lambda validates approval tickets as soon as they are set, instead of at the next scheduled sweep.
+    `TagRole` and `TagUser` CloudTrail events are forwarded from us-east-1 in each account of `deployment_targets`
+    to an event bus next to the lambda.
+  EOT
+  type        = bool
+}